@@ -5,6 +5,9 @@
 pub struct QueryParams {
     file: Vec<String>,
     package: Vec<String>,
+    /// results of processors with a `cache` header, see `fastn_core::library2022::cache`, the
+    /// name of a processor, eg `pg`, or `all`
+    processor: Vec<String>,
     all_dependencies: bool,
 }
 
@@ -32,6 +35,16 @@ fn query(uri: &str) -> fastn_core::Result<QueryParams> {
                 }
             })
             .collect_vec(),
+        processor: query
+            .iter()
+            .filter_map(|(key, value)| {
+                if key.eq("processor") {
+                    Some(value.to_string())
+                } else {
+                    None
+                }
+            })
+            .collect_vec(),
         all_dependencies: query
             .iter()
             .any(|(key, value)| key.eq("all-dependencies") && (value.eq("true") || value.eq("t"))),
@@ -69,6 +82,18 @@ pub async fn clear_(
     query: &QueryParams,
     _req: &fastn_core::http::Request,
 ) -> fastn_core::Result<()> {
+    for processor in query.processor.iter() {
+        fastn_core::library2022::cache::clear(processor);
+    }
+    // Processor results are the only thing to clear, nothing is downloaded again
+    if !query.processor.is_empty()
+        && query.file.is_empty()
+        && query.package.is_empty()
+        && !query.all_dependencies
+    {
+        return Ok(());
+    }
+
     if config.package.download_base_url.is_none() {
         return Err(fastn_core::Error::APIResponseError(
             "cannot remove anything, package does not have `download_base_url`".to_string(),
@@ -2,7 +2,7 @@ use crate::auth::email_password::key_expired;
 
 pub(crate) async fn confirm_email(
     req_config: &mut fastn_core::RequestConfig,
-    db_pool: &fastn_core::db::PgPool,
+    db_pool: &fastn_core::db::Pool,
     next: String,
 ) -> fastn_core::Result<fastn_core::http::Response> {
     use diesel::prelude::*;
@@ -31,16 +31,18 @@ pub(crate) async fn confirm_email(
         })?;
 
     let conf_data: Option<(i64, i64, chrono::DateTime<chrono::Utc>)> =
-        fastn_core::schema::fastn_email_confirmation::table
-            .select((
-                fastn_core::schema::fastn_email_confirmation::email_id,
-                fastn_core::schema::fastn_email_confirmation::session_id,
-                fastn_core::schema::fastn_email_confirmation::sent_at,
-            ))
-            .filter(fastn_core::schema::fastn_email_confirmation::key.eq(&code))
-            .first(&mut conn)
-            .await
-            .optional()?;
+        fastn_core::with_conn!(&mut conn, |c| {
+            fastn_core::schema::fastn_email_confirmation::table
+                .select((
+                    fastn_core::schema::fastn_email_confirmation::email_id,
+                    fastn_core::schema::fastn_email_confirmation::session_id,
+                    fastn_core::schema::fastn_email_confirmation::sent_at,
+                ))
+                .filter(fastn_core::schema::fastn_email_confirmation::key.eq(&code))
+                .first(c)
+                .await
+                .optional()
+        })?;
 
     if conf_data.is_none() {
         tracing::info!("invalid code value. No entry exists for the given code in db");
@@ -61,20 +63,23 @@ pub(crate) async fn confirm_email(
         )));
     }
 
-    let email: fastn_core::utils::CiString =
+    let email: fastn_core::utils::CiString = fastn_core::with_conn!(&mut conn, |c| {
         diesel::update(fastn_core::schema::fastn_user_email::table)
             .set(fastn_core::schema::fastn_user_email::verified.eq(true))
             .filter(fastn_core::schema::fastn_user_email::id.eq(email_id))
             .returning(fastn_core::schema::fastn_user_email::email)
-            .get_result(&mut conn)
-            .await?;
-
-    let user_id: i64 = diesel::update(fastn_core::schema::fastn_user::table)
-        .set(fastn_core::schema::fastn_user::verified_email.eq(true))
-        .filter(fastn_core::schema::fastn_user::email.eq(&email))
-        .returning(fastn_core::schema::fastn_user::id)
-        .get_result(&mut conn)
-        .await?;
+            .get_result(c)
+            .await
+    })?;
+
+    let user_id: i64 = fastn_core::with_conn!(&mut conn, |c| {
+        diesel::update(fastn_core::schema::fastn_user::table)
+            .set(fastn_core::schema::fastn_user::verified_email.eq(true))
+            .filter(fastn_core::schema::fastn_user::email.eq(&email))
+            .returning(fastn_core::schema::fastn_user::id)
+            .get_result(c)
+            .await
+    })?;
 
     // Onboarding step is opt-in
     let onboarding_enabled = req_config
@@ -96,14 +101,16 @@ pub(crate) async fn confirm_email(
     let now = chrono::Utc::now();
 
     // session always exists for new unverified user since it is created during `create-account`
-    let affected = diesel::update(fastn_core::schema::fastn_auth_session::table)
-        .set((
-            fastn_core::schema::fastn_auth_session::user_id.eq(&user_id),
-            fastn_core::schema::fastn_auth_session::updated_at.eq(&now),
-        ))
-        .filter(fastn_core::schema::fastn_auth_session::id.eq(session_id))
-        .execute(&mut conn)
-        .await?;
+    let affected = fastn_core::with_conn!(&mut conn, |c| {
+        diesel::update(fastn_core::schema::fastn_auth_session::table)
+            .set((
+                fastn_core::schema::fastn_auth_session::user_id.eq(&user_id),
+                fastn_core::schema::fastn_auth_session::updated_at.eq(&now),
+            ))
+            .filter(fastn_core::schema::fastn_auth_session::id.eq(session_id))
+            .execute(c)
+            .await
+    })?;
 
     tracing::info!("updated session. affected: {}", affected);
 
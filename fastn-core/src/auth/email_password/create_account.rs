@@ -27,11 +27,11 @@ struct UserPayload {
 
 pub(crate) async fn create_account(
     req_config: &mut fastn_core::RequestConfig,
-    db_pool: &fastn_core::db::PgPool,
+    db_pool: &fastn_core::db::Pool,
     next: String,
 ) -> fastn_core::Result<fastn_core::http::Response> {
     use diesel::prelude::*;
-    use diesel_async::RunQueryDsl;
+    use diesel_async::{AsyncConnection, RunQueryDsl};
     use validator::ValidateArgs;
 
     let now = chrono::Utc::now();
@@ -87,11 +87,13 @@ pub(crate) async fn create_account(
             message: format!("Failed to get connection to db. {:?}", e),
         })?;
 
-    let username_check: i64 = fastn_core::schema::fastn_user::table
-        .filter(fastn_core::schema::fastn_user::username.eq(&user_payload.username))
-        .select(diesel::dsl::count(fastn_core::schema::fastn_user::id))
-        .first(&mut conn)
-        .await?;
+    let username_check: i64 = fastn_core::with_conn!(&mut conn, |c| {
+        fastn_core::schema::fastn_user::table
+            .filter(fastn_core::schema::fastn_user::username.eq(&user_payload.username))
+            .select(diesel::dsl::count(fastn_core::schema::fastn_user::id))
+            .first(c)
+            .await
+    })?;
 
     if username_check > 0 {
         return fastn_core::http::user_err(
@@ -100,14 +102,16 @@ pub(crate) async fn create_account(
         );
     }
 
-    let email_check: i64 = fastn_core::schema::fastn_user::table
-        .filter(
-            fastn_core::schema::fastn_user::email
-                .eq(fastn_core::utils::citext(&user_payload.email)),
-        )
-        .select(diesel::dsl::count(fastn_core::schema::fastn_user::id))
-        .first(&mut conn)
-        .await?;
+    let email_check: i64 = fastn_core::with_conn!(&mut conn, |c| {
+        fastn_core::schema::fastn_user::table
+            .filter(
+                fastn_core::schema::fastn_user::email
+                    .eq(fastn_core::utils::citext(&user_payload.email)),
+            )
+            .select(diesel::dsl::count(fastn_core::schema::fastn_user::id))
+            .first(c)
+            .await
+    })?;
 
     if email_check > 0 {
         return fastn_core::http::user_err(
@@ -126,9 +130,8 @@ pub(crate) async fn create_account(
             .map_err(|e| fastn_core::Error::generic(format!("error in hashing password: {e}")))?
             .to_string();
 
-    let save_user_email_transaction = conn
-        .build_transaction()
-        .run(|c| {
+    let save_user_email_transaction = fastn_core::with_conn!(&mut conn, |c| {
+        c.transaction(|c| {
             Box::pin(async move {
                 let user = diesel::insert_into(fastn_core::schema::fastn_user::table)
                     .values((
@@ -164,7 +167,8 @@ pub(crate) async fn create_account(
                 Ok::<fastn_core::auth::FastnUser, diesel::result::Error>(user)
             })
         })
-        .await;
+        .await
+    });
 
     if let Err(e) = save_user_email_transaction {
         return fastn_core::http::user_err(
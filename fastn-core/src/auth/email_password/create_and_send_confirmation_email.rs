@@ -12,17 +12,19 @@ pub(crate) async fn create_and_send_confirmation_email(
     let key = generate_key(64);
     let now = chrono::Utc::now();
 
-    let query_result: Result<(i64, i64), _> = fastn_core::schema::fastn_user_email::table
-        .select((
-            fastn_core::schema::fastn_user_email::id,
-            fastn_core::schema::fastn_user_email::user_id,
-        ))
-        .filter(
-            fastn_core::schema::fastn_user_email::email
-                .eq(fastn_core::utils::citext(email.as_str())),
-        )
-        .first(conn)
-        .await;
+    let query_result: Result<(i64, i64), _> = fastn_core::with_conn!(conn, |c| {
+        fastn_core::schema::fastn_user_email::table
+            .select((
+                fastn_core::schema::fastn_user_email::id,
+                fastn_core::schema::fastn_user_email::user_id,
+            ))
+            .filter(
+                fastn_core::schema::fastn_user_email::email
+                    .eq(fastn_core::utils::citext(email.as_str())),
+            )
+            .first(c)
+            .await
+    });
 
     if let Err(e) = query_result {
         tracing::error!("failed to get email_id and user_id from db: {:?}", e);
@@ -32,36 +34,41 @@ pub(crate) async fn create_and_send_confirmation_email(
     let (email_id, user_id) = query_result.unwrap();
 
     // create a non active fastn_auth_session entry for auto login
-    let session_id: i64 = diesel::insert_into(fastn_core::schema::fastn_auth_session::table)
-        .values((
-            fastn_core::schema::fastn_auth_session::user_id.eq(&user_id),
-            fastn_core::schema::fastn_auth_session::created_at.eq(&now),
-            fastn_core::schema::fastn_auth_session::updated_at.eq(&now),
-        ))
-        .returning(fastn_core::schema::fastn_auth_session::id)
-        .get_result(conn)
-        .await?;
+    let session_id: i64 = fastn_core::with_conn!(conn, |c| {
+        diesel::insert_into(fastn_core::schema::fastn_auth_session::table)
+            .values((
+                fastn_core::schema::fastn_auth_session::user_id.eq(&user_id),
+                fastn_core::schema::fastn_auth_session::created_at.eq(&now),
+                fastn_core::schema::fastn_auth_session::updated_at.eq(&now),
+            ))
+            .returning(fastn_core::schema::fastn_auth_session::id)
+            .get_result(c)
+            .await
+    })?;
 
-    let stored_key: String =
+    let stored_key: String = fastn_core::with_conn!(conn, |c| {
         diesel::insert_into(fastn_core::schema::fastn_email_confirmation::table)
             .values((
                 fastn_core::schema::fastn_email_confirmation::email_id.eq(email_id),
                 fastn_core::schema::fastn_email_confirmation::session_id.eq(&session_id),
                 fastn_core::schema::fastn_email_confirmation::sent_at.eq(&now),
                 fastn_core::schema::fastn_email_confirmation::created_at.eq(&now),
-                fastn_core::schema::fastn_email_confirmation::key.eq(key),
+                fastn_core::schema::fastn_email_confirmation::key.eq(&key),
             ))
             .returning(fastn_core::schema::fastn_email_confirmation::key)
-            .get_result(conn)
-            .await?;
+            .get_result(c)
+            .await
+    })?;
 
     let confirmation_link = confirmation_link(&req_config.request, stored_key, next);
 
-    let name: String = fastn_core::schema::fastn_user::table
-        .select(fastn_core::schema::fastn_user::name)
-        .filter(fastn_core::schema::fastn_user::id.eq(user_id))
-        .first(conn)
-        .await?;
+    let name: String = fastn_core::with_conn!(conn, |c| {
+        fastn_core::schema::fastn_user::table
+            .select(fastn_core::schema::fastn_user::name)
+            .filter(fastn_core::schema::fastn_user::id.eq(user_id))
+            .first(c)
+            .await
+    })?;
 
     // To use auth. The package has to have auto import with alias `auth` setup
     let path = req_config
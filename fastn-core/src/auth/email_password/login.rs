@@ -1,6 +1,6 @@
 pub(crate) async fn login(
     req_config: &mut fastn_core::RequestConfig,
-    db_pool: &fastn_core::db::PgPool,
+    db_pool: &fastn_core::db::Pool,
     next: String,
 ) -> fastn_core::Result<fastn_core::http::Response> {
     use diesel::prelude::*;
@@ -60,14 +60,18 @@ pub(crate) async fn login(
             message: format!("Failed to get connection to db. {:?}", e),
         })?;
 
-    let query = fastn_core::schema::fastn_user::table
-        .filter(fastn_core::schema::fastn_user::username.eq(&payload.username))
-        .or_filter(
-            fastn_core::schema::fastn_user::email.eq(fastn_core::utils::citext(&payload.username)),
-        )
-        .select(fastn_core::auth::FastnUser::as_select());
-
-    let user: Option<fastn_core::auth::FastnUser> = query.first(&mut conn).await.optional()?;
+    let user: Option<fastn_core::auth::FastnUser> = fastn_core::with_conn!(&mut conn, |c| {
+        fastn_core::schema::fastn_user::table
+            .filter(fastn_core::schema::fastn_user::username.eq(&payload.username))
+            .or_filter(
+                fastn_core::schema::fastn_user::email
+                    .eq(fastn_core::utils::citext(&payload.username)),
+            )
+            .select(fastn_core::auth::FastnUser::as_select())
+            .first(c)
+            .await
+            .optional()
+    })?;
 
     if user.is_none() {
         return fastn_core::http::user_err(
@@ -112,15 +116,17 @@ pub(crate) async fn login(
     let now = chrono::Utc::now();
 
     // TODO: session should store device that was used to login (chrome desktop on windows)
-    let session_id: i64 = diesel::insert_into(fastn_core::schema::fastn_auth_session::table)
-        .values((
-            fastn_core::schema::fastn_auth_session::user_id.eq(&user.id),
-            fastn_core::schema::fastn_auth_session::created_at.eq(now),
-            fastn_core::schema::fastn_auth_session::updated_at.eq(now),
-        ))
-        .returning(fastn_core::schema::fastn_auth_session::id)
-        .get_result(&mut conn)
-        .await?;
+    let session_id: i64 = fastn_core::with_conn!(&mut conn, |c| {
+        diesel::insert_into(fastn_core::schema::fastn_auth_session::table)
+            .values((
+                fastn_core::schema::fastn_auth_session::user_id.eq(&user.id),
+                fastn_core::schema::fastn_auth_session::created_at.eq(now),
+                fastn_core::schema::fastn_auth_session::updated_at.eq(now),
+            ))
+            .returning(fastn_core::schema::fastn_auth_session::id)
+            .get_result(c)
+            .await
+    })?;
 
     tracing::info!("session created. session id: {}", &session_id);
 
@@ -0,0 +1,438 @@
+use crate::auth::email_password::{
+    generate_key, login_link_confirm_ftd, login_link_form_ftd, login_link_sent_ftd,
+    redirect_url_from_next,
+};
+
+/// check if the login link was sent more than 15 minutes ago
+/// can be configured using FASTN_LOGIN_LINK_EXPIRE_MINUTES
+async fn login_link_expired(
+    ds: &fastn_ds::DocumentStore,
+    sent_at: chrono::DateTime<chrono::Utc>,
+) -> bool {
+    const DEFAULT_EXPIRY_LIMIT_IN_MINUTES: i64 = 15;
+
+    let expiry_limit_in_minutes = match ds.env("FASTN_LOGIN_LINK_EXPIRE_MINUTES").await {
+        Ok(v) => v.parse().unwrap_or_else(|_| {
+            tracing::warn!(
+                "FASTN_LOGIN_LINK_EXPIRE_MINUTES is not a number: {}, using {} minutes",
+                v,
+                DEFAULT_EXPIRY_LIMIT_IN_MINUTES
+            );
+            DEFAULT_EXPIRY_LIMIT_IN_MINUTES
+        }),
+        Err(_) => DEFAULT_EXPIRY_LIMIT_IN_MINUTES,
+    };
+
+    sent_at + chrono::Duration::minutes(expiry_limit_in_minutes) <= chrono::offset::Utc::now()
+}
+
+/// GET | POST /-/auth/login-link/
+/// POST: send an email with a single use link to log in without a password, eg
+/// `{"email": "alice@example.com"}`
+pub(crate) async fn login_link(
+    req_config: &mut fastn_core::RequestConfig,
+    db_pool: &fastn_core::db::Pool,
+    next: String,
+) -> fastn_core::Result<fastn_core::http::Response> {
+    use diesel::prelude::*;
+    use diesel_async::RunQueryDsl;
+
+    if req_config.request.ud(&req_config.config.ds).await.is_some() {
+        return Ok(fastn_core::http::temporary_redirect(next));
+    }
+
+    if req_config.request.method() == "GET" {
+        let main = fastn_core::Document {
+            package_name: req_config.config.package.name.clone(),
+            id: fastn_core::auth::Route::LoginLink.to_string(),
+            content: login_link_form_ftd().to_string(),
+            parent_path: fastn_ds::Path::new("/"),
+        };
+
+        let resp = fastn_core::package::package_doc::read_ftd(req_config, &main, "/", false, false)
+            .await?;
+
+        return Ok(resp.into());
+    }
+
+    if req_config.request.method() != "POST" {
+        return Ok(fastn_core::not_found!("invalid route"));
+    }
+
+    #[derive(serde::Deserialize)]
+    struct Payload {
+        email: String,
+    }
+
+    let payload = match req_config.request.json::<Payload>() {
+        Ok(payload) => payload,
+        Err(e) => {
+            return fastn_core::http::user_err(
+                vec![
+                    ("payload".into(), vec![format!("invalid payload: {:?}", e)]),
+                    ("email".into(), vec!["email is required".to_string()]),
+                ],
+                fastn_core::http::StatusCode::OK,
+            );
+        }
+    };
+
+    if payload.email.is_empty() {
+        return fastn_core::http::user_err(
+            vec![("email".into(), vec!["email is required".to_string()])],
+            fastn_core::http::StatusCode::OK,
+        );
+    }
+
+    let key = generate_key(64);
+    let login_link = login_link_url(
+        req_config.request.connection_info.scheme(),
+        req_config.request.connection_info.host(),
+        &key,
+        &next,
+    );
+    // The mail is rendered before looking up the account, the response takes as long whether
+    // there is an account for the email or not
+    let html = login_link_mail(req_config, &login_link).await?;
+
+    let mut conn = db_pool
+        .get()
+        .await
+        .map_err(|e| fastn_core::Error::DatabaseError {
+            message: format!("Failed to get connection to db. {:?}", e),
+        })?;
+
+    // the link is only sent to the primary email, it is verified when the link is used
+    let user: Option<fastn_core::auth::FastnUser> = fastn_core::with_conn!(&mut conn, |c| {
+        fastn_core::schema::fastn_user::table
+            .filter(
+                fastn_core::schema::fastn_user::email.eq(fastn_core::utils::citext(&payload.email)),
+            )
+            .select(fastn_core::auth::FastnUser::as_select())
+            .first(c)
+            .await
+            .optional()
+    })?;
+
+    // The response does not tell whether there is an account for the email
+    let user = match user {
+        Some(user) => user,
+        None => {
+            tracing::info!("login link requested for an unknown email");
+            return Ok(link_sent(req_config, None));
+        }
+    };
+
+    let enable_email = req_config
+        .config
+        .ds
+        .env_bool("FASTN_ENABLE_EMAIL", true)
+        .await
+        .unwrap_or(true);
+
+    if !enable_email {
+        println!("LOGIN LINK: {}", &login_link);
+    }
+
+    // The link is stored and mailed off the request path, for the same reason. `fastn test`
+    // uses the link right away, it waits for it.
+    let ds = req_config.config.ds.clone();
+    let send = actix_web::rt::spawn(async move {
+        if let Err(e) = send_login_link(&ds, &mut conn, &user, &key, html).await {
+            tracing::error!("failed to send login link: {e}");
+        }
+    });
+    if req_config.config.test_command_running {
+        send.await
+            .map_err(|e| fastn_core::Error::generic(format!("failed to send login link: {e}")))?;
+    }
+
+    Ok(link_sent(req_config, Some(login_link)))
+}
+
+/// `/-/auth/login-link/confirm/?code=<key>&next=<next>`, `next` is encoded so that it can not
+/// add to or cut the query.
+fn login_link_url(scheme: &str, host: &str, key: &str, next: &str) -> String {
+    format!(
+        "{scheme}://{host}{login_link_confirm_route}?{query}",
+        login_link_confirm_route = fastn_core::auth::Route::LoginLinkConfirm,
+        query = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("code", key)
+            .append_pair("next", next)
+            .finish(),
+    )
+}
+
+/// The html of the login link mail, `login-link-mail-html` of the package's `auth` document with
+/// `{{link}}` replaced by `login_link`.
+async fn login_link_mail(
+    req_config: &mut fastn_core::RequestConfig,
+    login_link: &str,
+) -> fastn_core::Result<String> {
+    // To use auth. The package has to have auto import with alias `auth` setup
+    let path = req_config
+        .config
+        .package
+        .eval_auto_import("auth")
+        .ok_or_else(|| {
+            fastn_core::Error::generic(
+                "the package has to auto import the auth package with the alias `auth`",
+            )
+        })?
+        .to_owned();
+
+    let path = path
+        .strip_prefix(format!("{}/", req_config.config.package.name).as_str())
+        .ok_or_else(|| {
+            fastn_core::Error::generic(format!(
+                "the `auth` auto import has to be a document of the package {}, found: {}",
+                req_config.config.package.name, path
+            ))
+        })?;
+
+    let content = req_config
+        .config
+        .ds
+        .read_to_string(&fastn_ds::Path::new(format!("{}.ftd", path)))
+        .await?;
+
+    let auth_doc = fastn_core::Document {
+        package_name: req_config.config.package.name.clone(),
+        id: path.to_string(),
+        content,
+        parent_path: fastn_ds::Path::new("/"),
+    };
+
+    let main_ftd_doc = fastn_core::doc::interpret_helper(
+        auth_doc.id_with_package().as_str(),
+        auth_doc.content.as_str(),
+        req_config,
+        "/",
+        false,
+        0,
+    )
+    .await?;
+
+    let html_email_templ = format!(
+        "{}/{}#login-link-mail-html",
+        req_config.config.package.name, path
+    );
+
+    let html: String = main_ftd_doc.get(&html_email_templ)?;
+    Ok(html.replace("{{link}}", login_link))
+}
+
+async fn send_login_link(
+    ds: &fastn_ds::DocumentStore,
+    conn: &mut fastn_core::db::Conn,
+    user: &fastn_core::auth::FastnUser,
+    key: &str,
+    html: String,
+) -> fastn_core::Result<()> {
+    use diesel::prelude::*;
+    use diesel_async::RunQueryDsl;
+
+    fastn_core::with_conn!(conn, |c| {
+        diesel::insert_into(fastn_core::schema::fastn_login_link::table)
+            .values((
+                fastn_core::schema::fastn_login_link::user_id.eq(&user.id),
+                fastn_core::schema::fastn_login_link::key.eq(key),
+                fastn_core::schema::fastn_login_link::sent_at.eq(chrono::offset::Utc::now()),
+            ))
+            .execute(c)
+            .await
+    })?;
+
+    ds.send_email(
+        (&user.name, &user.email.0),
+        "Your login link",
+        html,
+        fastn_ds::mail::EmailKind::LoginLink,
+    )
+    .await
+    .map_err(|e| fastn_core::Error::generic(format!("failed to send email: {e}")))
+}
+
+/// The response to a login link request, the same whether there is an account for the email
+/// or not. `login_link` is only given to `fastn test`.
+fn link_sent(
+    req_config: &fastn_core::RequestConfig,
+    login_link: Option<String>,
+) -> fastn_core::http::Response {
+    let resp_body = serde_json::json!({
+        "success": true,
+        "redirect": redirect_url_from_next(&req_config.request, fastn_core::auth::Route::LoginLinkSent.to_string()),
+    });
+
+    let mut resp = actix_web::HttpResponse::Ok();
+
+    if req_config.config.test_command_running {
+        resp.insert_header(("X-Fastn-Test", "true"));
+        if let Some(login_link) = login_link {
+            resp.insert_header(("X-Fastn-Test-Login-Link", login_link));
+        }
+    }
+
+    resp.json(resp_body)
+}
+
+pub(crate) async fn login_link_sent(
+    req_config: &mut fastn_core::RequestConfig,
+) -> fastn_core::Result<fastn_core::http::Response> {
+    if req_config.request.method() != "GET" {
+        return Ok(fastn_core::not_found!("invalid route"));
+    }
+
+    let main = fastn_core::Document {
+        package_name: req_config.config.package.name.clone(),
+        id: fastn_core::auth::Route::LoginLinkSent.to_string(),
+        content: login_link_sent_ftd().to_string(),
+        parent_path: fastn_ds::Path::new("/"),
+    };
+
+    let resp =
+        fastn_core::package::package_doc::read_ftd(req_config, &main, "/", false, false).await?;
+
+    Ok(resp.into())
+}
+
+/// GET | POST /-/auth/login-link/confirm/
+/// GET: asks the user to confirm, so that a link opened by a mail scanner does not use the code
+/// POST: logs in the user the ?code was sent to, a code can only be used once
+pub(crate) async fn login_link_confirm(
+    req_config: &mut fastn_core::RequestConfig,
+    db_pool: &fastn_core::db::Pool,
+    next: String,
+) -> fastn_core::Result<fastn_core::http::Response> {
+    use diesel::prelude::*;
+    use diesel_async::RunQueryDsl;
+
+    if req_config.request.method() == "GET" {
+        let main = fastn_core::Document {
+            package_name: req_config.config.package.name.clone(),
+            id: fastn_core::auth::Route::LoginLinkConfirm.to_string(),
+            content: login_link_confirm_ftd().to_string(),
+            parent_path: fastn_ds::Path::new("/"),
+        };
+
+        let resp = fastn_core::package::package_doc::read_ftd(req_config, &main, "/", false, false)
+            .await?;
+
+        return Ok(resp.into());
+    }
+
+    if req_config.request.method() != "POST" {
+        return Ok(fastn_core::not_found!("invalid route"));
+    }
+
+    let code = match req_config.request.query().get("code") {
+        Some(serde_json::Value::String(c)) => c.to_owned(),
+        _ => {
+            tracing::info!("finishing response due to bad ?code");
+            return Ok(fastn_core::http::api_error("Bad Request")?);
+        }
+    };
+
+    let mut conn = db_pool
+        .get()
+        .await
+        .map_err(|e| fastn_core::Error::DatabaseError {
+            message: format!("Failed to get connection to db. {:?}", e),
+        })?;
+
+    let link: Option<(i64, chrono::DateTime<chrono::Utc>)> =
+        fastn_core::with_conn!(&mut conn, |c| {
+            diesel::delete(
+                fastn_core::schema::fastn_login_link::table
+                    .filter(fastn_core::schema::fastn_login_link::key.eq(&code)),
+            )
+            .returning((
+                fastn_core::schema::fastn_login_link::user_id,
+                fastn_core::schema::fastn_login_link::sent_at,
+            ))
+            .get_result(c)
+            .await
+            .optional()
+        })?;
+
+    let (user_id, sent_at) = match link {
+        Some(link) => link,
+        None => {
+            tracing::info!("invalid or already used login link code");
+            return Ok(fastn_core::http::api_error("Bad Request")?);
+        }
+    };
+
+    if login_link_expired(&req_config.config.ds, sent_at).await {
+        tracing::info!("provided code has expired.");
+        return Ok(fastn_core::http::temporary_redirect(format!(
+            "{scheme}://{host}{login_link_route}?next={next}",
+            scheme = req_config.request.connection_info.scheme(),
+            host = req_config.request.connection_info.host(),
+            login_link_route = fastn_core::auth::Route::LoginLink,
+        )));
+    }
+
+    // the link was sent to the primary email of the user, they own it
+    let email: fastn_core::utils::CiString = fastn_core::with_conn!(&mut conn, |c| {
+        diesel::update(fastn_core::schema::fastn_user::table)
+            .set(fastn_core::schema::fastn_user::verified_email.eq(true))
+            .filter(fastn_core::schema::fastn_user::id.eq(user_id))
+            .returning(fastn_core::schema::fastn_user::email)
+            .get_result(c)
+            .await
+    })?;
+
+    fastn_core::with_conn!(&mut conn, |c| {
+        diesel::update(fastn_core::schema::fastn_user_email::table)
+            .set(fastn_core::schema::fastn_user_email::verified.eq(true))
+            .filter(fastn_core::schema::fastn_user_email::user_id.eq(user_id))
+            .filter(fastn_core::schema::fastn_user_email::email.eq(&email))
+            .execute(c)
+            .await
+    })?;
+
+    let now = chrono::Utc::now();
+
+    let session_id: i64 = fastn_core::with_conn!(&mut conn, |c| {
+        diesel::insert_into(fastn_core::schema::fastn_auth_session::table)
+            .values((
+                fastn_core::schema::fastn_auth_session::user_id.eq(&user_id),
+                fastn_core::schema::fastn_auth_session::created_at.eq(now),
+                fastn_core::schema::fastn_auth_session::updated_at.eq(now),
+            ))
+            .returning(fastn_core::schema::fastn_auth_session::id)
+            .get_result(c)
+            .await
+    })?;
+
+    tracing::info!(
+        "session created with login link. session id: {}",
+        &session_id
+    );
+
+    fastn_core::auth::set_session_cookie_and_redirect_to_next(
+        &req_config.request,
+        &req_config.config.ds,
+        session_id,
+        next,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn login_link_url() {
+        assert_eq!(
+            super::login_link_url("https", "example.com", "KEY", "/"),
+            "https://example.com/-/auth/login-link/confirm/?code=KEY&next=%2F"
+        );
+        // `next` can not replace the code or cut the link
+        assert_eq!(
+            super::login_link_url("https", "example.com", "KEY", "/a/?b=1&code=other#top"),
+            "https://example.com/-/auth/login-link/confirm/?code=KEY\
+            &next=%2Fa%2F%3Fb%3D1%26code%3Dother%23top"
+        );
+    }
+}
@@ -3,9 +3,11 @@ mod create_account;
 mod create_and_send_confirmation_email;
 mod email_confirmation_sent;
 mod login;
+mod login_link;
 mod onboarding;
 mod resend_confirmation_email;
 mod set_password;
+pub(crate) mod totp;
 mod urls;
 
 pub(crate) use {
@@ -14,9 +16,11 @@ pub(crate) use {
     create_and_send_confirmation_email::create_and_send_confirmation_email,
     email_confirmation_sent::email_confirmation_sent,
     login::login,
+    login_link::{login_link, login_link_confirm, login_link_sent},
     onboarding::onboarding,
     resend_confirmation_email::resend_confirmation_email,
     set_password::*,
+    totp::{totp, totp_disable, totp_setup},
     urls::{confirmation_link, redirect_url_from_next},
 };
 
@@ -94,8 +98,62 @@ fn login_ftd() -> &'static str {
     "#
 }
 
+fn login_link_form_ftd() -> &'static str {
+    r#"
+    -- auth.login-link-form-page:
+    "#
+}
+
+fn login_link_sent_ftd() -> &'static str {
+    r#"
+    -- auth.login-link-sent-page:
+    "#
+}
+
+/// The page POSTs to the current url, which has the ?code
+fn login_link_confirm_ftd() -> &'static str {
+    r#"
+    -- auth.login-link-confirm-page:
+    "#
+}
+
 fn onboarding_ftd() -> &'static str {
     r#"
     -- auth.onboarding-page:
     "#
 }
+
+fn totp_login_ftd() -> &'static str {
+    r#"
+    -- auth.totp-login-page:
+    "#
+}
+
+/// The page POSTs `{}` to the current url to start the setup.
+fn totp_setup_start_ftd() -> &'static str {
+    r#"
+    -- auth.totp-setup-start-page:
+    "#
+}
+
+/// `otpauth-uri` is to be shown as a QR code, the recovery codes are only shown here.
+fn totp_setup_ftd(otpauth_uri: &str, secret: &str, recovery_codes: &[String]) -> String {
+    let recovery_codes = recovery_codes
+        .iter()
+        .map(|code| format!("-- string: {code}\n\n"))
+        .collect::<String>();
+
+    format!(
+        r#"
+-- string list recovery-codes:
+
+{recovery_codes}
+-- end: recovery-codes
+
+-- auth.totp-setup-page:
+otpauth-uri: {otpauth_uri}
+secret: {secret}
+recovery-codes: $recovery-codes
+"#
+    )
+}
@@ -3,7 +3,7 @@ use crate::auth::email_password::create_and_send_confirmation_email;
 pub(crate) async fn resend_confirmation_email(
     req: &fastn_core::http::Request,
     req_config: &mut fastn_core::RequestConfig,
-    db_pool: &fastn_core::db::PgPool,
+    db_pool: &fastn_core::db::Pool,
     next: String,
 ) -> fastn_core::Result<fastn_core::http::Response> {
     // TODO: should be able to use username for this too
@@ -31,7 +31,7 @@ pub(crate) async fn resend_confirmation_email(
             message: format!("Failed to get connection to db. {:?}", e),
         })?;
 
-    let (conf_link, session_id) =
+    let (conf_link, _session_id) =
         create_and_send_confirmation_email(email, &mut conn, req_config, next.clone()).await?;
 
     // email is not enabled, we should log conf link assuming dev mode
@@ -44,11 +44,10 @@ pub(crate) async fn resend_confirmation_email(
         println!("CONFIRMATION LINK: {}", conf_link);
     }
 
-    fastn_core::auth::set_session_cookie_and_redirect_to_next(
-        &req_config.request,
-        &req_config.config.ds,
-        session_id,
-        next,
-    )
-    .await
+    // anyone can ask for a confirmation email, the session is only started by the link in it,
+    // see `confirm_email`
+    Ok(fastn_core::http::temporary_redirect(format!(
+        "{}?next={next}",
+        fastn_core::auth::Route::EmailConfirmationSent
+    )))
 }
@@ -8,7 +8,7 @@ use crate::auth::email_password::{
 /// for unauthenticated users
 pub(crate) async fn forgot_password_request(
     req_config: &mut fastn_core::RequestConfig,
-    db_pool: &fastn_core::db::PgPool,
+    db_pool: &fastn_core::db::Pool,
     next: String,
 ) -> fastn_core::Result<fastn_core::http::Response> {
     use diesel::prelude::*;
@@ -76,20 +76,23 @@ pub(crate) async fn forgot_password_request(
             message: format!("Failed to get connection to db. {:?}", e),
         })?;
 
-    let query = fastn_core::schema::fastn_user::table
-        .inner_join(fastn_core::schema::fastn_user_email::table)
-        .filter(fastn_core::schema::fastn_user::username.eq(&payload.email_or_username))
-        .or_filter(
-            fastn_core::schema::fastn_user_email::email
-                .eq(fastn_core::utils::citext(&payload.email_or_username)),
-        )
-        .select((
-            fastn_core::auth::FastnUser::as_select(),
-            fastn_core::schema::fastn_user_email::email,
-        ));
-
     let user: Option<(fastn_core::auth::FastnUser, fastn_core::utils::CiString)> =
-        query.first(&mut conn).await.optional()?;
+        fastn_core::with_conn!(&mut conn, |c| {
+            fastn_core::schema::fastn_user::table
+                .inner_join(fastn_core::schema::fastn_user_email::table)
+                .filter(fastn_core::schema::fastn_user::username.eq(&payload.email_or_username))
+                .or_filter(
+                    fastn_core::schema::fastn_user_email::email
+                        .eq(fastn_core::utils::citext(&payload.email_or_username)),
+                )
+                .select((
+                    fastn_core::auth::FastnUser::as_select(),
+                    fastn_core::schema::fastn_user_email::email,
+                ))
+                .first(c)
+                .await
+                .optional()
+        })?;
 
     if user.is_none() {
         return fastn_core::http::user_err(
@@ -105,14 +108,16 @@ pub(crate) async fn forgot_password_request(
 
     let key = generate_key(64);
 
-    diesel::insert_into(fastn_core::schema::fastn_password_reset::table)
-        .values((
-            fastn_core::schema::fastn_password_reset::user_id.eq(&user.id),
-            fastn_core::schema::fastn_password_reset::key.eq(&key),
-            fastn_core::schema::fastn_password_reset::sent_at.eq(chrono::offset::Utc::now()),
-        ))
-        .execute(&mut conn)
-        .await?;
+    fastn_core::with_conn!(&mut conn, |c| {
+        diesel::insert_into(fastn_core::schema::fastn_password_reset::table)
+            .values((
+                fastn_core::schema::fastn_password_reset::user_id.eq(&user.id),
+                fastn_core::schema::fastn_password_reset::key.eq(&key),
+                fastn_core::schema::fastn_password_reset::sent_at.eq(chrono::offset::Utc::now()),
+            ))
+            .execute(c)
+            .await
+    })?;
 
     let reset_link = format!(
         "{scheme}://{host}{reset_password_route}?code={key}&next={next}",
@@ -225,7 +230,7 @@ pub(crate) async fn forgot_password_request_success(
 /// GET | POST /-/auth/set-password/
 pub(crate) async fn set_password(
     req_config: &mut fastn_core::RequestConfig,
-    db_pool: &fastn_core::db::PgPool,
+    db_pool: &fastn_core::db::Pool,
     next: String,
 ) -> fastn_core::Result<fastn_core::http::Response> {
     use diesel::prelude::*;
@@ -328,13 +333,16 @@ pub(crate) async fn set_password(
                     message: format!("Failed to get connection to db. {:?}", e),
                 })?;
 
-            let query = diesel::delete(
-                fastn_core::schema::fastn_password_reset::table
-                    .filter(fastn_core::schema::fastn_password_reset::key.eq(&key)),
-            )
-            .returning(fastn_core::schema::fastn_password_reset::user_id);
-
-            let user_id: Option<i64> = query.get_result(&mut conn).await.optional()?;
+            let user_id: Option<i64> = fastn_core::with_conn!(&mut conn, |c| {
+                diesel::delete(
+                    fastn_core::schema::fastn_password_reset::table
+                        .filter(fastn_core::schema::fastn_password_reset::key.eq(&key)),
+                )
+                .returning(fastn_core::schema::fastn_password_reset::user_id)
+                .get_result(c)
+                .await
+                .optional()
+            })?;
 
             if user_id.is_none() {
                 return Ok(fastn_core::http::api_error("Bad Request")?);
@@ -361,19 +369,23 @@ pub(crate) async fn set_password(
             .map_err(|e| fastn_core::Error::generic(format!("error in hashing password: {e}")))?
             .to_string();
 
-    diesel::update(fastn_core::schema::fastn_user::table)
-        .set(fastn_core::schema::fastn_user::password.eq(&hashed_password))
-        .filter(fastn_core::schema::fastn_user::id.eq(&user_id))
-        .execute(&mut conn)
-        .await?;
+    fastn_core::with_conn!(&mut conn, |c| {
+        diesel::update(fastn_core::schema::fastn_user::table)
+            .set(fastn_core::schema::fastn_user::password.eq(&hashed_password))
+            .filter(fastn_core::schema::fastn_user::id.eq(&user_id))
+            .execute(c)
+            .await
+    })?;
 
     // log the user out of all sessions
-    let affected = diesel::delete(
-        fastn_core::schema::fastn_auth_session::table
-            .filter(fastn_core::schema::fastn_auth_session::user_id.eq(&user_id)),
-    )
-    .execute(&mut conn)
-    .await?;
+    let affected = fastn_core::with_conn!(&mut conn, |c| {
+        diesel::delete(
+            fastn_core::schema::fastn_auth_session::table
+                .filter(fastn_core::schema::fastn_auth_session::user_id.eq(&user_id)),
+        )
+        .execute(c)
+        .await
+    })?;
 
     tracing::info!("{affected} session removed");
 
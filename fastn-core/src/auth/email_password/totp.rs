@@ -0,0 +1,789 @@
+//! Two-factor authentication of email/password users with time-based one-time passwords,
+//! https://datatracker.ietf.org/doc/html/rfc6238, the codes of authenticator apps.
+//!
+//! A user enrolls at `/-/auth/totp/setup/`. Starting the setup, with a POST, shows the secret as
+//! an `otpauth://` uri, to be shown as a QR code, and their recovery codes. Once enabled, every
+//! login, with a password, a login link or an oauth provider, asks for a code at `/-/auth/totp/`
+//! before the session is started, see `fastn_core::auth::set_session_cookie_and_redirect_to_next`.
+
+use crate::auth::email_password::{
+    generate_key, totp_login_ftd, totp_setup_ftd, totp_setup_start_ftd,
+};
+
+/// The session of the user that logged in, until they enter a code at `/-/auth/totp/`.
+const PENDING_LOGIN_COOKIE_NAME: &str = "fastn_totp_login";
+const PENDING_LOGIN_MINUTES: i64 = 5;
+
+const STEP_SECONDS: u64 = 30;
+const DIGITS: u32 = 6;
+const RECOVERY_CODES: usize = 10;
+
+/// Failed attempts of a user allowed in `ATTEMPT_WINDOW_MINUTES`.
+const MAX_FAILED_ATTEMPTS: u32 = 5;
+const ATTEMPT_WINDOW_MINUTES: i64 = 5;
+
+static FAILED_ATTEMPTS: once_cell::sync::Lazy<
+    std::sync::Mutex<std::collections::HashMap<i64, (u32, chrono::DateTime<chrono::Utc>)>>,
+> = once_cell::sync::Lazy::new(|| std::sync::Mutex::new(std::collections::HashMap::new()));
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct PendingLogin {
+    user_id: i64,
+    session_id: i64,
+    next: String,
+    expires_at: i64,
+}
+
+#[derive(serde::Deserialize)]
+struct Payload {
+    code: String,
+}
+
+/// If `user_id` has enabled two-factor authentication.
+pub(crate) async fn enabled(
+    conn: &mut fastn_core::db::Conn,
+    user_id: i64,
+) -> fastn_core::Result<bool> {
+    use diesel::prelude::*;
+    use diesel_async::RunQueryDsl;
+
+    let enabled_at: Option<Option<chrono::DateTime<chrono::Utc>>> =
+        fastn_core::with_conn!(conn, |c| {
+            fastn_core::schema::fastn_user::table
+                .select(fastn_core::schema::fastn_user::totp_enabled_at)
+                .filter(fastn_core::schema::fastn_user::id.eq(user_id))
+                .first(c)
+                .await
+                .optional()
+        })?;
+
+    Ok(matches!(enabled_at, Some(Some(_))))
+}
+
+/// Called instead of starting `session_id`, which is started once the user enters a code, the
+/// client has to follow the redirect.
+pub(crate) async fn ask_for_code(
+    req: &fastn_core::http::Request,
+    ds: &fastn_ds::DocumentStore,
+    user_id: i64,
+    session_id: i64,
+    next: String,
+) -> fastn_core::Result<fastn_core::http::Response> {
+    let pending = serde_json::to_string(&PendingLogin {
+        user_id,
+        session_id,
+        next,
+        expires_at: (chrono::Utc::now() + chrono::Duration::minutes(PENDING_LOGIN_MINUTES))
+            .timestamp(),
+    })?;
+
+    Ok(actix_web::HttpResponse::Found()
+        .cookie(
+            actix_web::cookie::Cookie::build(
+                PENDING_LOGIN_COOKIE_NAME,
+                fastn_core::auth::utils::encrypt(ds, pending.as_str()).await,
+            )
+            .domain(fastn_core::auth::utils::domain(req.connection_info.host()))
+            .path("/")
+            .max_age(actix_web::cookie::time::Duration::minutes(
+                PENDING_LOGIN_MINUTES,
+            ))
+            .http_only(true)
+            .same_site(actix_web::cookie::SameSite::Lax)
+            .finish(),
+        )
+        .append_header((
+            actix_web::http::header::LOCATION,
+            fastn_core::auth::Route::Totp.to_string(),
+        ))
+        .finish())
+}
+
+async fn pending_login(
+    req: &fastn_core::http::Request,
+    ds: &fastn_ds::DocumentStore,
+) -> Option<PendingLogin> {
+    let pending = fastn_core::auth::utils::decrypt(ds, &req.cookie(PENDING_LOGIN_COOKIE_NAME)?)
+        .await
+        .ok()?;
+
+    serde_json::from_str::<PendingLogin>(pending.as_str())
+        .ok()
+        .filter(|p| p.expires_at > chrono::Utc::now().timestamp())
+}
+
+// route: /-/auth/totp/
+// GET: asks for a code of the authenticator app, or a recovery code
+// POST: starts the session of the login, eg `{"code": "123456"}`
+pub(crate) async fn totp(
+    req_config: &mut fastn_core::RequestConfig,
+    db_pool: &fastn_core::db::Pool,
+) -> fastn_core::Result<fastn_core::http::Response> {
+    let ds = req_config.config.ds.clone();
+
+    let pending = match pending_login(&req_config.request, &ds).await {
+        Some(pending) => pending,
+        // the user took too long, or did not enter their password
+        None => {
+            return Ok(fastn_core::http::temporary_redirect(
+                fastn_core::auth::Route::Login.to_string(),
+            ))
+        }
+    };
+
+    if req_config.request.method() != "POST" {
+        let main = fastn_core::Document {
+            package_name: req_config.config.package.name.clone(),
+            id: fastn_core::auth::Route::Totp.to_string(),
+            content: totp_login_ftd().to_string(),
+            parent_path: fastn_ds::Path::new("/"),
+        };
+
+        let resp = fastn_core::package::package_doc::read_ftd(req_config, &main, "/", false, false)
+            .await?;
+
+        return Ok(resp.into());
+    }
+
+    let payload = match req_config.request.json::<Payload>() {
+        Ok(payload) => payload,
+        Err(e) => {
+            return fastn_core::http::user_err(
+                vec![("payload".into(), vec![format!("invalid payload: {:?}", e)])],
+                fastn_core::http::StatusCode::OK,
+            );
+        }
+    };
+
+    let mut conn = db_pool
+        .get()
+        .await
+        .map_err(|e| fastn_core::Error::DatabaseError {
+            message: format!("Failed to get connection to db. {:?}", e),
+        })?;
+
+    if let Err(message) = verify_user_code(&mut conn, &ds, pending.user_id, &payload.code).await? {
+        return fastn_core::http::user_err(
+            vec![("code".into(), vec![message])],
+            fastn_core::http::StatusCode::OK,
+        );
+    }
+
+    tracing::info!("totp entered for session {}", pending.session_id);
+
+    let mut resp = fastn_core::auth::start_session_and_redirect_to_next(
+        &req_config.request,
+        &ds,
+        pending.session_id,
+        pending.next,
+    )
+    .await?;
+
+    resp.add_cookie(
+        &actix_web::cookie::Cookie::build(PENDING_LOGIN_COOKIE_NAME, "")
+            .domain(fastn_core::auth::utils::domain(
+                req_config.request.connection_info.host(),
+            ))
+            .path("/")
+            .expires(actix_web::cookie::time::OffsetDateTime::now_utc())
+            .finish(),
+    )
+    .map_err(|e| fastn_core::Error::generic(format!("failed to set cookie: {e}")))?;
+
+    Ok(resp)
+}
+
+// route: /-/auth/totp/setup/
+// GET: asks the logged in user to start the setup, nothing is written on a GET
+// POST: `{}` starts the setup, a new secret and recovery codes to be confirmed with a code,
+// `{"code": "123456"}` enables two-factor authentication if the code is of the new secret
+pub(crate) async fn totp_setup(
+    req_config: &mut fastn_core::RequestConfig,
+    db_pool: &fastn_core::db::Pool,
+    next: String,
+) -> fastn_core::Result<fastn_core::http::Response> {
+    use diesel::prelude::*;
+    use diesel_async::{AsyncConnection, RunQueryDsl};
+
+    let ds = req_config.config.ds.clone();
+
+    let user = match req_config.request.ud(&ds).await {
+        Some(user) => user,
+        None => return Ok(fastn_core::http::api_error("Not logged in")?),
+    };
+
+    let mut conn = db_pool
+        .get()
+        .await
+        .map_err(|e| fastn_core::Error::DatabaseError {
+            message: format!("Failed to get connection to db. {:?}", e),
+        })?;
+
+    // the secret is not shown again once enabled, it has to be disabled first
+    if enabled(&mut conn, user.id).await? {
+        return Ok(fastn_core::http::temporary_redirect(next));
+    }
+
+    if req_config.request.method() != "POST" {
+        let main = fastn_core::Document {
+            package_name: req_config.config.package.name.clone(),
+            id: fastn_core::auth::Route::TotpSetup.to_string(),
+            content: totp_setup_start_ftd().to_string(),
+            parent_path: fastn_ds::Path::new("/"),
+        };
+
+        let resp = fastn_core::package::package_doc::read_ftd(req_config, &main, "/", false, false)
+            .await?;
+
+        return Ok(resp.into());
+    }
+
+    #[derive(serde::Deserialize)]
+    struct SetupPayload {
+        code: Option<String>,
+    }
+
+    let payload = match req_config.request.json::<SetupPayload>() {
+        Ok(payload) => payload,
+        Err(e) => {
+            return fastn_core::http::user_err(
+                vec![("payload".into(), vec![format!("invalid payload: {:?}", e)])],
+                fastn_core::http::StatusCode::OK,
+            );
+        }
+    };
+
+    let code = match payload.code {
+        Some(code) => code,
+        None => {
+            let secret = new_secret();
+            let encrypted_secret = fastn_core::auth::utils::encrypt(&ds, &secret).await;
+            let recovery_codes: Vec<String> =
+                (0..RECOVERY_CODES).map(|_| recovery_code()).collect();
+            let now = chrono::Utc::now();
+            let user_id = user.id;
+            let rows: Vec<_> = recovery_codes
+                .iter()
+                .map(|code| {
+                    (
+                        fastn_core::schema::fastn_user_recovery_code::user_id.eq(user_id),
+                        fastn_core::schema::fastn_user_recovery_code::code
+                            .eq(recovery_code_hash(code)),
+                        fastn_core::schema::fastn_user_recovery_code::created_at.eq(now),
+                    )
+                })
+                .collect();
+
+            // the secret and codes of an earlier, unfinished, setup are replaced, all at once
+            fastn_core::with_conn!(&mut conn, |c| {
+                c.transaction(|c| {
+                    Box::pin(async move {
+                        diesel::update(fastn_core::schema::fastn_user::table)
+                            .set(fastn_core::schema::fastn_user::totp_secret.eq(&encrypted_secret))
+                            .filter(fastn_core::schema::fastn_user::id.eq(user_id))
+                            .execute(c)
+                            .await?;
+
+                        diesel::delete(fastn_core::schema::fastn_user_recovery_code::table)
+                            .filter(
+                                fastn_core::schema::fastn_user_recovery_code::user_id.eq(user_id),
+                            )
+                            .execute(c)
+                            .await?;
+
+                        diesel::insert_into(fastn_core::schema::fastn_user_recovery_code::table)
+                            .values(&rows)
+                            .execute(c)
+                            .await
+                    })
+                })
+                .await
+            })?;
+
+            let issuer = ds
+                .env("FASTN_TOTP_ISSUER")
+                .await
+                .unwrap_or_else(|_| req_config.config.package.name.clone());
+
+            let main = fastn_core::Document {
+                package_name: req_config.config.package.name.clone(),
+                id: fastn_core::auth::Route::TotpSetup.to_string(),
+                content: totp_setup_ftd(
+                    otpauth_uri(&issuer, &user.email, &secret).as_str(),
+                    secret.as_str(),
+                    &recovery_codes,
+                ),
+                parent_path: fastn_ds::Path::new("/"),
+            };
+
+            let resp =
+                fastn_core::package::package_doc::read_ftd(req_config, &main, "/", false, false)
+                    .await?;
+
+            return Ok(resp.into());
+        }
+    };
+
+    let secret = match user_secret(&mut conn, &ds, user.id).await? {
+        Some(secret) => secret,
+        None => {
+            return fastn_core::http::user_err(
+                vec![("code".into(), vec!["setup has not been started".into()])],
+                fastn_core::http::StatusCode::OK,
+            );
+        }
+    };
+
+    let now = chrono::Utc::now();
+
+    let is_valid = match code_step(&secret, &code, now.timestamp() as u64) {
+        Some(step) => use_step(&mut conn, user.id, step).await?,
+        None => false,
+    };
+
+    if let Err(message) = record_attempt(user.id, is_valid) {
+        return fastn_core::http::user_err(
+            vec![("code".into(), vec![message])],
+            fastn_core::http::StatusCode::OK,
+        );
+    }
+
+    fastn_core::with_conn!(&mut conn, |c| {
+        diesel::update(fastn_core::schema::fastn_user::table)
+            .set(fastn_core::schema::fastn_user::totp_enabled_at.eq(&now))
+            .filter(fastn_core::schema::fastn_user::id.eq(user.id))
+            .execute(c)
+            .await
+    })?;
+
+    tracing::info!("totp enabled for user {}", user.id);
+
+    Ok(actix_web::HttpResponse::Found()
+        .append_header((actix_web::http::header::LOCATION, next))
+        .finish())
+}
+
+// route: /-/auth/totp/disable/
+/// Disables two-factor authentication of the logged in user, eg `{"code": "123456"}`, a code
+/// of the authenticator app or a recovery code.
+pub(crate) async fn totp_disable(
+    req_config: &mut fastn_core::RequestConfig,
+    db_pool: &fastn_core::db::Pool,
+    next: String,
+) -> fastn_core::Result<fastn_core::http::Response> {
+    use diesel::prelude::*;
+    use diesel_async::RunQueryDsl;
+
+    if req_config.request.method() != "POST" {
+        return Ok(fastn_core::not_found!("invalid route"));
+    }
+
+    let ds = req_config.config.ds.clone();
+
+    let user = match req_config.request.ud(&ds).await {
+        Some(user) => user,
+        None => return Ok(fastn_core::http::api_error("Not logged in")?),
+    };
+
+    let payload = match req_config.request.json::<Payload>() {
+        Ok(payload) => payload,
+        Err(e) => {
+            return fastn_core::http::user_err(
+                vec![("payload".into(), vec![format!("invalid payload: {:?}", e)])],
+                fastn_core::http::StatusCode::OK,
+            );
+        }
+    };
+
+    let mut conn = db_pool
+        .get()
+        .await
+        .map_err(|e| fastn_core::Error::DatabaseError {
+            message: format!("Failed to get connection to db. {:?}", e),
+        })?;
+
+    if let Err(message) = verify_user_code(&mut conn, &ds, user.id, &payload.code).await? {
+        return fastn_core::http::user_err(
+            vec![("code".into(), vec![message])],
+            fastn_core::http::StatusCode::OK,
+        );
+    }
+
+    fastn_core::with_conn!(&mut conn, |c| {
+        diesel::update(fastn_core::schema::fastn_user::table)
+            .set((
+                fastn_core::schema::fastn_user::totp_secret.eq(None::<String>),
+                fastn_core::schema::fastn_user::totp_enabled_at
+                    .eq(None::<chrono::DateTime<chrono::Utc>>),
+                fastn_core::schema::fastn_user::totp_last_step.eq(None::<i64>),
+            ))
+            .filter(fastn_core::schema::fastn_user::id.eq(user.id))
+            .execute(c)
+            .await
+    })?;
+
+    fastn_core::with_conn!(&mut conn, |c| {
+        diesel::delete(fastn_core::schema::fastn_user_recovery_code::table)
+            .filter(fastn_core::schema::fastn_user_recovery_code::user_id.eq(user.id))
+            .execute(c)
+            .await
+    })?;
+
+    tracing::info!("totp disabled for user {}", user.id);
+
+    Ok(actix_web::HttpResponse::Found()
+        .append_header((actix_web::http::header::LOCATION, next))
+        .finish())
+}
+
+/// The decrypted totp secret of `user_id`, enabled or not.
+async fn user_secret(
+    conn: &mut fastn_core::db::Conn,
+    ds: &fastn_ds::DocumentStore,
+    user_id: i64,
+) -> fastn_core::Result<Option<String>> {
+    use diesel::prelude::*;
+    use diesel_async::RunQueryDsl;
+
+    let secret: Option<Option<String>> = fastn_core::with_conn!(conn, |c| {
+        fastn_core::schema::fastn_user::table
+            .select(fastn_core::schema::fastn_user::totp_secret)
+            .filter(fastn_core::schema::fastn_user::id.eq(user_id))
+            .first(c)
+            .await
+            .optional()
+    })?;
+
+    match secret.flatten() {
+        Some(secret) => Ok(Some(
+            fastn_core::auth::utils::decrypt(ds, &secret)
+                .await
+                .map_err(|e| fastn_core::Error::generic(format!("failed to decrypt: {e}")))?,
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Checks `code`, of the authenticator app of `user_id`, or one of their recovery codes, which
+/// can not be used again. The error is the message for the user.
+async fn verify_user_code(
+    conn: &mut fastn_core::db::Conn,
+    ds: &fastn_ds::DocumentStore,
+    user_id: i64,
+    code: &str,
+) -> fastn_core::Result<Result<(), String>> {
+    use diesel::prelude::*;
+    use diesel_async::RunQueryDsl;
+
+    if too_many_attempts(user_id) {
+        return Ok(Err(TOO_MANY_ATTEMPTS.to_string()));
+    }
+
+    if !enabled(conn, user_id).await? {
+        return Ok(Err("two-factor authentication is not enabled".to_string()));
+    }
+
+    let secret = match user_secret(conn, ds, user_id).await? {
+        Some(secret) => secret,
+        None => return Ok(Err("two-factor authentication is not enabled".to_string())),
+    };
+
+    let now = chrono::Utc::now();
+    let is_totp = match code_step(&secret, code, now.timestamp() as u64) {
+        Some(step) => use_step(conn, user_id, step).await?,
+        None => false,
+    };
+
+    let mut is_recovery_code = false;
+    if !is_totp {
+        let hash = recovery_code_hash(code);
+        let affected = fastn_core::with_conn!(conn, |c| {
+            diesel::update(fastn_core::schema::fastn_user_recovery_code::table)
+                .set(fastn_core::schema::fastn_user_recovery_code::used_at.eq(&now))
+                .filter(fastn_core::schema::fastn_user_recovery_code::user_id.eq(user_id))
+                .filter(fastn_core::schema::fastn_user_recovery_code::code.eq(&hash))
+                .filter(fastn_core::schema::fastn_user_recovery_code::used_at.is_null())
+                .execute(c)
+                .await
+        })?;
+        is_recovery_code = affected > 0;
+
+        if is_recovery_code {
+            tracing::info!("recovery code used by user {user_id}");
+        }
+    }
+
+    Ok(record_attempt(user_id, is_totp || is_recovery_code))
+}
+
+const TOO_MANY_ATTEMPTS: &str = "too many attempts, try again in a few minutes";
+
+/// If `user_id` has failed `MAX_FAILED_ATTEMPTS` times in the last `ATTEMPT_WINDOW_MINUTES`.
+fn too_many_attempts(user_id: i64) -> bool {
+    let window_start = chrono::Utc::now() - chrono::Duration::minutes(ATTEMPT_WINDOW_MINUTES);
+
+    match FAILED_ATTEMPTS.lock().expect("poisoned lock").get(&user_id) {
+        Some((count, since)) => *since > window_start && *count >= MAX_FAILED_ATTEMPTS,
+        None => false,
+    }
+}
+
+/// Counts the failed attempts of `user_id`, the error is the message for the user.
+fn record_attempt(user_id: i64, success: bool) -> Result<(), String> {
+    if too_many_attempts(user_id) {
+        return Err(TOO_MANY_ATTEMPTS.to_string());
+    }
+
+    let mut failed_attempts = FAILED_ATTEMPTS.lock().expect("poisoned lock");
+
+    if success {
+        failed_attempts.remove(&user_id);
+        return Ok(());
+    }
+
+    count_failure(&mut failed_attempts, user_id, chrono::Utc::now());
+
+    Err("invalid code".to_string())
+}
+
+/// Counts a failed attempt of `user_id` at `now`. The counts whose window has passed are
+/// dropped, so users who once mistyped a code are not kept forever.
+fn count_failure(
+    failed_attempts: &mut std::collections::HashMap<i64, (u32, chrono::DateTime<chrono::Utc>)>,
+    user_id: i64,
+    now: chrono::DateTime<chrono::Utc>,
+) {
+    let window_start = now - chrono::Duration::minutes(ATTEMPT_WINDOW_MINUTES);
+    failed_attempts.retain(|_, (_, since)| *since > window_start);
+    failed_attempts.entry(user_id).or_insert((0, now)).0 += 1;
+}
+
+/// A random 160 bit secret, base32 encoded as authenticator apps expect it.
+fn new_secret() -> String {
+    let mut secret = [0u8; 20];
+    rand::RngCore::fill_bytes(&mut rand::thread_rng(), &mut secret);
+    base32_encode(&secret)
+}
+
+/// eg `ab12c-de34f`, shown once when enrolling.
+fn recovery_code() -> String {
+    let code = generate_key(10).to_lowercase();
+    format!("{}-{}", &code[..5], &code[5..])
+}
+
+/// Recovery codes are compared ignoring case, spaces and dashes.
+fn recovery_code_hash(code: &str) -> String {
+    let code: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    fastn_core::utils::generate_hash(code)
+}
+
+/// https://github.com/google/google-authenticator/wiki/Key-Uri-Format
+fn otpauth_uri(issuer: &str, account: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECONDS}",
+        issuer = percent_encode(issuer),
+        account = percent_encode(account),
+    )
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'@' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Marks the code of time step `step` of `user_id` as used, false if it, or a later one, was
+/// used already, https://datatracker.ietf.org/doc/html/rfc6238#section-5.2
+async fn use_step(
+    conn: &mut fastn_core::db::Conn,
+    user_id: i64,
+    step: u64,
+) -> fastn_core::Result<bool> {
+    use diesel::prelude::*;
+    use diesel_async::RunQueryDsl;
+
+    let step = step as i64;
+
+    // a single update, two requests with the same code can not both succeed
+    let affected = fastn_core::with_conn!(conn, |c| {
+        diesel::update(fastn_core::schema::fastn_user::table)
+            .set(fastn_core::schema::fastn_user::totp_last_step.eq(step))
+            .filter(fastn_core::schema::fastn_user::id.eq(user_id))
+            .filter(
+                fastn_core::schema::fastn_user::totp_last_step
+                    .is_null()
+                    .or(fastn_core::schema::fastn_user::totp_last_step.lt(step)),
+            )
+            .execute(c)
+            .await
+    })?;
+
+    if affected == 0 {
+        tracing::info!("totp code reused by user {user_id}");
+    }
+
+    Ok(affected > 0)
+}
+
+/// The time step `code` is the code of, if it is the code of `secret` at `unix_time`, or of the
+/// step before or after it, for clocks that are a little off.
+fn code_step(secret: &str, code: &str, unix_time: u64) -> Option<u64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let secret = base32_decode(secret)?;
+
+    let step = unix_time / STEP_SECONDS;
+    [step.saturating_sub(1), step, step + 1]
+        .into_iter()
+        .find(|step| {
+            let expected = format!("{:0width$}", hotp(&secret, *step), width = DIGITS as usize);
+            // compared in constant time, both have `DIGITS` digits
+            expected
+                .bytes()
+                .zip(code.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+        })
+}
+
+/// https://datatracker.ietf.org/doc/html/rfc4226#section-5.3
+fn hotp(secret: &[u8], counter: u64) -> u32 {
+    use hmac::Mac;
+
+    let mut mac =
+        hmac::Hmac::<sha1::Sha1>::new_from_slice(secret).expect("HMAC can take key of any size");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let code = u32::from_be_bytes([
+        hash[offset],
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]) & 0x7fff_ffff;
+
+    code % 10u32.pow(DIGITS)
+}
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// https://datatracker.ietf.org/doc/html/rfc4648#section-6, without padding
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    let (mut buffer, mut bits) = (0u32, 0u32);
+
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+
+    encoded
+}
+
+fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = vec![];
+    let (mut buffer, mut bits) = (0u32, 0u32);
+
+    for c in encoded.trim_end_matches('=').bytes() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|a| *a == c.to_ascii_uppercase())?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn base32() {
+        let secret = super::base32_encode(b"12345678901234567890");
+        assert_eq!(secret, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(
+            super::base32_decode(&secret).unwrap(),
+            b"12345678901234567890"
+        );
+        assert_eq!(super::base32_encode(b"f"), "MY");
+        assert_eq!(super::base32_decode("MY======").unwrap(), b"f");
+        assert!(super::base32_decode("not base32!").is_none());
+    }
+
+    #[test]
+    fn code_step() {
+        // https://datatracker.ietf.org/doc/html/rfc6238#appendix-B, the last 6 digits
+        let secret = super::base32_encode(b"12345678901234567890");
+        assert_eq!(super::code_step(&secret, "287082", 59), Some(1));
+        assert_eq!(
+            super::code_step(&secret, "081804", 1111111109),
+            Some(37037036)
+        );
+        assert_eq!(
+            super::code_step(&secret, "005924", 1234567890),
+            Some(41152263)
+        );
+        // the steps before and after are accepted, the step is the one of the code
+        assert_eq!(
+            super::code_step(&secret, "005924", 1234567890 + 30),
+            Some(41152263)
+        );
+        assert_eq!(super::code_step(&secret, "005924", 1234567890 + 60), None);
+        assert_eq!(super::code_step(&secret, "5924", 1234567890), None);
+    }
+
+    #[test]
+    fn recovery_code_hash() {
+        let code = super::recovery_code();
+        assert_eq!(code.len(), 11);
+        assert_eq!(
+            super::recovery_code_hash(&code),
+            super::recovery_code_hash(&code.replace('-', " ").to_uppercase())
+        );
+    }
+
+    #[test]
+    fn count_failure() {
+        let now = chrono::Utc::now();
+        let expired = now - chrono::Duration::minutes(super::ATTEMPT_WINDOW_MINUTES);
+        let mut failed_attempts =
+            std::collections::HashMap::from([(1, (3, now)), (2, (5, expired))]);
+
+        super::count_failure(&mut failed_attempts, 1, now);
+        // the count of user 2 is dropped, its window has passed
+        assert_eq!(
+            failed_attempts,
+            std::collections::HashMap::from([(1, (4, now))])
+        );
+
+        super::count_failure(&mut failed_attempts, 3, now);
+        assert_eq!(failed_attempts.get(&3), Some(&(1, now)));
+    }
+}
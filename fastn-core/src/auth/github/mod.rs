@@ -43,13 +43,9 @@ pub async fn login(
 pub async fn callback(
     req: &fastn_core::http::Request,
     ds: &fastn_ds::DocumentStore,
-    db_pool: &fastn_core::db::PgPool,
+    db_pool: &fastn_core::db::Pool,
     next: String,
 ) -> fastn_core::Result<fastn_core::http::Response> {
-    use diesel::prelude::*;
-    use diesel_async::RunQueryDsl;
-
-    let now = chrono::Utc::now();
     let code = req.q("code", "".to_string())?;
     // TODO: CSRF check
 
@@ -88,172 +84,25 @@ pub async fn callback(
         gh_user.email = Some(primary.email);
     }
 
-    let mut conn = db_pool
-        .get()
-        .await
-        .map_err(|e| fastn_core::Error::DatabaseError {
-            message: format!("Failed to get connection to db. {:?}", e),
-        })?;
-
-    let existing_email_and_user_id: Option<(fastn_core::utils::CiString, i64)> =
-        fastn_core::schema::fastn_user_email::table
-            .select((
-                fastn_core::schema::fastn_user_email::email,
-                fastn_core::schema::fastn_user_email::user_id,
-            ))
-            .filter(
-                fastn_core::schema::fastn_user_email::email.eq(fastn_core::utils::citext(
-                    gh_user
-                        .email
-                        .as_ref()
-                        .expect("Every github account has a primary email"),
-                )),
-            )
-            .first(&mut conn)
-            .await
-            .optional()?;
-
-    if existing_email_and_user_id.is_some() {
-        // user already exists, just create a session and redirect to next
-        let (_, user_id) = existing_email_and_user_id.unwrap();
-
-        let session_id: i64 = diesel::insert_into(fastn_core::schema::fastn_auth_session::table)
-            .values((
-                fastn_core::schema::fastn_auth_session::user_id.eq(&user_id),
-                fastn_core::schema::fastn_auth_session::created_at.eq(now),
-                fastn_core::schema::fastn_auth_session::updated_at.eq(now),
-            ))
-            .returning(fastn_core::schema::fastn_auth_session::id)
-            .get_result(&mut conn)
-            .await?;
-
-        tracing::info!("session created. session_id: {}", &session_id);
-
-        // TODO: access_token expires?
-        // handle refresh tokens
-        let token_id: i64 = diesel::insert_into(fastn_core::schema::fastn_oauthtoken::table)
-            .values((
-                fastn_core::schema::fastn_oauthtoken::session_id.eq(session_id),
-                fastn_core::schema::fastn_oauthtoken::token.eq(access_token),
-                fastn_core::schema::fastn_oauthtoken::provider.eq("github"),
-                fastn_core::schema::fastn_oauthtoken::created_at.eq(now),
-                fastn_core::schema::fastn_oauthtoken::updated_at.eq(now),
-            ))
-            .returning(fastn_core::schema::fastn_oauthtoken::id)
-            .get_result(&mut conn)
-            .await?;
-
-        tracing::info!("token stored. token_id: {}", &token_id);
-
-        return fastn_core::auth::set_session_cookie_and_redirect_to_next(
-            req, ds, session_id, next,
-        )
-        .await;
-    }
-
-    // first time login, create fastn_user
-    let user = diesel::insert_into(fastn_core::schema::fastn_user::table)
-        .values((
-            fastn_core::schema::fastn_user::username.eq(gh_user.login),
-            fastn_core::schema::fastn_user::password.eq(""),
-            // TODO: should present an onabording form that asks for a name if github name is null
-            fastn_core::schema::fastn_user::name.eq(gh_user.name.unwrap_or_default()),
-            fastn_core::schema::fastn_user::verified_email.eq(true),
-            fastn_core::schema::fastn_user::email.eq(fastn_core::utils::citext(
-                gh_user
-                    .email
-                    .as_ref()
-                    .expect("Every github account has a primary email"),
-            )),
-            fastn_core::schema::fastn_user::created_at.eq(now),
-            fastn_core::schema::fastn_user::updated_at.eq(now),
-        ))
-        .returning(fastn_core::auth::FastnUser::as_returning())
-        .get_result(&mut conn)
-        .await?;
-
-    tracing::info!("fastn_user created. user_id: {:?}", &user.id);
-
-    let email_id: i64 = diesel::insert_into(fastn_core::schema::fastn_user_email::table)
-        .values((
-            fastn_core::schema::fastn_user_email::user_id.eq(&user.id),
-            fastn_core::schema::fastn_user_email::email.eq(fastn_core::utils::citext(
-                gh_user
-                    .email
-                    .as_ref()
-                    .expect("Every github account has a primary email"),
-            )),
-            fastn_core::schema::fastn_user_email::verified.eq(true),
-            fastn_core::schema::fastn_user_email::primary.eq(true),
-            fastn_core::schema::fastn_user_email::created_at.eq(now),
-            fastn_core::schema::fastn_user_email::updated_at.eq(now),
-        ))
-        .returning(fastn_core::schema::fastn_user_email::id)
-        .get_result(&mut conn)
-        .await?;
-
-    tracing::info!("fastn_user_email created. email: {:?}", &email_id);
-
-    // TODO: session should store device that was used to login (chrome desktop on windows)
-    let session_id: i64 = diesel::insert_into(fastn_core::schema::fastn_auth_session::table)
-        .values((
-            fastn_core::schema::fastn_auth_session::user_id.eq(&user.id),
-            fastn_core::schema::fastn_auth_session::created_at.eq(now),
-            fastn_core::schema::fastn_auth_session::updated_at.eq(now),
-        ))
-        .returning(fastn_core::schema::fastn_auth_session::id)
-        .get_result(&mut conn)
-        .await?;
-
-    tracing::info!("session created. session_id: {}", &session_id);
-
-    // TODO: access_token expires?
-    // handle refresh tokens
-    let token_id: i64 = diesel::insert_into(fastn_core::schema::fastn_oauthtoken::table)
-        .values((
-            fastn_core::schema::fastn_oauthtoken::session_id.eq(session_id),
-            fastn_core::schema::fastn_oauthtoken::token.eq(access_token),
-            fastn_core::schema::fastn_oauthtoken::provider.eq("github"),
-            fastn_core::schema::fastn_oauthtoken::created_at.eq(now),
-            fastn_core::schema::fastn_oauthtoken::updated_at.eq(now),
-        ))
-        .returning(fastn_core::schema::fastn_oauthtoken::id)
-        .get_result(&mut conn)
-        .await?;
-
-    tracing::info!("token stored. token_id: {}", &token_id);
-
-    // Onboarding step is opt-in
-    let onboarding_enabled = ds.env("FASTN_AUTH_ADD_ONBOARDING_STEP").await.is_ok();
-
-    let next_path = if onboarding_enabled {
-        format!(
-            "{onboarding_route}?next={next}",
-            onboarding_route = fastn_core::auth::Route::Onboarding
-        )
-    } else {
-        next.to_string()
-    };
-
-    // redirect to onboarding route with a GET request
-    let mut resp =
-        fastn_core::auth::set_session_cookie_and_redirect_to_next(req, ds, session_id, next_path)
-            .await?;
-
-    if onboarding_enabled {
-        resp.add_cookie(
-            &actix_web::cookie::Cookie::build(
-                fastn_core::auth::FIRST_TIME_SESSION_COOKIE_NAME,
-                "1",
-            )
-            .domain(fastn_core::auth::utils::domain(req.connection_info.host()))
-            .path("/")
-            .finish(),
-        )
-        .map_err(|e| fastn_core::Error::generic(format!("failed to set cookie: {e}")))?;
-    }
-
-    Ok(resp)
+    let email = gh_user
+        .email
+        .expect("Every github account has a primary email");
+
+    fastn_core::auth::login_oauth_user(
+        req,
+        ds,
+        db_pool,
+        fastn_core::auth::OAuthLogin {
+            provider: fastn_core::auth::AuthProviders::GitHub.as_str().to_string(),
+            access_token,
+            claims: None,
+            username: gh_user.login,
+            name: gh_user.name.unwrap_or_default(),
+            email,
+        },
+        next,
+    )
+    .await
 }
 
 // it returns identities which matches to given input
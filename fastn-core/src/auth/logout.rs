@@ -2,39 +2,28 @@
 pub async fn logout(
     req: &fastn_core::http::Request,
     ds: &fastn_ds::DocumentStore,
-    db_pool: &fastn_core::db::PgPool,
+    db_pool: &fastn_core::db::Pool,
     next: String,
 ) -> fastn_core::Result<fastn_core::http::Response> {
     use diesel::prelude::*;
     use diesel_async::RunQueryDsl;
 
-    if let Some(session_data) = req.cookie(fastn_core::auth::SESSION_COOKIE_NAME) {
-        let session_data = fastn_core::auth::utils::decrypt(ds, &session_data)
+    if let Some(session_id) = fastn_core::auth::session::session_id(req, ds).await {
+        let mut conn = db_pool
+            .get()
             .await
-            .unwrap_or_default();
+            .map_err(|e| fastn_core::Error::DatabaseError {
+                message: format!("Failed to get connection to db. {:?}", e),
+            })?;
 
-        #[derive(serde::Deserialize)]
-        struct SessionData {
-            session_id: i64,
-        }
-
-        if let Ok(data) = serde_json::from_str::<SessionData>(session_data.as_str()) {
-            let session_id = data.session_id;
-
-            let mut conn = db_pool
-                .get()
-                .await
-                .map_err(|e| fastn_core::Error::DatabaseError {
-                    message: format!("Failed to get connection to db. {:?}", e),
-                })?;
-
-            let affected = diesel::delete(fastn_core::schema::fastn_auth_session::table)
+        let affected = fastn_core::with_conn!(&mut conn, |c| {
+            diesel::delete(fastn_core::schema::fastn_auth_session::table)
                 .filter(fastn_core::schema::fastn_auth_session::id.eq(&session_id))
-                .execute(&mut conn)
-                .await?;
+                .execute(c)
+                .await
+        })?;
 
-            tracing::info!("session destroyed for {session_id}. Rows affected {affected}.");
-        }
+        tracing::info!("session destroyed for {session_id}. Rows affected {affected}.");
     }
 
     Ok(actix_web::HttpResponse::Found()
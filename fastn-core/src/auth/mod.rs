@@ -1,10 +1,12 @@
 pub(crate) mod github;
+pub(crate) mod oidc;
 pub(crate) mod routes;
 pub(crate) mod utils;
 pub(crate) mod validator;
 
 mod email_password;
 mod logout;
+pub(crate) mod session;
 mod urls;
 
 pub(crate) use logout::logout;
@@ -14,7 +16,13 @@ pub const SESSION_COOKIE_NAME: &str = "fastn_session";
 pub const FIRST_TIME_SESSION_COOKIE_NAME: &str = "fastn_first_time_user";
 
 #[derive(
-    Debug, PartialEq, serde::Deserialize, serde::Serialize, diesel::Queryable, diesel::Selectable,
+    Clone,
+    Debug,
+    PartialEq,
+    serde::Deserialize,
+    serde::Serialize,
+    diesel::Queryable,
+    diesel::Selectable,
 )]
 #[diesel(table_name = fastn_core::schema::fastn_user)]
 pub struct FastnUser {
@@ -57,89 +65,173 @@ pub async fn get_auth_identities(
     cookies: &std::collections::HashMap<String, String>,
     identities: &[fastn_core::user_group::UserIdentity],
 ) -> fastn_core::Result<Vec<fastn_core::user_group::UserIdentity>> {
+    use diesel::prelude::*;
+    use diesel_async::RunQueryDsl;
+
     let mut matched_identities: Vec<fastn_core::user_group::UserIdentity> = vec![];
 
-    let session_id =
-        cookies
-            .get(fastn_core::auth::SESSION_COOKIE_NAME)
-            .ok_or(fastn_core::Error::GenericError(
-                "github user detail not found in the cookies".to_string(),
-            ));
+    let session_id = match cookies.get(fastn_core::auth::SESSION_COOKIE_NAME) {
+        Some(cookie) => fastn_core::auth::session::session_id_from_cookie(ds, cookie).await,
+        None => None,
+    };
 
-    match session_id {
-        Ok(session_id) => {
-            use diesel::prelude::*;
-            use diesel_async::RunQueryDsl;
+    let session_id = match session_id {
+        Some(session_id) => session_id,
+        None => return Ok(matched_identities),
+    };
 
-            let session_id: i64 = session_id.parse()?;
+    let user = match fastn_core::auth::get_authenticated_user_with_email(&session_id, ds).await {
+        Err(e) => {
+            tracing::error!("couldn't retrieve authenticated user. Reason: {:?}", e);
 
-            let pool = fastn_core::db::pool(ds).await.as_ref().map_err(|e| {
-                fastn_core::Error::DatabaseError {
-                    message: format!("Failed to get connection to db. {:?}", e),
-                }
+            if e == AuthUserError::UserDoesNotExist {
+                return Err(fastn_core::Error::GenericError(
+                    "User does not exist".to_string(),
+                ));
+            } else if let AuthUserError::UserExistsWithUnverifiedEmail(_) = e {
+                return Err(fastn_core::Error::GenericError(
+                    "User is not verified".to_string(),
+                ));
+            } else if e == AuthUserError::SessionExpired {
+                return Ok(matched_identities);
+            }
+
+            return Err(fastn_core::Error::GenericError(
+                "Failed to query database".to_string(),
+            ));
+        }
+
+        Ok(user) => user,
+    };
+
+    let pool =
+        fastn_core::db::pool(ds)
+            .await
+            .as_ref()
+            .map_err(|e| fastn_core::Error::DatabaseError {
+                message: format!("Failed to get connection to db. {:?}", e),
             })?;
 
-            let mut conn = pool
-                .get()
-                .await
-                .map_err(|e| fastn_core::Error::DatabaseError {
-                    message: format!("Failed to get connection to db. {:?}", e),
-                })?;
-
-            let token: String = fastn_core::schema::fastn_oauthtoken::table
-                .select(fastn_core::schema::fastn_oauthtoken::token)
-                .filter(fastn_core::schema::fastn_oauthtoken::session_id.eq(&session_id))
-                .filter(fastn_core::schema::fastn_oauthtoken::provider.eq("github"))
-                .first::<String>(&mut conn)
-                .await
-                .map_err(|e| fastn_core::Error::DatabaseError {
-                    message: format!("failed to get token from fastn_oauthtoken: {e}"),
-                })?;
-
-            let user =
-                match fastn_core::auth::get_authenticated_user_with_email(&session_id, ds).await {
-                    Err(e) => {
-                        tracing::error!("couldn't retrieve authenticated user. Reason: {:?}", e);
-
-                        if e == AuthUserError::UserDoesNotExist {
-                            return Err(fastn_core::Error::GenericError(
-                                "User does not exist".to_string(),
-                            ));
-                        } else if let AuthUserError::UserExistsWithUnverifiedEmail(_) = e {
-                            return Err(fastn_core::Error::GenericError(
-                                "User is not verified".to_string(),
-                            ));
-                        }
-
-                        return Err(fastn_core::Error::GenericError(
-                            "Failed to query database".to_string(),
-                        ));
-                    }
-
-                    Ok(user) => user,
-                };
+    let mut conn = pool
+        .get()
+        .await
+        .map_err(|e| fastn_core::Error::DatabaseError {
+            message: format!("Failed to get connection to db. {:?}", e),
+        })?;
 
+    let tokens: Vec<(String, String, Option<String>)> = fastn_core::with_conn!(&mut conn, |c| {
+        fastn_core::schema::fastn_oauthtoken::table
+            .select((
+                fastn_core::schema::fastn_oauthtoken::token,
+                fastn_core::schema::fastn_oauthtoken::provider,
+                fastn_core::schema::fastn_oauthtoken::claims,
+            ))
+            .filter(fastn_core::schema::fastn_oauthtoken::session_id.eq(&session_id))
+            .load(c)
+            .await
+            .map_err(|e| fastn_core::Error::DatabaseError {
+                message: format!("failed to get token from fastn_oauthtoken: {e}"),
+            })
+    })?;
+
+    for (token, provider, claims) in tokens {
+        if provider.eq(AuthProviders::GitHub.as_str()) {
             let github_ud: github::UserDetail = github::UserDetail {
                 access_token: token,
-                user,
+                user: user.clone(),
             };
 
             matched_identities.extend(github::matched_identities(github_ud, identities).await?);
+            continue;
         }
-        Err(err) => {
-            // TODO: What to do with this error
-            format!("{}{}", "github user detail not found in the cookies", err);
-        }
-    };
+
+        // users of the OpenID Connect provider, see `fastn_core::auth::oidc`
+        let claims = match claims.map(|claims| serde_json::from_str(claims.as_str())) {
+            Some(Ok(claims)) => claims,
+            Some(Err(e)) => {
+                tracing::warn!("invalid claims of {provider} token: {e}");
+                continue;
+            }
+            None => continue,
+        };
+        let config = match oidc::utils::Config::from_env(ds).await {
+            Ok(config) if config.provider.eq(&provider) => config,
+            _ => continue,
+        };
+
+        matched_identities.extend(oidc::matched_identities(
+            provider.as_str(),
+            config.identity_claims.as_slice(),
+            &claims,
+            identities,
+        ));
+    }
+
     Ok(matched_identities)
 }
 
+/// Logs in the user of `session_id`. Users that enabled two-factor authentication are asked
+/// for a code first, see `email_password::totp`, every login has to go through here.
 async fn set_session_cookie_and_redirect_to_next(
     req: &fastn_core::http::Request,
     ds: &fastn_ds::DocumentStore,
     session_id: i64,
     next: String,
 ) -> fastn_core::Result<fastn_core::http::Response> {
+    use diesel::prelude::*;
+    use diesel_async::RunQueryDsl;
+
+    let mut conn = session_connection(ds).await?;
+
+    let user_id: i64 = fastn_core::with_conn!(&mut conn, |c| {
+        fastn_core::schema::fastn_auth_session::table
+            .select(fastn_core::schema::fastn_auth_session::user_id)
+            .filter(fastn_core::schema::fastn_auth_session::id.eq(session_id))
+            .first(c)
+            .await
+    })?;
+
+    if fastn_core::auth::email_password::totp::enabled(&mut conn, user_id).await? {
+        return fastn_core::auth::email_password::totp::ask_for_code(
+            req, ds, user_id, session_id, next,
+        )
+        .await;
+    }
+
+    start_session_and_redirect_to_next(req, ds, session_id, next).await
+}
+
+async fn session_connection(
+    ds: &fastn_ds::DocumentStore,
+) -> fastn_core::Result<fastn_core::db::Conn> {
+    let pool =
+        fastn_core::db::pool(ds)
+            .await
+            .as_ref()
+            .map_err(|e| fastn_core::Error::DatabaseError {
+                message: format!("Failed to get connection to db. {:?}", e),
+            })?;
+
+    pool.get()
+        .await
+        .map_err(|e| fastn_core::Error::DatabaseError {
+            message: format!("Failed to get connection to db. {:?}", e),
+        })
+}
+
+/// Sets the cookie of `session_id`, without asking for a second factor.
+async fn start_session_and_redirect_to_next(
+    req: &fastn_core::http::Request,
+    ds: &fastn_ds::DocumentStore,
+    session_id: i64,
+    next: String,
+) -> fastn_core::Result<fastn_core::http::Response> {
+    let expires_at = {
+        let mut conn = session_connection(ds).await?;
+
+        fastn_core::auth::session::start(&mut conn, ds, session_id, req.user_agent()).await?
+    };
+
     let user = match fastn_core::auth::get_authenticated_user_with_email(&session_id, ds).await {
         Err(e) => {
             tracing::error!("couldn't retrieve authenticated user. Reason: {:?}", e);
@@ -165,15 +257,11 @@ async fn set_session_cookie_and_redirect_to_next(
         Ok(data) => data,
     };
 
+    tracing::info!("session {session_id} started for user {}", user.id);
+
+    // the user is read from the db on every request, so the cookie only has the session
     let cookie_json = serde_json::json!({
         "session_id": session_id,
-        "user": {
-            "id": user.id,
-            "username": user.username,
-            "name": user.name,
-            "email": user.email.0,
-            "verified_email": user.verified_email,
-        }
     });
 
     let encrypted_cookie = fastn_core::auth::utils::encrypt(ds, &cookie_json.to_string()).await;
@@ -186,7 +274,9 @@ async fn set_session_cookie_and_redirect_to_next(
             )
             .domain(fastn_core::auth::utils::domain(req.connection_info.host()))
             .path("/")
-            .permanent()
+            .max_age(actix_web::cookie::time::Duration::seconds(
+                (expires_at - chrono::Utc::now()).num_seconds(),
+            ))
             .http_only(true)
             .same_site(actix_web::cookie::SameSite::Lax)
             .finish(),
@@ -196,6 +286,187 @@ async fn set_session_cookie_and_redirect_to_next(
         .finish());
 }
 
+/// A user logged in with an oauth provider, eg github.
+pub(crate) struct OAuthLogin {
+    pub provider: String,
+    pub access_token: String,
+    /// what the provider told about the user, as json, see `fastn_core::auth::oidc`
+    pub claims: Option<String>,
+    pub username: String,
+    pub name: String,
+    pub email: String,
+}
+
+/// Starts a session for the user with the email of `login`, creating a `fastn_user` the first
+/// time, stores the token of the provider with it, and redirects to `next`.
+async fn login_oauth_user(
+    req: &fastn_core::http::Request,
+    ds: &fastn_ds::DocumentStore,
+    db_pool: &fastn_core::db::Pool,
+    login: OAuthLogin,
+    next: String,
+) -> fastn_core::Result<fastn_core::http::Response> {
+    use diesel::prelude::*;
+    use diesel_async::RunQueryDsl;
+
+    let now = chrono::Utc::now();
+
+    let mut conn = db_pool
+        .get()
+        .await
+        .map_err(|e| fastn_core::Error::DatabaseError {
+            message: format!("Failed to get connection to db. {:?}", e),
+        })?;
+
+    let existing_user_id: Option<i64> = fastn_core::with_conn!(&mut conn, |c| {
+        fastn_core::schema::fastn_user_email::table
+            .select(fastn_core::schema::fastn_user_email::user_id)
+            .filter(
+                fastn_core::schema::fastn_user_email::email
+                    .eq(fastn_core::utils::citext(login.email.as_str())),
+            )
+            .first(c)
+            .await
+            .optional()
+    })?;
+
+    let (user_id, first_login) = match existing_user_id {
+        // user already exists, just create a session and redirect to next
+        Some(user_id) => (user_id, false),
+        None => {
+            // first time login, create fastn_user
+            let username_taken: Option<i64> = fastn_core::with_conn!(&mut conn, |c| {
+                fastn_core::schema::fastn_user::table
+                    .select(fastn_core::schema::fastn_user::id)
+                    .filter(fastn_core::schema::fastn_user::username.eq(&login.username))
+                    .first(c)
+                    .await
+                    .optional()
+            })?;
+
+            // emails are unique
+            let username = if username_taken.is_some() {
+                login.email.clone()
+            } else {
+                login.username.clone()
+            };
+
+            let user = fastn_core::with_conn!(&mut conn, |c| {
+                diesel::insert_into(fastn_core::schema::fastn_user::table)
+                    .values((
+                        fastn_core::schema::fastn_user::username.eq(&username),
+                        fastn_core::schema::fastn_user::password.eq(""),
+                        // TODO: should present an onabording form that asks for a name if the
+                        // provider does not have it
+                        fastn_core::schema::fastn_user::name.eq(&login.name),
+                        fastn_core::schema::fastn_user::verified_email.eq(true),
+                        fastn_core::schema::fastn_user::email
+                            .eq(fastn_core::utils::citext(login.email.as_str())),
+                        fastn_core::schema::fastn_user::created_at.eq(now),
+                        fastn_core::schema::fastn_user::updated_at.eq(now),
+                    ))
+                    .returning(fastn_core::auth::FastnUser::as_returning())
+                    .get_result(c)
+                    .await
+            })?;
+
+            tracing::info!("fastn_user created. user_id: {:?}", &user.id);
+
+            let email_id: i64 = fastn_core::with_conn!(&mut conn, |c| {
+                diesel::insert_into(fastn_core::schema::fastn_user_email::table)
+                    .values((
+                        fastn_core::schema::fastn_user_email::user_id.eq(&user.id),
+                        fastn_core::schema::fastn_user_email::email
+                            .eq(fastn_core::utils::citext(login.email.as_str())),
+                        fastn_core::schema::fastn_user_email::verified.eq(true),
+                        fastn_core::schema::fastn_user_email::primary.eq(true),
+                        fastn_core::schema::fastn_user_email::created_at.eq(now),
+                        fastn_core::schema::fastn_user_email::updated_at.eq(now),
+                    ))
+                    .returning(fastn_core::schema::fastn_user_email::id)
+                    .get_result(c)
+                    .await
+            })?;
+
+            tracing::info!("fastn_user_email created. email: {:?}", &email_id);
+
+            (user.id, true)
+        }
+    };
+
+    let session_id: i64 = fastn_core::with_conn!(&mut conn, |c| {
+        diesel::insert_into(fastn_core::schema::fastn_auth_session::table)
+            .values((
+                fastn_core::schema::fastn_auth_session::user_id.eq(&user_id),
+                fastn_core::schema::fastn_auth_session::created_at.eq(now),
+                fastn_core::schema::fastn_auth_session::updated_at.eq(now),
+            ))
+            .returning(fastn_core::schema::fastn_auth_session::id)
+            .get_result(c)
+            .await
+    })?;
+
+    tracing::info!("session created. session_id: {}", &session_id);
+
+    // TODO: access_token expires?
+    // handle refresh tokens
+    let token_id: i64 = fastn_core::with_conn!(&mut conn, |c| {
+        diesel::insert_into(fastn_core::schema::fastn_oauthtoken::table)
+            .values((
+                fastn_core::schema::fastn_oauthtoken::session_id.eq(session_id),
+                fastn_core::schema::fastn_oauthtoken::token.eq(&login.access_token),
+                fastn_core::schema::fastn_oauthtoken::provider.eq(&login.provider),
+                fastn_core::schema::fastn_oauthtoken::claims.eq(&login.claims),
+                fastn_core::schema::fastn_oauthtoken::created_at.eq(now),
+                fastn_core::schema::fastn_oauthtoken::updated_at.eq(now),
+            ))
+            .returning(fastn_core::schema::fastn_oauthtoken::id)
+            .get_result(c)
+            .await
+    })?;
+
+    tracing::info!("token stored. token_id: {}", &token_id);
+
+    if !first_login {
+        return fastn_core::auth::set_session_cookie_and_redirect_to_next(
+            req, ds, session_id, next,
+        )
+        .await;
+    }
+
+    // Onboarding step is opt-in
+    let onboarding_enabled = ds.env("FASTN_AUTH_ADD_ONBOARDING_STEP").await.is_ok();
+
+    let next_path = if onboarding_enabled {
+        format!(
+            "{onboarding_route}?next={next}",
+            onboarding_route = fastn_core::auth::Route::Onboarding
+        )
+    } else {
+        next.to_string()
+    };
+
+    // redirect to onboarding route with a GET request
+    let mut resp =
+        fastn_core::auth::set_session_cookie_and_redirect_to_next(req, ds, session_id, next_path)
+            .await?;
+
+    if onboarding_enabled {
+        resp.add_cookie(
+            &actix_web::cookie::Cookie::build(
+                fastn_core::auth::FIRST_TIME_SESSION_COOKIE_NAME,
+                "1",
+            )
+            .domain(fastn_core::auth::utils::domain(req.connection_info.host()))
+            .path("/")
+            .finish(),
+        )
+        .map_err(|e| fastn_core::Error::generic(format!("failed to set cookie: {e}")))?;
+    }
+
+    Ok(resp)
+}
+
 #[derive(PartialEq, thiserror::Error, Debug)]
 pub enum AuthUserError {
     #[error("User exists but doesn't have a verified email")]
@@ -204,6 +475,9 @@ pub enum AuthUserError {
     #[error("User does not exist")]
     UserDoesNotExist,
 
+    #[error("Session has expired or was revoked")]
+    SessionExpired,
+
     #[error("Failed to query db. Details: {0:?}")]
     WrongQuery(#[from] diesel::result::Error),
 
@@ -211,7 +485,8 @@ pub enum AuthUserError {
     Connection { reason: String },
 }
 
-/// get FastnUser and its primary email from session
+/// get FastnUser and its primary email from session, if the session is active, see
+/// `fastn_core::auth::session`
 pub async fn get_authenticated_user_with_email(
     session_id: &i64,
     ds: &fastn_ds::DocumentStore,
@@ -230,25 +505,18 @@ pub async fn get_authenticated_user_with_email(
         reason: format!("{:?}", e),
     })?;
 
-    let user_id: Option<i64> = fastn_core::schema::fastn_auth_session::table
-        .select(fastn_core::schema::fastn_auth_session::user_id)
-        .filter(fastn_core::schema::fastn_auth_session::id.eq(session_id))
-        .first(&mut conn)
-        .await
-        .optional()?;
-
-    if user_id.is_none() {
-        return Err(AuthUserError::UserDoesNotExist);
-    }
-
-    let user_id = user_id.expect("user_id must be Some");
-
-    let user: Option<fastn_core::auth::FastnUser> = fastn_core::schema::fastn_user::table
-        .filter(fastn_core::schema::fastn_user::id.eq(user_id))
-        .select(fastn_core::auth::FastnUser::as_select())
-        .first(&mut conn)
-        .await
-        .optional()?;
+    let user_id = fastn_core::auth::session::active_user_id(&mut conn, ds, *session_id)
+        .await?
+        .ok_or(AuthUserError::SessionExpired)?;
+
+    let user: Option<fastn_core::auth::FastnUser> = fastn_core::with_conn!(&mut conn, |c| {
+        fastn_core::schema::fastn_user::table
+            .filter(fastn_core::schema::fastn_user::id.eq(user_id))
+            .select(fastn_core::auth::FastnUser::as_select())
+            .first(c)
+            .await
+            .optional()
+    })?;
 
     if user.is_none() {
         return Err(AuthUserError::UserDoesNotExist);
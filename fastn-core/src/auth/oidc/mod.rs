@@ -0,0 +1,522 @@
+pub(crate) mod utils;
+
+/// The login in progress, from `login()` to `callback()`.
+const STATE_COOKIE_NAME: &str = "fastn_oidc_state";
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct LoginState {
+    state: String,
+    nonce: String,
+    pkce_verifier: String,
+    next: String,
+}
+
+// route: /-/auth/oidc/
+// redirects to the provider, see `utils::Config`
+pub async fn login(
+    ds: &fastn_ds::DocumentStore,
+    req: &fastn_core::http::Request,
+    next: String,
+) -> fastn_core::Result<fastn_core::http::Response> {
+    let config = fastn_core::auth::oidc::utils::Config::from_env(ds).await?;
+    let metadata = fastn_core::auth::oidc::utils::provider_metadata(&config).await?;
+
+    let (pkce_challenge, pkce_verifier) = oauth2::PkceCodeChallenge::new_random_sha256();
+    let nonce = oauth2::CsrfToken::new_random();
+
+    let (authorize_url, state) =
+        fastn_core::auth::oidc::utils::oidc_client(&config, metadata, redirect_url(req))?
+            .authorize_url(oauth2::CsrfToken::new_random)
+            .add_scopes(config.scopes.iter().cloned().map(oauth2::Scope::new))
+            .add_extra_param("nonce", nonce.secret())
+            .set_pkce_challenge(pkce_challenge)
+            .url();
+
+    // `next` is not in the redirect url, providers want it to be the one registered with them
+    let login_state = serde_json::to_string(&LoginState {
+        state: state.secret().to_string(),
+        nonce: nonce.secret().to_string(),
+        pkce_verifier: pkce_verifier.secret().to_string(),
+        next,
+    })?;
+
+    Ok(actix_web::HttpResponse::Found()
+        .cookie(
+            actix_web::cookie::Cookie::build(
+                STATE_COOKIE_NAME,
+                fastn_core::auth::utils::encrypt(ds, login_state.as_str()).await,
+            )
+            .domain(fastn_core::auth::utils::domain(req.connection_info.host()))
+            .path("/")
+            .max_age(actix_web::cookie::time::Duration::minutes(10))
+            .http_only(true)
+            .same_site(actix_web::cookie::SameSite::Lax)
+            .finish(),
+        )
+        .append_header((actix_web::http::header::LOCATION, authorize_url.to_string()))
+        .finish())
+}
+
+fn redirect_url(req: &fastn_core::http::Request) -> String {
+    format!(
+        "{scheme}://{host}{callback_url}",
+        scheme = req.connection_info.scheme(),
+        host = req.connection_info.host(),
+        callback_url = fastn_core::auth::Route::OidcCallback,
+    )
+}
+
+// route: /-/auth/oidc/callback/
+// logs in the user the provider redirected back with
+pub async fn callback(
+    req: &fastn_core::http::Request,
+    ds: &fastn_ds::DocumentStore,
+    db_pool: &fastn_core::db::Pool,
+) -> fastn_core::Result<fastn_core::http::Response> {
+    let login_state = match req.cookie(STATE_COOKIE_NAME) {
+        Some(login_state) => fastn_core::auth::utils::decrypt(ds, &login_state)
+            .await
+            .ok()
+            .and_then(|v| serde_json::from_str::<LoginState>(v.as_str()).ok()),
+        None => None,
+    };
+
+    let login_state = match login_state {
+        Some(login_state) if login_state.state.eq(&req.q("state", "".to_string())?) => login_state,
+        _ => {
+            tracing::info!("oidc callback without the state of a login");
+            return Ok(fastn_core::http::api_error("Bad Request")?);
+        }
+    };
+
+    let mut resp = match login_user(req, ds, db_pool, login_state).await? {
+        Ok(resp) => resp,
+        Err(e) => fastn_core::server_error!("{}", e),
+    };
+
+    resp.add_cookie(
+        &actix_web::cookie::Cookie::build(STATE_COOKIE_NAME, "")
+            .domain(fastn_core::auth::utils::domain(req.connection_info.host()))
+            .path("/")
+            .expires(actix_web::cookie::time::OffsetDateTime::now_utc())
+            .finish(),
+    )
+    .map_err(|e| fastn_core::Error::generic(format!("failed to set cookie: {e}")))?;
+
+    Ok(resp)
+}
+
+/// Logs in the user of the code in `req`, errors of the provider are `Ok(Err(..))`.
+async fn login_user(
+    req: &fastn_core::http::Request,
+    ds: &fastn_ds::DocumentStore,
+    db_pool: &fastn_core::db::Pool,
+    login_state: LoginState,
+) -> fastn_core::Result<Result<fastn_core::http::Response, String>> {
+    let error: String = req.q("error", "".to_string())?;
+    if !error.is_empty() {
+        return Ok(Err(format!("login failed: {}", error)));
+    }
+
+    // TODO: if a user is already logged in using emailpassword and uses oidc
+    // present a merge account option
+    if req.ud(ds).await.is_some() {
+        return Ok(Ok(fastn_core::http::temporary_redirect(login_state.next)));
+    }
+
+    let config = fastn_core::auth::oidc::utils::Config::from_env(ds).await?;
+    let metadata = fastn_core::auth::oidc::utils::provider_metadata(&config).await?;
+
+    let (access_token, claims) = match user_claims(
+        &config,
+        metadata,
+        redirect_url(req),
+        req.q("code", "".to_string())?,
+        login_state.pkce_verifier,
+        login_state.nonce.as_str(),
+    )
+    .await?
+    {
+        Ok(user) => user,
+        Err(e) => return Ok(Err(e)),
+    };
+
+    let email = match fastn_core::auth::oidc::utils::claim(&claims, config.email_claim.as_str()) {
+        Some(email) => email,
+        None => {
+            return Ok(Err(format!(
+                "the `{}` claim is required, add the `email` scope",
+                config.email_claim
+            )))
+        }
+    };
+
+    let login = fastn_core::auth::OAuthLogin {
+        provider: config.provider.clone(),
+        access_token,
+        username: fastn_core::auth::oidc::utils::claim(&claims, config.username_claim.as_str())
+            .unwrap_or_else(|| email.clone()),
+        name: fastn_core::auth::oidc::utils::claim(&claims, config.name_claim.as_str())
+            .unwrap_or_default(),
+        email,
+        claims: Some(serde_json::to_string(&claims)?),
+    };
+
+    Ok(Ok(fastn_core::auth::login_oauth_user(
+        req,
+        ds,
+        db_pool,
+        login,
+        login_state.next,
+    )
+    .await?))
+}
+
+/// The access token and the claims of the user of `code`, from the id token and userinfo,
+/// errors of the provider are `Ok(Err(..))`.
+async fn user_claims(
+    config: &fastn_core::auth::oidc::utils::Config,
+    metadata: &fastn_core::auth::oidc::utils::ProviderMetadata,
+    redirect_url: String,
+    code: String,
+    pkce_verifier: String,
+    nonce: &str,
+) -> fastn_core::Result<Result<(String, serde_json::Map<String, serde_json::Value>), String>> {
+    let token = match fastn_core::auth::oidc::utils::oidc_client(config, metadata, redirect_url)?
+        .exchange_code(oauth2::AuthorizationCode::new(code))
+        .set_pkce_verifier(oauth2::PkceCodeVerifier::new(pkce_verifier))
+        .request_async(oauth2::reqwest::async_http_client)
+        .await
+    {
+        Ok(token) => token,
+        Err(e) => return Ok(Err(e.to_string())),
+    };
+
+    let access_token = oauth2::TokenResponse::access_token(&token)
+        .secret()
+        .to_string();
+
+    let id_token = match oauth2::TokenResponse::extra_fields(&token)
+        .id_token
+        .as_ref()
+    {
+        Some(id_token) => id_token,
+        None => return Ok(Err("the provider did not return an id_token".to_string())),
+    };
+
+    let jwks: jsonwebtoken::jwk::JwkSet =
+        fastn_core::auth::oidc::utils::get_json(metadata.jwks_uri.clone()).await?;
+
+    let mut claims = match fastn_core::auth::oidc::utils::verify_id_token(
+        id_token,
+        &jwks,
+        config,
+        metadata.issuer.as_str(),
+        nonce,
+    ) {
+        Ok(claims) => claims,
+        Err(e) => return Ok(Err(format!("invalid id_token: {e}"))),
+    };
+
+    // the id token of some providers only has `sub`, the rest is in userinfo
+    if let Some(userinfo_endpoint) = metadata.userinfo_endpoint.as_ref() {
+        let userinfo: serde_json::Map<String, serde_json::Value> =
+            fastn_core::http::get_api(userinfo_endpoint, access_token.as_str()).await?;
+
+        if userinfo.get("sub") != claims.get("sub") {
+            return Ok(Err(
+                "userinfo is not of the user of the id_token".to_string()
+            ));
+        }
+
+        claims.extend(userinfo);
+    }
+
+    // users are matched on their email, existing accounts too, the provider has to say it is
+    // theirs
+    if !config.allow_unverified_email
+        && claims.get("email_verified") != Some(&serde_json::Value::Bool(true))
+    {
+        return Ok(Err(
+            "the provider did not verify the email of the user, `email_verified` is not `true`"
+                .to_string(),
+        ));
+    }
+
+    Ok(Ok((access_token, claims)))
+}
+
+/// The identities of `identities` the user has, `<provider>-<claim>: <value>` for each claim of
+/// `identity_claims`, eg `keycloak-groups: admins` if `admins` is one of their `groups`.
+pub fn matched_identities(
+    provider: &str,
+    identity_claims: &[String],
+    claims: &serde_json::Map<String, serde_json::Value>,
+    identities: &[fastn_core::user_group::UserIdentity],
+) -> Vec<fastn_core::user_group::UserIdentity> {
+    let provider = provider.to_lowercase();
+    identities
+        .iter()
+        .filter(|identity| {
+            // identities are case insensitive, claims are not
+            let key = identity.key.to_lowercase();
+            let claim = match key
+                .strip_prefix(provider.as_str())
+                .and_then(|key| key.strip_prefix('-'))
+            {
+                Some(claim) => claim,
+                None => return false,
+            };
+            let claim = match identity_claims.iter().find(|c| c.to_lowercase().eq(claim)) {
+                Some(claim) => claim,
+                None => return false,
+            };
+            fastn_core::auth::oidc::utils::claim_values(claims, claim)
+                .iter()
+                .any(|value| value.to_lowercase().eq(&identity.value.to_lowercase()))
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    fn config() -> super::utils::Config {
+        super::utils::Config {
+            provider: "keycloak".to_string(),
+            discovery_url: "http://127.0.0.1:8080/realms/fastn".to_string(),
+            client_id: "fastn".to_string(),
+            client_secret: Some("secret".to_string()),
+            scopes: vec!["openid".to_string()],
+            username_claim: "preferred_username".to_string(),
+            name_claim: "name".to_string(),
+            email_claim: "email".to_string(),
+            identity_claims: vec!["groups".to_string()],
+            allow_unverified_email: false,
+        }
+    }
+
+    /// A provider on a random port, the user of code `verified` has a verified email, the email
+    /// of the others is not said to be verified.
+    fn mock_provider() -> String {
+        async fn discovery(issuer: actix_web::web::Data<String>) -> actix_web::HttpResponse {
+            let issuer = issuer.as_str();
+            actix_web::HttpResponse::Ok().json(serde_json::json!({
+                "issuer": issuer,
+                "authorization_endpoint": format!("{issuer}/auth"),
+                "token_endpoint": format!("{issuer}/token"),
+                "userinfo_endpoint": format!("{issuer}/userinfo"),
+                "jwks_uri": format!("{issuer}/jwks"),
+            }))
+        }
+
+        async fn token(
+            issuer: actix_web::web::Data<String>,
+            form: actix_web::web::Form<std::collections::HashMap<String, String>>,
+        ) -> actix_web::HttpResponse {
+            let mut claims = serde_json::json!({
+                "iss": issuer.as_str(),
+                "aud": "fastn",
+                "sub": "42",
+                "exp": chrono::Utc::now().timestamp() + 60,
+                "nonce": "n-0",
+                "email": "alice@example.com",
+            });
+            if form.get("code").map(String::as_str) == Some("verified") {
+                claims["email_verified"] = serde_json::Value::Bool(true);
+            }
+            let id_token = jsonwebtoken::encode(
+                &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS256),
+                &claims,
+                &jsonwebtoken::EncodingKey::from_secret(b"secret"),
+            )
+            .unwrap();
+            actix_web::HttpResponse::Ok().json(serde_json::json!({
+                "access_token": "at-0",
+                "token_type": "bearer",
+                "id_token": id_token,
+            }))
+        }
+
+        async fn userinfo() -> actix_web::HttpResponse {
+            actix_web::HttpResponse::Ok().json(serde_json::json!({
+                "sub": "42",
+                "groups": ["admins"],
+            }))
+        }
+
+        async fn jwks() -> actix_web::HttpResponse {
+            actix_web::HttpResponse::Ok().json(serde_json::json!({ "keys": [] }))
+        }
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+
+        let data = actix_web::web::Data::new(issuer.clone());
+        let server = actix_web::HttpServer::new(move || {
+            actix_web::App::new()
+                .app_data(data.clone())
+                .route(
+                    "/.well-known/openid-configuration",
+                    actix_web::web::get().to(discovery),
+                )
+                .route("/token", actix_web::web::post().to(token))
+                .route("/userinfo", actix_web::web::get().to(userinfo))
+                .route("/jwks", actix_web::web::get().to(jwks))
+        })
+        .workers(1)
+        .disable_signals()
+        .listen(listener)
+        .unwrap()
+        .run();
+        actix_web::rt::spawn(server);
+
+        issuer
+    }
+
+    #[actix_web::test]
+    async fn user_claims() {
+        let issuer = mock_provider();
+        let config = super::utils::Config {
+            discovery_url: issuer.clone(),
+            ..config()
+        };
+        let metadata: super::utils::ProviderMetadata =
+            super::utils::get_json(super::utils::discovery_url(issuer.as_str()))
+                .await
+                .unwrap();
+        async fn user_claims(
+            config: &super::utils::Config,
+            metadata: &super::utils::ProviderMetadata,
+            code: &str,
+            nonce: &str,
+        ) -> Result<(String, serde_json::Map<String, serde_json::Value>), String> {
+            super::user_claims(
+                config,
+                metadata,
+                format!("{}/callback", config.discovery_url),
+                code.to_string(),
+                "verifier".to_string(),
+                nonce,
+            )
+            .await
+            .unwrap()
+        }
+
+        let (access_token, claims) = user_claims(&config, &metadata, "verified", "n-0")
+            .await
+            .unwrap();
+        assert_eq!(access_token, "at-0");
+        assert_eq!(
+            super::utils::claim(&claims, "email").as_deref(),
+            Some("alice@example.com")
+        );
+        // merged from userinfo
+        assert_eq!(
+            super::utils::claim_values(&claims, "groups"),
+            vec!["admins".to_string()]
+        );
+
+        // not this login
+        assert!(user_claims(&config, &metadata, "verified", "n-1")
+            .await
+            .is_err());
+
+        // the email may not be of the user
+        assert!(user_claims(&config, &metadata, "unverified", "n-0")
+            .await
+            .is_err());
+
+        let config = super::utils::Config {
+            allow_unverified_email: true,
+            ..config
+        };
+        assert!(user_claims(&config, &metadata, "unverified", "n-0")
+            .await
+            .is_ok());
+    }
+
+    #[test]
+    fn discovery_url() {
+        assert_eq!(
+            super::utils::discovery_url("https://accounts.google.com/"),
+            "https://accounts.google.com/.well-known/openid-configuration"
+        );
+        assert_eq!(
+            super::utils::discovery_url(
+                "http://127.0.0.1:8080/realms/fastn/.well-known/openid-configuration"
+            ),
+            "http://127.0.0.1:8080/realms/fastn/.well-known/openid-configuration"
+        );
+    }
+
+    #[test]
+    fn verify_id_token() {
+        let issuer = "http://127.0.0.1:8080/realms/fastn";
+        let token = |aud: &str, nonce: &str| {
+            jsonwebtoken::encode(
+                &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS256),
+                &serde_json::json!({
+                    "iss": issuer,
+                    "aud": aud,
+                    "sub": "42",
+                    "exp": chrono::Utc::now().timestamp() + 60,
+                    "nonce": nonce,
+                    "email": "alice@example.com",
+                }),
+                &jsonwebtoken::EncodingKey::from_secret(b"secret"),
+            )
+            .unwrap()
+        };
+        let jwks = jsonwebtoken::jwk::JwkSet { keys: vec![] };
+        let verify =
+            |token: &str| super::utils::verify_id_token(token, &jwks, &config(), issuer, "n-0");
+
+        let claims = verify(token("fastn", "n-0").as_str()).unwrap();
+        assert_eq!(
+            super::utils::claim(&claims, "email").as_deref(),
+            Some("alice@example.com")
+        );
+        assert!(verify(token("other-client", "n-0").as_str()).is_err());
+        assert!(verify(token("fastn", "n-1").as_str()).is_err());
+    }
+
+    #[test]
+    fn matched_identities() {
+        let identity = |key: &str, value: &str| fastn_core::user_group::UserIdentity {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        let claims = match serde_json::json!({"groups": ["admins", "staff"], "email": "a@b.c"}) {
+            serde_json::Value::Object(claims) => claims,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(
+            super::matched_identities(
+                "keycloak",
+                &config().identity_claims,
+                &claims,
+                &[
+                    identity("keycloak-groups", "admins"),
+                    identity("keycloak-groups", "owners"),
+                    identity("github-groups", "staff"),
+                    // not an identity claim
+                    identity("keycloak-email", "a@b.c"),
+                ],
+            ),
+            vec![identity("keycloak-groups", "admins")]
+        );
+
+        // the provider and identities are case insensitive
+        assert_eq!(
+            super::matched_identities(
+                "Keycloak",
+                &config().identity_claims,
+                &claims,
+                &[identity("KeyCloak-Groups", "Admins")],
+            ),
+            vec![identity("KeyCloak-Groups", "Admins")]
+        );
+    }
+}
@@ -0,0 +1,242 @@
+/// The OpenID Connect provider, eg Google, GitLab or Keycloak, configured with:
+///
+/// - `FASTN_OIDC_DISCOVERY_URL`: the issuer, eg `https://accounts.google.com`, or its
+///   `/.well-known/openid-configuration`
+/// - `FASTN_OIDC_CLIENT_ID` and `FASTN_OIDC_CLIENT_SECRET`
+/// - `FASTN_OIDC_PROVIDER`: its name, `oidc` by default, the prefix of its identities
+/// - `FASTN_OIDC_SCOPES`: `openid email profile` by default
+/// - `FASTN_OIDC_USERNAME_CLAIM` (`preferred_username`), `FASTN_OIDC_NAME_CLAIM` (`name`) and
+///   `FASTN_OIDC_EMAIL_CLAIM` (`email`): the claims the fastn user is created from
+/// - `FASTN_OIDC_IDENTITY_CLAIMS`: `groups` by default, the claims that are identities of the
+///   user, eg `keycloak-groups: admins` for `FASTN_OIDC_PROVIDER=keycloak`
+/// - `FASTN_OIDC_ALLOW_UNVERIFIED_EMAIL`: `false` by default, logins are refused unless the
+///   provider says the email is verified, `email_verified: true`, as users are matched on their
+///   email. Only for providers that do not have the claim but verify emails.
+#[derive(Debug)]
+pub struct Config {
+    pub provider: String,
+    pub discovery_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub scopes: Vec<String>,
+    pub username_claim: String,
+    pub name_claim: String,
+    pub email_claim: String,
+    pub identity_claims: Vec<String>,
+    pub allow_unverified_email: bool,
+}
+
+impl Config {
+    pub async fn from_env(ds: &fastn_ds::DocumentStore) -> fastn_core::Result<Config> {
+        let env = |name: &'static str, default: &'static str| async move {
+            ds.env(name).await.unwrap_or_else(|_| default.to_string())
+        };
+        let list = |value: String| {
+            value
+                .split([' ', ','])
+                .filter(|v| !v.is_empty())
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        Ok(Config {
+            provider: env("FASTN_OIDC_PROVIDER", "oidc").await,
+            discovery_url: ds.env("FASTN_OIDC_DISCOVERY_URL").await.map_err(|e| {
+                fastn_core::Error::generic(format!("FASTN_OIDC_DISCOVERY_URL: {e}"))
+            })?,
+            client_id: ds
+                .env("FASTN_OIDC_CLIENT_ID")
+                .await
+                .map_err(|e| fastn_core::Error::generic(format!("FASTN_OIDC_CLIENT_ID: {e}")))?,
+            client_secret: ds.env("FASTN_OIDC_CLIENT_SECRET").await.ok(),
+            scopes: list(env("FASTN_OIDC_SCOPES", "openid email profile").await),
+            username_claim: env("FASTN_OIDC_USERNAME_CLAIM", "preferred_username").await,
+            name_claim: env("FASTN_OIDC_NAME_CLAIM", "name").await,
+            email_claim: env("FASTN_OIDC_EMAIL_CLAIM", "email").await,
+            identity_claims: list(env("FASTN_OIDC_IDENTITY_CLAIMS", "groups").await),
+            allow_unverified_email: ds
+                .env_bool("FASTN_OIDC_ALLOW_UNVERIFIED_EMAIL", false)
+                .await
+                .unwrap_or(false),
+        })
+    }
+}
+
+/// https://openid.net/specs/openid-connect-discovery-1_0.html#ProviderMetadata
+#[derive(Debug, serde::Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: Option<String>,
+    pub jwks_uri: String,
+    #[serde(default)]
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+}
+
+static PROVIDER_METADATA: tokio::sync::OnceCell<ProviderMetadata> =
+    tokio::sync::OnceCell::const_new();
+
+/// `https://accounts.google.com` is `https://accounts.google.com/.well-known/openid-configuration`
+pub fn discovery_url(url: &str) -> String {
+    if url.ends_with("/.well-known/openid-configuration") {
+        return url.to_string();
+    }
+    format!(
+        "{}/.well-known/openid-configuration",
+        url.trim_end_matches('/')
+    )
+}
+
+/// The metadata of the provider, fetched once.
+pub async fn provider_metadata(config: &Config) -> fastn_core::Result<&'static ProviderMetadata> {
+    PROVIDER_METADATA
+        .get_or_try_init(|| get_json(discovery_url(config.discovery_url.as_str())))
+        .await
+}
+
+pub async fn get_json<T: serde::de::DeserializeOwned>(url: String) -> fastn_core::Result<T> {
+    let response = reqwest::Client::new()
+        .get(url.as_str())
+        .header(reqwest::header::ACCEPT, "application/json")
+        .header(
+            reqwest::header::USER_AGENT,
+            reqwest::header::HeaderValue::from_static("fastn"),
+        )
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(fastn_core::Error::APIResponseError(format!(
+            "fastn-API-ERROR: {}, Error: {}",
+            url,
+            response.text().await?
+        )));
+    }
+
+    Ok(response.json().await?)
+}
+
+/// The `id_token` the token endpoint returns along with the access token.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct IdTokenFields {
+    pub id_token: Option<String>,
+}
+
+impl oauth2::ExtraTokenFields for IdTokenFields {}
+
+pub type TokenResponse =
+    oauth2::StandardTokenResponse<IdTokenFields, oauth2::basic::BasicTokenType>;
+
+pub type Client = oauth2::Client<
+    oauth2::basic::BasicErrorResponse,
+    TokenResponse,
+    oauth2::basic::BasicTokenType,
+    oauth2::basic::BasicTokenIntrospectionResponse,
+    oauth2::StandardRevocableToken,
+    oauth2::basic::BasicRevocationErrorResponse,
+>;
+
+pub fn oidc_client(
+    config: &Config,
+    metadata: &ProviderMetadata,
+    redirect_url: String,
+) -> fastn_core::Result<Client> {
+    let client = Client::new(
+        oauth2::ClientId::new(config.client_id.clone()),
+        config.client_secret.clone().map(oauth2::ClientSecret::new),
+        oauth2::AuthUrl::new(metadata.authorization_endpoint.clone())?,
+        Some(oauth2::TokenUrl::new(metadata.token_endpoint.clone())?),
+    )
+    .set_redirect_uri(oauth2::RedirectUrl::new(redirect_url)?);
+
+    // `client_secret_basic` is the default, some providers only support `client_secret_post`
+    let methods = &metadata.token_endpoint_auth_methods_supported;
+    if !methods.is_empty()
+        && !methods.iter().any(|m| m.eq("client_secret_basic"))
+        && methods.iter().any(|m| m.eq("client_secret_post"))
+    {
+        return Ok(client.set_auth_type(oauth2::AuthType::RequestBody));
+    }
+
+    Ok(client)
+}
+
+/// The claims of `id_token` if it is signed by the provider, with a key from `jwks`, or with
+/// the client secret, for `HS*` algorithms, and is for this client and this login.
+/// https://openid.net/specs/openid-connect-core-1_0.html#IDTokenValidation
+pub fn verify_id_token(
+    id_token: &str,
+    jwks: &jsonwebtoken::jwk::JwkSet,
+    config: &Config,
+    issuer: &str,
+    nonce: &str,
+) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+    let header = jsonwebtoken::decode_header(id_token).map_err(|e| e.to_string())?;
+
+    let key = match header.alg {
+        jsonwebtoken::Algorithm::HS256
+        | jsonwebtoken::Algorithm::HS384
+        | jsonwebtoken::Algorithm::HS512 => match config.client_secret.as_ref() {
+            Some(secret) => jsonwebtoken::DecodingKey::from_secret(secret.as_bytes()),
+            None => return Err("FASTN_OIDC_CLIENT_SECRET is not set".to_string()),
+        },
+        _ => {
+            let jwk = match header.kid.as_ref() {
+                Some(kid) => jwks.find(kid),
+                None => jwks.keys.first(),
+            };
+            match jwk {
+                Some(jwk) => jsonwebtoken::DecodingKey::from_jwk(jwk).map_err(|e| e.to_string())?,
+                None => return Err(format!("signing key {:?} not found", header.kid)),
+            }
+        }
+    };
+
+    let mut validation = jsonwebtoken::Validation::new(header.alg);
+    validation.set_audience(&[config.client_id.as_str()]);
+    validation.set_issuer(&[issuer]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+    let claims = jsonwebtoken::decode::<serde_json::Map<String, serde_json::Value>>(
+        id_token,
+        &key,
+        &validation,
+    )
+    .map_err(|e| e.to_string())?
+    .claims;
+
+    match claims.get("nonce") {
+        Some(serde_json::Value::String(n)) if n.eq(nonce) => Ok(claims),
+        _ => Err("nonce does not match".to_string()),
+    }
+}
+
+/// The string value of `claim`, numbers too.
+pub fn claim(claims: &serde_json::Map<String, serde_json::Value>, claim: &str) -> Option<String> {
+    match claims.get(claim)? {
+        serde_json::Value::String(v) => Some(v.to_string()),
+        serde_json::Value::Number(v) => Some(v.to_string()),
+        _ => None,
+    }
+}
+
+/// The values of `claim`, eg `groups: ["admins", "staff"]`.
+pub fn claim_values(
+    claims: &serde_json::Map<String, serde_json::Value>,
+    claim: &str,
+) -> Vec<String> {
+    match claims.get(claim) {
+        Some(serde_json::Value::Array(values)) => values
+            .iter()
+            .filter_map(|v| match v {
+                serde_json::Value::String(v) => Some(v.to_string()),
+                serde_json::Value::Null => None,
+                v => Some(v.to_string()),
+            })
+            .collect(),
+        Some(serde_json::Value::String(v)) => vec![v.to_string()],
+        Some(serde_json::Value::Null) | None => vec![],
+        Some(v) => vec![v.to_string()],
+    }
+}
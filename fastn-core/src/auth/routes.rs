@@ -18,13 +18,32 @@ pub async fn handle_auth(
 
     match Into::<Route>::into(req.path()) {
         Route::Login => fastn_core::auth::email_password::login(req_config, pool, next).await,
+        Route::LoginLink => {
+            fastn_core::auth::email_password::login_link(req_config, pool, next).await
+        }
+        Route::LoginLinkSent => fastn_core::auth::email_password::login_link_sent(req_config).await,
+        Route::LoginLinkConfirm => {
+            fastn_core::auth::email_password::login_link_confirm(req_config, pool, next).await
+        }
         Route::GithubLogin => {
             fastn_core::auth::github::login(&req_config.config.ds, &req, next).await
         }
         Route::GithubCallback => {
             fastn_core::auth::github::callback(&req, &req_config.config.ds, pool, next).await
         }
+        Route::OidcLogin => fastn_core::auth::oidc::login(&req_config.config.ds, &req, next).await,
+        Route::OidcCallback => {
+            fastn_core::auth::oidc::callback(&req, &req_config.config.ds, pool).await
+        }
         Route::Logout => fastn_core::auth::logout(&req, &req_config.config.ds, pool, next).await,
+        Route::LogoutEverywhere => {
+            fastn_core::auth::session::logout_everywhere(&req, &req_config.config.ds, pool, next)
+                .await
+        }
+        Route::Sessions => fastn_core::auth::session::sessions(req_config).await,
+        Route::RevokeSession => {
+            fastn_core::auth::session::revoke_session(req_config, pool, next).await
+        }
         Route::CreateAccount => {
             fastn_core::auth::email_password::create_account(req_config, pool, next).await
         }
@@ -55,6 +74,13 @@ pub async fn handle_auth(
         Route::SetPasswordSuccess => {
             fastn_core::auth::email_password::set_password_success(req_config).await
         }
+        Route::Totp => fastn_core::auth::email_password::totp(req_config, pool).await,
+        Route::TotpSetup => {
+            fastn_core::auth::email_password::totp_setup(req_config, pool, next).await
+        }
+        Route::TotpDisable => {
+            fastn_core::auth::email_password::totp_disable(req_config, pool, next).await
+        }
         Route::Invalid => Ok(fastn_core::not_found!("route not found: {}", req.path())),
     }
 }
@@ -0,0 +1,444 @@
+async fn days(ds: &fastn_ds::DocumentStore, env: &str, default: i64) -> chrono::Duration {
+    match ds.env(env).await {
+        Ok(days) => match days.trim().parse() {
+            Ok(days) => chrono::Duration::days(days),
+            Err(_) => {
+                tracing::warn!("{env} should be a number of days, using {default}");
+                chrono::Duration::days(default)
+            }
+        },
+        Err(_) => chrono::Duration::days(default),
+    }
+}
+
+/// A session starts when its cookie is set, eg after login, and expires
+/// `FASTN_SESSION_MAX_AGE_DAYS` (30 by default) later, or once it has not been used for
+/// `FASTN_SESSION_IDLE_TIMEOUT_DAYS` (7 by default), see `idle_timeout()`, whichever is first.
+/// It can be revoked before that. The cookie only has the id of the session, every request
+/// checks it against `fastn_auth_session`, see `active_user_id()`.
+pub(crate) async fn max_age(ds: &fastn_ds::DocumentStore) -> chrono::Duration {
+    days(ds, "FASTN_SESSION_MAX_AGE_DAYS", 30).await
+}
+
+pub(crate) async fn idle_timeout(ds: &fastn_ds::DocumentStore) -> chrono::Duration {
+    days(ds, "FASTN_SESSION_IDLE_TIMEOUT_DAYS", 7).await
+}
+
+/// `updated_at` is when the session was last used, it is not written more often than this
+const LAST_USED_PRECISION_SECONDS: i64 = 60;
+
+/// The id of the session in the session cookie of `req`, it may have expired.
+pub(crate) async fn session_id(
+    req: &fastn_core::http::Request,
+    ds: &fastn_ds::DocumentStore,
+) -> Option<i64> {
+    session_id_from_cookie(
+        ds,
+        req.cookie(fastn_core::auth::SESSION_COOKIE_NAME)?.as_str(),
+    )
+    .await
+}
+
+/// The id of the session in `session_data`, the value of the session cookie.
+pub(crate) async fn session_id_from_cookie(
+    ds: &fastn_ds::DocumentStore,
+    session_data: &str,
+) -> Option<i64> {
+    if session_data.is_empty() {
+        return None;
+    }
+
+    let session_data = match fastn_core::auth::utils::decrypt(ds, session_data).await {
+        Ok(v) => v,
+        Err(e) => {
+            tracing::warn!("failed to decrypt session data: {:?}", e);
+            return None;
+        }
+    };
+
+    #[derive(serde::Deserialize)]
+    struct SessionData {
+        session_id: i64,
+    }
+
+    match serde_json::from_str::<SessionData>(session_data.as_str()) {
+        Ok(sd) => Some(sd.session_id),
+        Err(e) => {
+            tracing::warn!("failed to deserialize session data: {:?}", e);
+            None
+        }
+    }
+}
+
+/// Starts session `session_id` as its cookie is set, returns when it expires.
+pub(crate) async fn start(
+    conn: &mut fastn_core::db::Conn,
+    ds: &fastn_ds::DocumentStore,
+    session_id: i64,
+    user_agent: Option<String>,
+) -> Result<chrono::DateTime<chrono::Utc>, diesel::result::Error> {
+    use diesel::prelude::*;
+    use diesel_async::RunQueryDsl;
+
+    let now = chrono::Utc::now();
+    let expires_at = now + max_age(ds).await;
+
+    fastn_core::with_conn!(conn, |c| {
+        diesel::update(fastn_core::schema::fastn_auth_session::table)
+            .set((
+                fastn_core::schema::fastn_auth_session::updated_at.eq(&now),
+                fastn_core::schema::fastn_auth_session::expires_at.eq(&expires_at),
+                fastn_core::schema::fastn_auth_session::user_agent.eq(&user_agent),
+            ))
+            .filter(fastn_core::schema::fastn_auth_session::id.eq(session_id))
+            .execute(c)
+            .await
+    })?;
+
+    Ok(expires_at)
+}
+
+/// The user of session `session_id` if it has not expired or been revoked. The session is
+/// marked as used.
+pub(crate) async fn active_user_id(
+    conn: &mut fastn_core::db::Conn,
+    ds: &fastn_ds::DocumentStore,
+    session_id: i64,
+) -> Result<Option<i64>, diesel::result::Error> {
+    use diesel::prelude::*;
+    use diesel_async::RunQueryDsl;
+
+    let session: Option<(
+        i64,
+        chrono::DateTime<chrono::Utc>,
+        Option<chrono::DateTime<chrono::Utc>>,
+        Option<chrono::DateTime<chrono::Utc>>,
+    )> = fastn_core::with_conn!(conn, |c| {
+        fastn_core::schema::fastn_auth_session::table
+            .select((
+                fastn_core::schema::fastn_auth_session::user_id,
+                fastn_core::schema::fastn_auth_session::updated_at,
+                fastn_core::schema::fastn_auth_session::expires_at,
+                fastn_core::schema::fastn_auth_session::revoked_at,
+            ))
+            .filter(fastn_core::schema::fastn_auth_session::id.eq(session_id))
+            .first(c)
+            .await
+            .optional()
+    })?;
+
+    let (user_id, last_used_at, expires_at, revoked_at) = match session {
+        Some(session) => session,
+        None => return Ok(None),
+    };
+
+    let now = chrono::Utc::now();
+    if !is_active(
+        now,
+        last_used_at,
+        expires_at,
+        revoked_at,
+        idle_timeout(ds).await,
+    ) {
+        tracing::info!("session {session_id} has expired or was revoked");
+        return Ok(None);
+    }
+
+    if now - last_used_at > chrono::Duration::seconds(LAST_USED_PRECISION_SECONDS) {
+        fastn_core::with_conn!(conn, |c| {
+            diesel::update(fastn_core::schema::fastn_auth_session::table)
+                .set(fastn_core::schema::fastn_auth_session::updated_at.eq(&now))
+                .filter(fastn_core::schema::fastn_auth_session::id.eq(session_id))
+                .execute(c)
+                .await
+        })?;
+    }
+
+    Ok(Some(user_id))
+}
+
+/// A session without `expires_at` never had its cookie set, eg that of a user who has not
+/// confirmed their email yet.
+fn is_active(
+    now: chrono::DateTime<chrono::Utc>,
+    last_used_at: chrono::DateTime<chrono::Utc>,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+    idle_timeout: chrono::Duration,
+) -> bool {
+    match expires_at {
+        Some(expires_at) => {
+            revoked_at.is_none() && now < expires_at && now < last_used_at + idle_timeout
+        }
+        None => false,
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+pub(crate) struct Session {
+    pub id: i64,
+    #[serde(rename = "created-at")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(rename = "last-used-at")]
+    pub last_used_at: chrono::DateTime<chrono::Utc>,
+    #[serde(rename = "expires-at")]
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    #[serde(rename = "user-agent")]
+    pub user_agent: Option<String>,
+    /// the session of the request
+    pub current: bool,
+}
+
+/// The active sessions of the user logged in with `req`, most recently used first, none if no
+/// one is logged in.
+pub(crate) async fn user_sessions(
+    req: &fastn_core::http::Request,
+    ds: &fastn_ds::DocumentStore,
+) -> fastn_core::Result<Vec<Session>> {
+    use diesel::prelude::*;
+    use diesel_async::RunQueryDsl;
+
+    let session_id = match session_id(req, ds).await {
+        Some(session_id) => session_id,
+        None => return Ok(vec![]),
+    };
+
+    let mut conn = connection(ds).await?;
+
+    let user_id = match active_user_id(&mut conn, ds, session_id).await? {
+        Some(user_id) => user_id,
+        None => return Ok(vec![]),
+    };
+
+    let sessions: Vec<(
+        i64,
+        chrono::DateTime<chrono::Utc>,
+        chrono::DateTime<chrono::Utc>,
+        Option<chrono::DateTime<chrono::Utc>>,
+        Option<String>,
+    )> = fastn_core::with_conn!(&mut conn, |c| {
+        fastn_core::schema::fastn_auth_session::table
+            .select((
+                fastn_core::schema::fastn_auth_session::id,
+                fastn_core::schema::fastn_auth_session::created_at,
+                fastn_core::schema::fastn_auth_session::updated_at,
+                fastn_core::schema::fastn_auth_session::expires_at,
+                fastn_core::schema::fastn_auth_session::user_agent,
+            ))
+            .filter(fastn_core::schema::fastn_auth_session::user_id.eq(user_id))
+            .filter(fastn_core::schema::fastn_auth_session::revoked_at.is_null())
+            .order(fastn_core::schema::fastn_auth_session::updated_at.desc())
+            .load(c)
+            .await
+    })?;
+
+    let now = chrono::Utc::now();
+    let idle_timeout = idle_timeout(ds).await;
+
+    Ok(sessions
+        .into_iter()
+        .filter_map(|(id, created_at, last_used_at, expires_at, user_agent)| {
+            if !is_active(now, last_used_at, expires_at, None, idle_timeout) {
+                return None;
+            }
+            Some(Session {
+                id,
+                created_at,
+                last_used_at,
+                expires_at: expires_at?,
+                user_agent,
+                current: id == session_id,
+            })
+        })
+        .collect())
+}
+
+async fn connection(ds: &fastn_ds::DocumentStore) -> fastn_core::Result<fastn_core::db::Conn> {
+    let pool =
+        fastn_core::db::pool(ds)
+            .await
+            .as_ref()
+            .map_err(|e| fastn_core::Error::DatabaseError {
+                message: format!("Failed to get connection to db. {:?}", e),
+            })?;
+
+    pool.get()
+        .await
+        .map_err(|e| fastn_core::Error::DatabaseError {
+            message: format!("Failed to get connection to db. {:?}", e),
+        })
+}
+
+// route: /-/auth/sessions/
+pub(crate) async fn sessions(
+    req_config: &mut fastn_core::RequestConfig,
+) -> fastn_core::Result<fastn_core::http::Response> {
+    let sessions = user_sessions(&req_config.request, &req_config.config.ds).await?;
+
+    // the session of the request is active if someone is logged in
+    if sessions.is_empty() {
+        return Ok(fastn_core::http::api_error("Not logged in")?);
+    }
+
+    Ok(fastn_core::http::api_ok(sessions)?)
+}
+
+// route: /-/auth/revoke-session/
+/// Revokes a session of the logged in user, eg `{"id": 42}`, from `/-/auth/sessions/`. The
+/// user is logged out if it is the session of the request.
+pub(crate) async fn revoke_session(
+    req_config: &mut fastn_core::RequestConfig,
+    db_pool: &fastn_core::db::Pool,
+    next: String,
+) -> fastn_core::Result<fastn_core::http::Response> {
+    use diesel::prelude::*;
+    use diesel_async::RunQueryDsl;
+
+    if req_config.request.method() != "POST" {
+        return Ok(fastn_core::not_found!("invalid route"));
+    }
+
+    #[derive(serde::Deserialize)]
+    struct Payload {
+        id: i64,
+    }
+
+    let payload = match req_config.request.json::<Payload>() {
+        Ok(payload) => payload,
+        Err(e) => {
+            return fastn_core::http::user_err(
+                vec![("payload".into(), vec![format!("invalid payload: {:?}", e)])],
+                fastn_core::http::StatusCode::OK,
+            );
+        }
+    };
+
+    let ds = &req_config.config.ds;
+    let current = session_id(&req_config.request, ds).await;
+
+    let mut conn = db_pool
+        .get()
+        .await
+        .map_err(|e| fastn_core::Error::DatabaseError {
+            message: format!("Failed to get connection to db. {:?}", e),
+        })?;
+
+    let user_id = match current {
+        Some(current) => active_user_id(&mut conn, ds, current).await?,
+        None => None,
+    };
+
+    let user_id = match user_id {
+        Some(user_id) => user_id,
+        None => return Ok(fastn_core::http::api_error("Not logged in")?),
+    };
+
+    let now = chrono::Utc::now();
+
+    let affected = fastn_core::with_conn!(&mut conn, |c| {
+        diesel::update(fastn_core::schema::fastn_auth_session::table)
+            .set(fastn_core::schema::fastn_auth_session::revoked_at.eq(&now))
+            .filter(fastn_core::schema::fastn_auth_session::id.eq(payload.id))
+            .filter(fastn_core::schema::fastn_auth_session::user_id.eq(user_id))
+            .filter(fastn_core::schema::fastn_auth_session::revoked_at.is_null())
+            .execute(c)
+            .await
+    })?;
+
+    if affected == 0 {
+        return fastn_core::http::user_err(
+            vec![("id".into(), vec!["session not found".into()])],
+            fastn_core::http::StatusCode::OK,
+        );
+    }
+
+    tracing::info!("session {} revoked by user {user_id}", payload.id);
+
+    if current == Some(payload.id) {
+        return fastn_core::auth::logout(&req_config.request, ds, db_pool, next).await;
+    }
+
+    Ok(actix_web::HttpResponse::Found()
+        .append_header((actix_web::http::header::LOCATION, next))
+        .finish())
+}
+
+// route: /-/auth/logout-everywhere/
+/// Revokes all the sessions of the logged in user, and logs them out, on a POST.
+pub(crate) async fn logout_everywhere(
+    req: &fastn_core::http::Request,
+    ds: &fastn_ds::DocumentStore,
+    db_pool: &fastn_core::db::Pool,
+    next: String,
+) -> fastn_core::Result<fastn_core::http::Response> {
+    use diesel::prelude::*;
+    use diesel_async::RunQueryDsl;
+
+    if req.method() != "POST" {
+        return Ok(fastn_core::not_found!("invalid route"));
+    }
+
+    if let Some(session_id) = session_id(req, ds).await {
+        let mut conn = db_pool
+            .get()
+            .await
+            .map_err(|e| fastn_core::Error::DatabaseError {
+                message: format!("Failed to get connection to db. {:?}", e),
+            })?;
+
+        if let Some(user_id) = active_user_id(&mut conn, ds, session_id).await? {
+            let now = chrono::Utc::now();
+
+            let affected = fastn_core::with_conn!(&mut conn, |c| {
+                diesel::update(fastn_core::schema::fastn_auth_session::table)
+                    .set(fastn_core::schema::fastn_auth_session::revoked_at.eq(&now))
+                    .filter(fastn_core::schema::fastn_auth_session::user_id.eq(user_id))
+                    .filter(fastn_core::schema::fastn_auth_session::revoked_at.is_null())
+                    .execute(c)
+                    .await
+            })?;
+
+            tracing::info!("{affected} sessions revoked for user {user_id}");
+        }
+    }
+
+    fastn_core::auth::logout(req, ds, db_pool, next).await
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn is_active() {
+        let now = chrono::Utc::now();
+        let day = chrono::Duration::days(1);
+        let idle = chrono::Duration::days(7);
+
+        assert!(super::is_active(
+            now,
+            now - day,
+            Some(now + day),
+            None,
+            idle
+        ));
+        // never started
+        assert!(!super::is_active(now, now, None, None, idle));
+        // expired
+        assert!(!super::is_active(now, now, Some(now - day), None, idle));
+        // idle for too long
+        assert!(!super::is_active(
+            now,
+            now - idle,
+            Some(now + day),
+            None,
+            idle
+        ));
+        // revoked
+        assert!(!super::is_active(
+            now,
+            now,
+            Some(now + day),
+            Some(now - day),
+            idle
+        ));
+    }
+}
@@ -1,8 +1,16 @@
 pub(crate) enum Route {
     Login,
+    LoginLink,
+    LoginLinkSent,
+    LoginLinkConfirm,
     GithubLogin,
     GithubCallback,
+    OidcLogin,
+    OidcCallback,
     Logout,
+    LogoutEverywhere,
+    Sessions,
+    RevokeSession,
     CreateAccount,
     EmailConfirmationSent,
     ConfirmEmail,
@@ -12,6 +20,9 @@ pub(crate) enum Route {
     ForgotPasswordSuccess,
     SetPassword,
     SetPasswordSuccess,
+    Totp,
+    TotpSetup,
+    TotpDisable,
     Invalid,
 }
 
@@ -19,9 +30,17 @@ impl From<&str> for Route {
     fn from(s: &str) -> Self {
         match s {
             "/-/auth/login/" => Self::Login,
+            "/-/auth/login-link/" => Self::LoginLink,
+            "/-/auth/login-link-sent/" => Self::LoginLinkSent,
+            "/-/auth/login-link/confirm/" => Self::LoginLinkConfirm,
             "/-/auth/github/" => Self::GithubLogin,
             "/-/auth/github/callback/" => Self::GithubCallback,
+            "/-/auth/oidc/" => Self::OidcLogin,
+            "/-/auth/oidc/callback/" => Self::OidcCallback,
             "/-/auth/logout/" => Self::Logout,
+            "/-/auth/logout-everywhere/" => Self::LogoutEverywhere,
+            "/-/auth/sessions/" => Self::Sessions,
+            "/-/auth/revoke-session/" => Self::RevokeSession,
             "/-/auth/create-account/" => Self::CreateAccount,
             "/-/auth/email-confirmation-sent/" => Self::EmailConfirmationSent,
             "/-/auth/confirm-email/" => Self::ConfirmEmail,
@@ -31,6 +50,9 @@ impl From<&str> for Route {
             "/-/auth/forgot-password-success/" => Self::ForgotPasswordSuccess,
             "/-/auth/set-password/" => Self::SetPassword,
             "/-/auth/set-password-success/" => Self::SetPasswordSuccess,
+            "/-/auth/totp/" => Self::Totp,
+            "/-/auth/totp/setup/" => Self::TotpSetup,
+            "/-/auth/totp/disable/" => Self::TotpDisable,
             _ => Self::Invalid,
         }
     }
@@ -40,9 +62,17 @@ impl std::fmt::Display for Route {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Login => write!(f, "/-/auth/login/"),
+            Self::LoginLink => write!(f, "/-/auth/login-link/"),
+            Self::LoginLinkSent => write!(f, "/-/auth/login-link-sent/"),
+            Self::LoginLinkConfirm => write!(f, "/-/auth/login-link/confirm/"),
             Self::GithubLogin => write!(f, "/-/auth/github/"),
             Self::GithubCallback => write!(f, "/-/auth/github/callback/"),
+            Self::OidcLogin => write!(f, "/-/auth/oidc/"),
+            Self::OidcCallback => write!(f, "/-/auth/oidc/callback/"),
             Self::Logout => write!(f, "/-/auth/logout/"),
+            Self::LogoutEverywhere => write!(f, "/-/auth/logout-everywhere/"),
+            Self::Sessions => write!(f, "/-/auth/sessions/"),
+            Self::RevokeSession => write!(f, "/-/auth/revoke-session/"),
             Self::CreateAccount => write!(f, "/-/auth/create-account/"),
             Self::EmailConfirmationSent => write!(f, "/-/auth/email-confirmation-sent/"),
             Self::ConfirmEmail => write!(f, "/-/auth/confirm-email/"),
@@ -52,6 +82,9 @@ impl std::fmt::Display for Route {
             Self::ForgotPasswordSuccess => write!(f, "/-/auth/forgot-password-success/"),
             Self::SetPassword => write!(f, "/-/auth/set-password/"),
             Self::SetPasswordSuccess => write!(f, "/-/auth/set-password-success/"),
+            Self::Totp => write!(f, "/-/auth/totp/"),
+            Self::TotpSetup => write!(f, "/-/auth/totp/setup/"),
+            Self::TotpDisable => write!(f, "/-/auth/totp/disable/"),
             Self::Invalid => write!(f, "invalid route"),
         }
     }
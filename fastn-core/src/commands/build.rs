@@ -1,4 +1,5 @@
 // #[tracing::instrument(skip(config))]
+#[allow(clippy::too_many_arguments)]
 pub async fn build(
     config: &fastn_core::Config,
     only_id: Option<&str>,
@@ -7,6 +8,7 @@ pub async fn build(
     test: bool,
     check_build: bool,
     zip_url: Option<&str>,
+    explain: bool,
 ) -> fastn_core::Result<()> {
     let build_dir = config.ds.root().join(".build");
     // Default css and js
@@ -22,16 +24,21 @@ pub async fn build(
         let documents = get_documents_for_current_package(config).await?;
         let zip_url = zip_url.map_or_else(|| config.package.zip.clone(), |z| Some(z.to_string()));
 
-        fastn_core::manifest::write_manifest_file(config, &build_dir, zip_url).await?;
+        let manifest =
+            fastn_core::manifest::write_manifest_file(config, &build_dir, zip_url).await?;
 
         match only_id {
             Some(id) => {
                 return handle_only_id(id, config, base_url, ignore_failed, test, documents).await
             }
             None => {
-                incremental_build(config, &documents, base_url, ignore_failed, test).await?;
+                incremental_build(config, &documents, base_url, ignore_failed, test, explain)
+                    .await?;
             }
         }
+
+        // Written after the build, so it is not overwritten by the package's own versions.json
+        fastn_core::manifest::write_versions_file(config, &build_dir, &manifest).await?;
     }
 
     // All redirect html files under .build
@@ -116,28 +123,35 @@ mod cache {
             }
             None => {
                 tracing::debug!("cached miss");
-                (
-                    false,
-                    Cache {
-                        build_content: std::collections::BTreeMap::new(),
-                        ftd_cache: std::collections::BTreeMap::new(),
-                        documents: std::collections::BTreeMap::new(),
-                        file_checksum: std::collections::BTreeMap::new(),
-                    },
-                )
+                (false, Cache::default())
             }
         };
         v.build_content = super::build_dir::get_build_content()?;
         Ok((cache_hit, v))
     }
 
-    #[derive(serde::Serialize, serde::Deserialize, Debug)]
+    #[derive(serde::Serialize, serde::Deserialize, Debug, Default)]
     pub(crate) struct Cache {
-        // fastn_version: String, // TODO
+        /// fastn that built the cached documents, all documents are rebuilt by another version
+        #[serde(default)]
+        pub(crate) fastn_version: Option<String>,
+        /// checksum of `FASTN.ftd`, all documents are rebuilt when it changes
+        #[serde(default)]
+        pub(crate) fastn_checksum: Option<String>,
+        /// checksum of the list of files of the package, generated modules like
+        /// `<package>/-/assets.ftd` are made from it
+        #[serde(default)]
+        pub(crate) files_checksum: Option<String>,
         #[serde(skip)]
         pub(crate) build_content: std::collections::BTreeMap<String, String>,
         #[serde(skip)]
         pub(crate) ftd_cache: std::collections::BTreeMap<String, Option<String>>,
+        /// Why none of the cached documents can be used, see `Cache::update_package()`
+        #[serde(skip)]
+        pub(crate) invalidated: Option<String>,
+        /// Whether files were added to or removed from the package since the previous build
+        #[serde(skip)]
+        pub(crate) files_changed: bool,
         pub(crate) documents: std::collections::BTreeMap<String, Document>,
         pub(crate) file_checksum: std::collections::BTreeMap<String, String>,
     }
@@ -147,27 +161,54 @@ mod cache {
             fastn_core::utils::cache_it(FILE_NAME, self)?;
             Ok(())
         }
-        pub(crate) fn get_file_hash(&mut self, path: &str) -> fastn_core::Result<String> {
-            if is_virtual_dep(path) {
-                // these are virtual file, they don't exist on disk, and hash only changes when
-                // fastn source changes
-                return Ok("hello".to_string());
-            }
-            match self.ftd_cache.get(path) {
-                Some(Some(v)) => Ok(v.to_owned()),
-                Some(None) => Err(fastn_core::Error::GenericError(path.to_string())),
-                None => {
-                    let hash = match fastn_core::utils::get_ftd_hash(path) {
-                        Ok(v) => v,
-                        Err(e) => {
-                            self.ftd_cache.insert(path.to_string(), None);
-                            return Err(e);
-                        }
-                    };
-                    self.ftd_cache.insert(path.to_string(), Some(hash.clone()));
-                    Ok(hash)
+
+        /// Compares what all documents depend on, the fastn version, `FASTN.ftd` and the list of
+        /// files, with the previous build.
+        pub(crate) async fn update_package(
+            &mut self,
+            ds: &fastn_ds::DocumentStore,
+            documents: &std::collections::BTreeMap<String, fastn_core::File>,
+        ) {
+            use itertools::Itertools;
+
+            let fastn_version = env!("CARGO_PKG_VERSION").to_string();
+            let fastn_checksum = self.get_file_hash(ds, "FASTN.ftd").await;
+            let files_checksum = fastn_core::utils::generate_hash(documents.keys().join("\n"));
+
+            self.invalidated = match self.fastn_version.as_ref() {
+                Some(version) if version.ne(&fastn_version) => {
+                    Some(format!("the previous build was made by fastn {}", version))
+                }
+                Some(_) if self.fastn_checksum.ne(&fastn_checksum) => {
+                    Some("FASTN.ftd changed".to_string())
                 }
+                Some(_) => None,
+                None => Some("the previous build was made by an older fastn".to_string()),
+            };
+            self.files_changed = self.files_checksum.as_ref().ne(&Some(&files_checksum));
+
+            self.fastn_version = Some(fastn_version);
+            self.fastn_checksum = fastn_checksum;
+            self.files_checksum = Some(files_checksum);
+        }
+
+        /// Checksum of the content of the file at `path`, relative to the package root, `None`
+        /// if it can not be read.
+        pub(crate) async fn get_file_hash(
+            &mut self,
+            ds: &fastn_ds::DocumentStore,
+            path: &str,
+        ) -> Option<String> {
+            if let Some(hash) = self.ftd_cache.get(path) {
+                return hash.clone();
             }
+            let hash = ds
+                .read_content(&ds.root().join(path))
+                .await
+                .ok()
+                .map(fastn_core::utils::generate_hash);
+            self.ftd_cache.insert(path.to_string(), hash.clone());
+            hash
         }
     }
 
@@ -180,65 +221,54 @@ mod cache {
     #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
     pub(crate) struct Document {
         pub(crate) html_checksum: String,
-        pub(crate) dependencies: Vec<String>,
+        /// Files imported while rendering the document, directly or not, relative to the
+        /// package root, eg `lib.ftd` or `.packages/foo.com/index.ftd`, with the checksum of
+        /// their content
+        #[serde(default)]
+        pub(crate) imports: std::collections::BTreeMap<String, String>,
+        /// Generated modules imported, eg `foo.com/-/assets.ftd`, see `Cache::files_checksum`
+        #[serde(default)]
+        pub(crate) generated_imports: Vec<String>,
+        #[serde(default)]
+        pub(crate) processors: Vec<fastn_core::library2022::ProcessorRun>,
     }
-}
 
-fn get_dependency_name_without_package_name(package_name: &str, dependency_name: &str) -> String {
-    if let Some(remaining) = dependency_name.strip_prefix(&format!("{}/", package_name)) {
-        remaining.to_string()
-    } else {
-        dependency_name.to_string()
+    impl Document {
+        pub(crate) fn new(
+            html_checksum: String,
+            req_config: fastn_core::RequestConfig,
+        ) -> Document {
+            let root = req_config.config.ds.root();
+            Document {
+                html_checksum,
+                imports: req_config
+                    .files_during_render
+                    .into_iter()
+                    .map(|(path, checksum)| {
+                        let path = fastn_ds::Path::new(path.as_str())
+                            .strip_prefix(&root)
+                            .map(|relative| relative.to_string())
+                            .unwrap_or(path);
+                        (path, checksum)
+                    })
+                    .collect(),
+                generated_imports: req_config
+                    .dependencies_during_render
+                    .into_iter()
+                    .filter(|path| is_virtual_dep(path))
+                    .collect(),
+                processors: req_config.processors_during_render,
+            }
+        }
     }
-    .trim_end_matches('/')
-    .to_string()
 }
 
 fn is_virtual_dep(path: &str) -> bool {
-    let path = std::path::Path::new(path);
-
     path.starts_with("$fastn$/")
         || path.ends_with("/-/fonts.ftd")
         || path.ends_with("/-/assets.ftd")
 }
 
-#[allow(clippy::too_many_arguments)]
-async fn handle_dependency_file(
-    config: &fastn_core::Config,
-    cache: &mut cache::Cache,
-    documents: &std::collections::BTreeMap<String, fastn_core::File>,
-    base_url: &str,
-    ignore_failed: bool,
-    test: bool,
-    name_without_package_name: String,
-    processed: &mut Vec<String>,
-) -> fastn_core::Result<()> {
-    for document in documents.values() {
-        if remove_extension(document.get_id()).eq(name_without_package_name.as_str())
-            || remove_extension(&document.get_id_with_package())
-                .eq(name_without_package_name.as_str())
-        {
-            let id = document.get_id().to_string();
-            if processed.contains(&id) {
-                continue;
-            }
-            handle_file(
-                document,
-                config,
-                base_url,
-                ignore_failed,
-                test,
-                true,
-                Some(cache),
-            )
-            .await?;
-            processed.push(id);
-        }
-    }
-
-    Ok(())
-}
-
 // removes deleted documents from cache and build folder
 async fn remove_deleted_documents(
     config: &fastn_core::Config,
@@ -291,6 +321,8 @@ async fn remove_deleted_documents(
     Ok(())
 }
 
+/// Every document records everything it was rendered from, see `cache::Document`, so only the
+/// documents whose inputs changed since the previous build are rendered again.
 #[tracing::instrument(skip(config, documents))]
 async fn incremental_build(
     config: &fastn_core::Config,
@@ -298,148 +330,28 @@ async fn incremental_build(
     base_url: &str,
     ignore_failed: bool,
     test: bool,
+    explain: bool,
 ) -> fastn_core::Result<()> {
     // https://fastn.com/rfc/incremental-build/
-    use itertools::Itertools;
-
     let (cache_hit, mut c) = cache::get()?;
+    c.update_package(&config.ds, documents).await;
 
-    let mut processed: Vec<String> = vec![];
+    for document in documents.values() {
+        handle_file(
+            document,
+            config,
+            base_url,
+            ignore_failed,
+            test,
+            true,
+            Some(&mut c),
+            explain,
+        )
+        .await?;
+    }
 
     if cache_hit {
-        let mut unresolved_dependencies = vec![];
-        let mut resolved_dependencies: Vec<String> = vec![];
-        let mut resolving_dependencies: Vec<String> = vec![];
-
-        for file in documents.values() {
-            // copy static files
-            if file.is_static() {
-                handle_file(
-                    file,
-                    config,
-                    base_url,
-                    ignore_failed,
-                    test,
-                    true,
-                    Some(&mut c),
-                )
-                .await?;
-                continue;
-            }
-
-            unresolved_dependencies.push(remove_extension(file.get_id()));
-        }
-
-        while let Some(unresolved_dependency) = unresolved_dependencies.pop() {
-            // println!("Current UR: {}", unresolved_dependency.as_str());
-            if let Some(doc) = c.documents.get(unresolved_dependency.as_str()) {
-                // println!(
-                //     "[INCREMENTAL BUILD][CACHE FOUND] Processing: {}",
-                //     &unresolved_dependency
-                // );
-
-                let mut own_resolved_dependencies: Vec<String> = vec![];
-
-                let dependencies: Vec<String> = doc
-                    .dependencies
-                    .iter()
-                    .map(|dep| get_dependency_name_without_package_name(&config.package.name, dep))
-                    .collect_vec();
-
-                for dep in &dependencies {
-                    if resolved_dependencies.contains(dep) || dep.eq(&unresolved_dependency) {
-                        own_resolved_dependencies.push(dep.to_string());
-                        continue;
-                    }
-                    unresolved_dependencies.push(dep.to_string());
-                }
-
-                // println!(
-                //     "[INCREMENTAL] [R]: {} [RV]: {} [UR]: {} [ORD]: {}",
-                //     &resolved_dependencies.len(),
-                //     &resolving_dependencies.len(),
-                //     &unresolved_dependencies.len(),
-                //     own_resolved_dependencies.len(),
-                // );
-
-                if own_resolved_dependencies.eq(&dependencies) {
-                    handle_dependency_file(
-                        config,
-                        &mut c,
-                        documents,
-                        base_url,
-                        ignore_failed,
-                        test,
-                        unresolved_dependency.to_string(),
-                        &mut processed,
-                    )
-                    .await?;
-
-                    resolved_dependencies.push(unresolved_dependency.to_string());
-                    if unresolved_dependencies.is_empty() {
-                        if let Some(resolving_dependency) = resolving_dependencies.pop() {
-                            if resolving_dependency.eq(&unresolved_dependency.as_str()) {
-                                // println!("[INCREMENTAL][CIRCULAR]: {}", &unresolved_dependency);
-                                continue;
-                            }
-                            unresolved_dependencies.push(resolving_dependency);
-                        }
-                    }
-                } else {
-                    // println!("Adding to RD: {}", unresolved_dependency.as_str());
-                    resolving_dependencies.push(unresolved_dependency.to_string());
-                }
-            } else {
-                if is_virtual_dep(&unresolved_dependency) {
-                    resolved_dependencies.push(unresolved_dependency.clone());
-                } else {
-                    // println!("Not found in cache UR: {}", unresolved_dependency.as_str());
-
-                    handle_dependency_file(
-                        config,
-                        &mut c,
-                        documents,
-                        base_url,
-                        ignore_failed,
-                        test,
-                        unresolved_dependency.to_string(),
-                        &mut processed,
-                    )
-                    .await?;
-
-                    resolved_dependencies.push(unresolved_dependency.clone());
-                }
-                if unresolved_dependencies.is_empty() {
-                    if let Some(resolving_dependency) = resolving_dependencies.pop() {
-                        if resolving_dependency.eq(&unresolved_dependency.as_str()) {
-                            // println!("[INCREMENTAL][CIRCULAR]: {}", &unresolved_dependency);
-                            continue;
-                        }
-                        unresolved_dependencies.push(resolving_dependency);
-                    }
-                }
-            }
-        }
-
         remove_deleted_documents(config, &mut c, documents).await?;
-    } else {
-        for document in documents.values() {
-            let id = document.get_id().to_string();
-            if processed.contains(&id) {
-                continue;
-            }
-            handle_file(
-                document,
-                config,
-                base_url,
-                ignore_failed,
-                test,
-                true,
-                Some(&mut c),
-            )
-            .await?;
-            processed.push(id);
-        }
     }
 
     c.cache_it()?;
@@ -458,7 +370,17 @@ async fn handle_only_id(
 ) -> fastn_core::Result<()> {
     for doc in documents.values() {
         if doc.get_id().eq(id) || doc.get_id_with_package().eq(id) {
-            return handle_file(doc, config, base_url, ignore_failed, test, false, None).await;
+            return handle_file(
+                doc,
+                config,
+                base_url,
+                ignore_failed,
+                test,
+                false,
+                None,
+                false,
+            )
+            .await;
         }
     }
 
@@ -469,6 +391,7 @@ async fn handle_only_id(
     )))
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn handle_file(
     document: &fastn_core::File,
     config: &fastn_core::Config,
@@ -477,6 +400,7 @@ async fn handle_file(
     test: bool,
     build_static_files: bool,
     cache: Option<&mut cache::Cache>,
+    explain: bool,
 ) -> fastn_core::Result<()> {
     let start = std::time::Instant::now();
     print!("Processing {} ... ", document.get_id_with_package());
@@ -489,6 +413,7 @@ async fn handle_file(
         test,
         build_static_files,
         cache,
+        explain,
     )
     .await;
     if process_status.is_ok() {
@@ -507,85 +432,69 @@ async fn handle_file(
     Ok(())
 }
 
-fn is_cached<'a>(
-    cache: Option<&'a mut cache::Cache>,
+/// Why `doc` has to be rendered again, `None` if `.build` has it up to date.
+async fn rebuild_reason(
+    cache: &mut cache::Cache,
     doc: &fastn_core::Document,
     file_path: &str,
-) -> (Option<&'a mut cache::Cache>, bool) {
-    let cache: &mut cache::Cache = match cache {
-        Some(c) => c,
-        None => {
-            // println!("cache miss: no have cache");
-            return (cache, false);
-        }
-    };
-
+    ds: &fastn_ds::DocumentStore,
+) -> Option<String> {
     let id = remove_extension(doc.id.as_str());
 
-    let cached_doc: cache::Document = match cache.documents.get(id.as_str()).cloned() {
+    let cached_doc = match cache.documents.get(id.as_str()).cloned() {
         Some(cached_doc) => cached_doc,
-        None => {
-            // println!("cache miss: no cache entry for {}", id.as_str());
-            return (Some(cache), false);
-        }
+        None => return Some("it was not built before".to_string()),
     };
 
-    // if it exists, check if the checksums match
-    // if they do, return
-    // dbg!(&cached_doc);
-    let doc_hash = match cache.build_content.get(file_path) {
-        Some(doc_hash) => doc_hash,
-        None => {
-            // println!("cache miss: document not present in .build: {}", file_path);
-            return (Some(cache), false);
-        }
-    };
+    if let Some(reason) = cache.invalidated.as_ref() {
+        return Some(reason.to_string());
+    }
 
-    // dbg!(doc_hash);
+    match cache.build_content.get(file_path) {
+        Some(html_checksum) if html_checksum.eq(&cached_doc.html_checksum) => {}
+        Some(_) => return Some(format!(".build/{} was modified", file_path)),
+        None => return Some(format!(".build/{} is missing", file_path)),
+    }
 
-    if doc_hash != &cached_doc.html_checksum {
-        // println!("cache miss: html file checksums don't match");
-        return (Some(cache), false);
+    if cache.file_checksum.get(id.as_str())
+        != Some(&fastn_core::utils::generate_hash(doc.content.as_str()))
+    {
+        return Some(format!("{} changed", doc.id));
     }
 
-    let file_checksum = match cache.file_checksum.get(id.as_str()).cloned() {
-        Some(file_checksum) => file_checksum,
-        None => {
-            // println!("cache miss: no cache entry for {}", id.as_str());
-            return (Some(cache), false);
+    for (path, checksum) in cached_doc.imports.iter() {
+        match cache.get_file_hash(ds, path).await {
+            Some(current) if current.eq(checksum) => {}
+            Some(_) => return Some(format!("imported {} changed", path)),
+            None => return Some(format!("imported {} was removed", path)),
         }
-    };
+    }
 
-    if file_checksum != fastn_core::utils::generate_hash(doc.content.as_str()) {
-        // println!("cache miss: ftd file checksums don't match");
-        return (Some(cache), false);
+    if cache.files_changed {
+        if let Some(module) = cached_doc.generated_imports.first() {
+            return Some(format!(
+                "files were added or removed, and it imports {}",
+                module
+            ));
+        }
     }
 
-    for dep in &cached_doc.dependencies {
-        let file_checksum = match cache.file_checksum.get(dep) {
-            None => {
-                // println!("cache miss: file {} not present in cache", dep);
-                return (Some(cache), false);
-            }
-            Some(file_checksum) => file_checksum.clone(),
-        };
+    if let Some(processor) = cached_doc.processors.iter().find(|p| !p.is_stable()) {
+        return Some(format!(
+            "the `{}` processor at {}:{} may return something else",
+            processor.processor, processor.module, processor.line_number
+        ));
+    }
 
-        let current_hash = match cache.get_file_hash(dep.as_str()) {
-            Ok(hash) => hash,
-            Err(_) => {
-                // println!("cache miss: dependency {} not present current folder", dep);
-                return (Some(cache), false);
-            }
-        };
+    None
+}
 
-        if file_checksum != current_hash {
-            // println!("cache miss: dependency {} checksums don't match", dep);
-            return (Some(cache), false);
-        }
+/// What `fastn build --explain` prints after the id of a document.
+fn explanation(rebuild_reason: Option<&str>) -> String {
+    match rebuild_reason {
+        Some(reason) => format!("(rebuilding: {}) ", reason),
+        None => "(up to date) ".to_string(),
     }
-
-    // println!("cache hit");
-    (Some(cache), true)
 }
 
 fn remove_extension(id: &str) -> String {
@@ -596,6 +505,7 @@ fn remove_extension(id: &str) -> String {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 #[tracing::instrument(skip(document, config, cache))]
 async fn handle_file_(
     document: &fastn_core::File,
@@ -605,6 +515,7 @@ async fn handle_file_(
     test: bool,
     build_static_files: bool,
     cache: Option<&mut cache::Cache>,
+    explain: bool,
 ) -> fastn_core::Result<()> {
     match document {
         fastn_core::File::Ftd(doc) => {
@@ -616,10 +527,19 @@ async fn handle_file_(
                 fastn_core::utils::replace_last_n(doc.id.as_str(), 1, ".ftd", "/index.html")
             };
 
-            let (cache, is_cached) = is_cached(cache, doc, file_path.as_str());
-            if is_cached {
-                return Ok(());
-            }
+            let cache = match cache {
+                Some(cache) => {
+                    let reason = rebuild_reason(cache, doc, file_path.as_str(), &config.ds).await;
+                    if explain {
+                        print!("{}", explanation(reason.as_deref()));
+                    }
+                    match reason {
+                        Some(_) => Some(cache),
+                        None => return Ok(()),
+                    }
+                }
+                None => None,
+            };
 
             fastn_core::utils::copy(
                 &config.ds.root().join(doc.id.as_str()),
@@ -633,35 +553,27 @@ async fn handle_file_(
                 return Ok(());
             }
 
-            let resp = {
-                let req = fastn_core::http::Request::default();
-                let mut req_config =
-                    fastn_core::RequestConfig::new(config, &req, doc.id.as_str(), base_url);
-                req_config.current_document = Some(document.get_id().to_string());
-
-                fastn_core::package::package_doc::process_ftd(
-                    &mut req_config,
-                    doc,
-                    base_url,
-                    build_static_files,
-                    test,
-                    file_path.as_str(),
-                )
-                .await
-            };
+            let req = fastn_core::http::Request::default();
+            let mut req_config =
+                fastn_core::RequestConfig::new(config, &req, doc.id.as_str(), base_url);
+            req_config.current_document = Some(document.get_id().to_string());
+
+            let resp = fastn_core::package::package_doc::process_ftd(
+                &mut req_config,
+                doc,
+                base_url,
+                build_static_files,
+                test,
+                file_path.as_str(),
+            )
+            .await;
 
             match (resp, ignore_failed) {
                 (Ok(r), _) => {
-                    // TODO: what to do with dependencies?
-                    // let dependencies = req_config.dependencies_during_render;
-                    let dependencies = vec![];
                     if let Some(cache) = cache {
                         cache.documents.insert(
                             remove_extension(doc.id.as_str()),
-                            cache::Document {
-                                html_checksum: r.checksum(),
-                                dependencies,
-                            },
+                            cache::Document::new(r.checksum(), req_config),
                         );
                         cache.file_checksum.insert(
                             remove_extension(doc.id.as_str()),
@@ -854,3 +766,186 @@ async fn process_static(
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    fn doc(content: &str) -> fastn_core::Document {
+        fastn_core::Document {
+            package_name: "foo.com".to_string(),
+            id: "index.ftd".to_string(),
+            content: content.to_string(),
+            parent_path: fastn_ds::Path::new("/p"),
+        }
+    }
+
+    /// A cache with `index.ftd` built from `-- ftd.text: hello`, importing `lib.ftd`.
+    async fn built() -> (fastn_ds::DocumentStore, super::cache::Cache) {
+        let ds = fastn_ds::DocumentStore::with_backend(
+            "/p",
+            std::sync::Arc::new(fastn_ds::backend::Memory::new()),
+        );
+        ds.write_content(&fastn_ds::Path::new("lib.ftd"), b"-- integer x: 1".to_vec())
+            .await
+            .unwrap();
+
+        let mut cache = super::cache::Cache::default();
+        cache
+            .build_content
+            .insert("index.html".to_string(), "HTML".to_string());
+        cache.file_checksum.insert(
+            "index".to_string(),
+            fastn_core::utils::generate_hash("-- ftd.text: hello"),
+        );
+        cache.documents.insert(
+            "index".to_string(),
+            super::cache::Document {
+                html_checksum: "HTML".to_string(),
+                imports: std::collections::BTreeMap::from([(
+                    "lib.ftd".to_string(),
+                    fastn_core::utils::generate_hash("-- integer x: 1"),
+                )]),
+                generated_imports: vec![],
+                processors: vec![],
+            },
+        );
+        (ds, cache)
+    }
+
+    async fn rebuild_reason(
+        ds: &fastn_ds::DocumentStore,
+        cache: &mut super::cache::Cache,
+        content: &str,
+    ) -> Option<String> {
+        super::rebuild_reason(cache, &doc(content), "index.html", ds).await
+    }
+
+    fn processor(processor: &str, line_number: usize) -> fastn_core::library2022::ProcessorRun {
+        fastn_core::library2022::ProcessorRun {
+            processor: processor.to_string(),
+            module: "foo.com/index".to_string(),
+            line_number,
+        }
+    }
+
+    #[tokio::test]
+    async fn rebuild_reason() {
+        let (ds, mut cache) = built().await;
+        assert_eq!(
+            rebuild_reason(&ds, &mut cache, "-- ftd.text: hello").await,
+            None
+        );
+        assert_eq!(
+            rebuild_reason(&ds, &mut cache, "-- ftd.text: hi")
+                .await
+                .as_deref(),
+            Some("index.ftd changed")
+        );
+
+        let (ds, mut cache) = built().await;
+        cache.documents.clear();
+        assert_eq!(
+            rebuild_reason(&ds, &mut cache, "-- ftd.text: hello")
+                .await
+                .as_deref(),
+            Some("it was not built before")
+        );
+
+        let (ds, mut cache) = built().await;
+        cache.invalidated = Some("FASTN.ftd changed".to_string());
+        assert_eq!(
+            rebuild_reason(&ds, &mut cache, "-- ftd.text: hello")
+                .await
+                .as_deref(),
+            Some("FASTN.ftd changed")
+        );
+
+        let (ds, mut cache) = built().await;
+        cache
+            .build_content
+            .insert("index.html".to_string(), "EDITED".to_string());
+        assert_eq!(
+            rebuild_reason(&ds, &mut cache, "-- ftd.text: hello")
+                .await
+                .as_deref(),
+            Some(".build/index.html was modified")
+        );
+        cache.build_content.clear();
+        assert_eq!(
+            rebuild_reason(&ds, &mut cache, "-- ftd.text: hello")
+                .await
+                .as_deref(),
+            Some(".build/index.html is missing")
+        );
+    }
+
+    #[tokio::test]
+    async fn rebuild_reason_imports() {
+        let (ds, mut cache) = built().await;
+        ds.write_content(&fastn_ds::Path::new("lib.ftd"), b"-- integer x: 2".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(
+            rebuild_reason(&ds, &mut cache, "-- ftd.text: hello")
+                .await
+                .as_deref(),
+            Some("imported lib.ftd changed")
+        );
+
+        let (ds, mut cache) = built().await;
+        ds.remove(&fastn_ds::Path::new("/p/lib.ftd")).await.unwrap();
+        assert_eq!(
+            rebuild_reason(&ds, &mut cache, "-- ftd.text: hello")
+                .await
+                .as_deref(),
+            Some("imported lib.ftd was removed")
+        );
+
+        let (ds, mut cache) = built().await;
+        cache
+            .documents
+            .get_mut("index")
+            .unwrap()
+            .generated_imports
+            .push("foo.com/-/assets.ftd".to_string());
+        assert_eq!(
+            rebuild_reason(&ds, &mut cache, "-- ftd.text: hello").await,
+            None
+        );
+        cache.files_changed = true;
+        assert_eq!(
+            rebuild_reason(&ds, &mut cache, "-- ftd.text: hello")
+                .await
+                .as_deref(),
+            Some("files were added or removed, and it imports foo.com/-/assets.ftd")
+        );
+    }
+
+    #[tokio::test]
+    async fn rebuild_reason_processors() {
+        let (ds, mut cache) = built().await;
+        let document = cache.documents.get_mut("index").unwrap();
+        document.processors.push(processor("toc", 3));
+        assert_eq!(
+            rebuild_reason(&ds, &mut cache, "-- ftd.text: hello").await,
+            None
+        );
+
+        let document = cache.documents.get_mut("index").unwrap();
+        document.processors.push(processor("sql", 7));
+        assert_eq!(
+            rebuild_reason(&ds, &mut cache, "-- ftd.text: hello")
+                .await
+                .as_deref(),
+            Some("the `sql` processor at foo.com/index:7 may return something else")
+        );
+    }
+
+    #[test]
+    fn explanation() {
+        assert_eq!(super::explanation(None), "(up to date) ");
+        assert_eq!(
+            super::explanation(Some("index.ftd changed")),
+            "(rebuilding: index.ftd changed) "
+        );
+    }
+}
@@ -20,6 +20,257 @@ pub async fn post_build_check(config: &fastn_core::Config) -> fastn_core::Result
     Ok(())
 }
 
+/// Prepares the query of every `sql`, `pg` and `package-query` processor of the package, without
+/// running it, and checks that the columns it returns fit the kind of its variable: each field of
+/// a record needs a column of the same name and of a compatible type, unless the field is
+/// optional, a list, or has a default, and other kinds need exactly one column.
+///
+/// The databases the queries use have to be reachable, so this only runs with
+/// `fastn check --queries`.
+pub async fn check_queries(config: &fastn_core::Config) -> fastn_core::Result<()> {
+    use itertools::Itertools;
+
+    println!("Checking queries ...");
+    let documents = config
+        .get_files(&config.package)
+        .await?
+        .into_iter()
+        .filter_map(|v| v.get_ftd_document())
+        .collect_vec();
+
+    let mut problems = vec![];
+    for document in documents {
+        // documents that do not parse fail to build, they are reported by `fastn build`
+        let parsed = match ftd::interpreter::ParsedDocument::parse(
+            document.id.as_str(),
+            document.content.as_str(),
+        ) {
+            Ok(parsed) => parsed,
+            Err(_) => continue,
+        };
+        let records: std::collections::HashMap<&str, &ftd::ast::Record> = parsed
+            .ast
+            .iter()
+            .filter_map(|ast| match ast {
+                ftd::ast::AST::Record(record) => Some((record.name.as_str(), record)),
+                _ => None,
+            })
+            .collect();
+
+        for ast in parsed.ast.iter() {
+            let variable = match ast {
+                ftd::ast::AST::VariableDefinition(variable) => variable,
+                _ => continue,
+            };
+            let processor = match variable
+                .processor
+                .as_deref()
+                .and_then(|p| p.rsplit('.').next())
+            {
+                Some(processor @ ("sql" | "pg" | "package-query")) => processor,
+                _ => continue,
+            };
+
+            let req = fastn_core::http::Request::default();
+            let req_config =
+                fastn_core::RequestConfig::new(config, &req, document.id.as_str(), "/");
+            let mut report = |problem: String| {
+                problems.push(format!(
+                    "{}:{}: {}",
+                    document.id, variable.line_number, problem
+                ))
+            };
+            let columns = match describe(processor, variable, &req_config).await {
+                Ok(Some(columns)) => columns,
+                Ok(None) => continue,
+                Err(e) => {
+                    report(e.to_string());
+                    continue;
+                }
+            };
+            for problem in kind_problems(&variable.kind, &columns, &records) {
+                report(problem);
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+    for problem in problems.iter() {
+        eprintln!("{}", problem);
+    }
+    Err(fastn_core::Error::GenericError(format!(
+        "{} queries do not fit the kind of their variable",
+        problems.len()
+    )))
+}
+
+async fn describe(
+    processor: &str,
+    variable: &ftd::ast::VariableDefinition,
+    req_config: &fastn_core::RequestConfig,
+) -> ftd::interpreter::Result<Option<Vec<fastn_core::library2022::processor::sql::Column>>> {
+    let (headers, query) = fastn_core::library2022::processor::sqlite::get_p1_data(
+        processor,
+        &variable.value,
+        req_config.document_id.as_str(),
+    )?;
+    fastn_core::library2022::processor::sql::describe(
+        processor,
+        &headers,
+        query.as_str(),
+        req_config.document_id.as_str(),
+        variable.line_number,
+        req_config,
+    )
+    .await
+}
+
+/// What does not fit `kind` in `columns`, see `check_queries()`. Only records defined in the
+/// same document are checked field by field.
+fn kind_problems(
+    kind: &ftd::ast::VariableKind,
+    columns: &[fastn_core::library2022::processor::sql::Column],
+    records: &std::collections::HashMap<&str, &ftd::ast::Record>,
+) -> Vec<String> {
+    use itertools::Itertools;
+
+    let record = match records.get(kind.kind.as_str()) {
+        Some(record) => record,
+        None if kind.kind.contains('.') => return vec![],
+        None if columns.len() != 1 => {
+            return vec![format!(
+                "`{}` needs one column, the query returns {}",
+                kind.kind,
+                columns.len()
+            )]
+        }
+        None if !columns[0].type_.converts_to(kind.kind.as_str()) => {
+            return vec![format!(
+                "column `{}` is {:?}, it does not convert to `{}`",
+                columns[0].name, columns[0].type_, kind.kind
+            )]
+        }
+        None => return vec![],
+    };
+
+    let mut problems = vec![];
+    for field in record.fields.iter() {
+        let column = columns.iter().find(|column| {
+            ftd::interpreter::utils::is_column_of_field(column.name.as_str(), field.name.as_str())
+        });
+        match column {
+            Some(column) if !column.type_.converts_to(field.kind.kind.as_str()) => {
+                problems.push(format!(
+                    "column `{}` is {:?}, it does not convert to `{}.{}`, a `{}`",
+                    column.name, column.type_, record.name, field.name, field.kind.kind
+                ))
+            }
+            Some(_) => {}
+            None if field.value.is_some()
+                || matches!(
+                    field.kind.modifier,
+                    Some(ftd::ast::VariableModifier::Optional | ftd::ast::VariableModifier::List)
+                ) => {}
+            None => problems.push(format!(
+                "no column for `{}.{}`, the columns are: {}",
+                record.name,
+                field.name,
+                columns.iter().map(|column| column.name.as_str()).join(", ")
+            )),
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod test {
+    use fastn_core::library2022::processor::sql::{Column, ColumnType};
+
+    #[track_caller]
+    fn p(source: &str, columns: &[(&str, ColumnType)]) -> Vec<String> {
+        let parsed = ftd::interpreter::ParsedDocument::parse("foo", source).unwrap();
+        let records = parsed
+            .ast
+            .iter()
+            .filter_map(|ast| match ast {
+                ftd::ast::AST::Record(record) => Some((record.name.as_str(), record)),
+                _ => None,
+            })
+            .collect();
+        let kind = parsed
+            .ast
+            .iter()
+            .find_map(|ast| match ast {
+                ftd::ast::AST::VariableDefinition(variable) => Some(&variable.kind),
+                _ => None,
+            })
+            .unwrap();
+        let columns = columns
+            .iter()
+            .map(|(name, type_)| Column {
+                name: name.to_string(),
+                type_: *type_,
+            })
+            .collect::<Vec<_>>();
+        super::kind_problems(kind, &columns, &records)
+    }
+
+    #[test]
+    fn kind_problems() {
+        let person = r#"
+-- record person:
+string full-name:
+integer age: 0
+optional string email:
+boolean admin:
+
+-- person list people:
+$processor$: pr.sql
+
+SELECT * FROM people
+"#;
+        assert!(p(
+            person,
+            &[
+                ("full_name", ColumnType::Text),
+                ("admin", ColumnType::Integer)
+            ]
+        )
+        .is_empty());
+        assert_eq!(
+            p(
+                person,
+                &[("name", ColumnType::Text), ("admin", ColumnType::Text)]
+            ),
+            vec![
+                "no column for `person.full-name`, the columns are: name, admin",
+                "column `admin` is Text, it does not convert to `person.admin`, a `boolean`",
+            ]
+        );
+
+        let count = r#"
+-- integer count:
+$processor$: pr.sql
+
+SELECT COUNT(*), MAX(id) FROM people
+"#;
+        assert_eq!(
+            p(
+                count,
+                &[("count", ColumnType::Integer), ("max", ColumnType::Integer)]
+            ),
+            vec!["`integer` needs one column, the query returns 2"]
+        );
+        assert_eq!(
+            p(count, &[("count", ColumnType::Decimal)]),
+            vec!["column `count` is Decimal, it does not convert to `integer`"]
+        );
+        assert!(p(count, &[("count", ColumnType::Unknown)]).is_empty());
+    }
+}
+
 // Todo: Rewrite this code
 /*#[async_recursion::async_recursion]
 async fn check_index_in_folders(
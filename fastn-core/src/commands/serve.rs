@@ -135,6 +135,7 @@ async fn serve_file(
                 path = path.as_str(),
                 error = e.to_string()
             );
+            fastn_core::watcher::build_error(path.as_str(), &e);
             fastn_core::server_error!("fastn-Error: path: {}, {:?}", path, e)
         }
     }
@@ -198,6 +199,10 @@ pub async fn serve_helper(
         }
         ("get", "/-/clear-cache/") => return clear_cache(config, req).await,
         ("get", "/-/poll/") => return fastn_core::watcher::poll().await,
+        ("get", "/-/live-reload/") => return fastn_core::watcher::live_reload().await,
+        ("get", "/-/pg/metrics/") => {
+            return fastn_core::library2022::processor::pg::metrics(&req_config).await;
+        }
         ("get", "/test/") => return test().await,
         _ => {}
     }
@@ -586,6 +591,8 @@ pub async fn listen(
         download_init_package(&package_download_base_url).await?;
     }
 
+    fastn_core::watcher::start(&config);
+
     let tcp_listener = match fastn_core::http::get_available_port(port, bind_address) {
         Some(listener) => listener,
         None => {
@@ -13,6 +13,10 @@ pub enum Error {
     FailedToRead(#[from] fastn_ds::ReadError),
 }
 
+/// Directory `fastn update --vendor` copies all dependencies to, along with their
+/// `config.json`. Packages are read from here instead of `.packages` when it exists.
+pub const VENDOR_DIRECTORY: &str = "vendor";
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct ConfigTemp {
     #[serde(rename = "package")]
@@ -49,10 +53,47 @@ impl ConfigTemp {
         Ok(())
     }
 
+    /// Writes the `config.json` of vendored packages in `vendor_root`, which is
+    /// `VENDOR_DIRECTORY` unless the packages are being vendored somewhere first.
+    pub async fn write_vendored(
+        ds: &fastn_ds::DocumentStore,
+        vendor_root: &fastn_ds::Path,
+        package_name: String,
+        all_packages: std::collections::BTreeMap<String, fastn_core::Manifest>,
+    ) -> Result<(), Error> {
+        let config_json_path = vendor_root.join("config.json");
+        let config_temp = ConfigTemp::new(package_name, all_packages);
+
+        ds.write_content(
+            &config_json_path,
+            serde_json::ser::to_vec_pretty(&config_temp)?,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns `None` if the dependencies of the package are not vendored.
+    pub async fn read_vendored(ds: &fastn_ds::DocumentStore) -> Result<Option<ConfigTemp>, Error> {
+        let config_json_path = ds.root().join(VENDOR_DIRECTORY).join("config.json");
+        match ConfigTemp::read_from(ds, &config_json_path).await {
+            Ok(config_temp) => Ok(Some(config_temp)),
+            Err(Error::NotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
     pub async fn read(ds: &fastn_ds::DocumentStore) -> Result<ConfigTemp, Error> {
         let dot_fastn = ds.root().join(".fastn");
         let config_json_path = dot_fastn.join("config.json");
-        let bytes = match ds.read_content(&config_json_path).await {
+        ConfigTemp::read_from(ds, &config_json_path).await
+    }
+
+    async fn read_from(
+        ds: &fastn_ds::DocumentStore,
+        config_json_path: &fastn_ds::Path,
+    ) -> Result<ConfigTemp, Error> {
+        let bytes = match ds.read_content(config_json_path).await {
             Ok(v) => v,
             Err(e) => {
                 if let fastn_ds::ReadError::NotFound = e {
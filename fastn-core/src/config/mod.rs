@@ -52,6 +52,11 @@ pub struct RequestConfig {
     pub downloaded_assets: std::collections::BTreeMap<String, String>,
     pub current_document: Option<String>,
     pub dependencies_during_render: Vec<String>,
+    /// Files of packages imported while rendering the current document, directly or not, by
+    /// path, with the checksum of their content
+    pub files_during_render: std::collections::BTreeMap<String, String>,
+    /// Processors run while rendering the current document
+    pub processors_during_render: Vec<fastn_core::library2022::ProcessorRun>,
     pub request: fastn_core::http::Request,
     pub config: Config,
     /// If the current module being parsed is a markdown file, `.markdown` contains the name and
@@ -86,6 +91,8 @@ impl RequestConfig {
             downloaded_assets: Default::default(),
             current_document: None,
             dependencies_during_render: vec![],
+            files_during_render: Default::default(),
+            processors_during_render: vec![],
             request: request.clone(),
             config: config.clone(),
             markdown: None,
@@ -601,6 +608,18 @@ impl Config {
         &self,
         package: &fastn_core::Package,
     ) -> fastn_core::Result<Vec<fastn_ds::Path>> {
+        let ignored_files = self.ignored_paths(package);
+        Ok(self
+            .ds
+            .get_all_file_path(
+                &self.get_root_for_package(package),
+                ignored_files.as_slice(),
+            )
+            .await)
+    }
+
+    /// Paths, relative to the package root, that are not part of the package content.
+    pub(crate) fn ignored_paths(&self, package: &fastn_core::Package) -> Vec<String> {
         let mut ignored_files = vec![
             ".history".to_string(),
             ".packages".to_string(),
@@ -609,15 +628,10 @@ impl Config {
             "rust-toolchain".to_string(),
             ".build".to_string(),
             "_tests".to_string(),
+            config_temp::VENDOR_DIRECTORY.to_string(),
         ];
         ignored_files.extend(package.ignored_paths.clone());
-        Ok(self
-            .ds
-            .get_all_file_path(
-                &self.get_root_for_package(package),
-                ignored_files.as_slice(),
-            )
-            .await)
+        ignored_files
     }
 
     // Input
@@ -1101,9 +1115,23 @@ impl Config {
     ) -> fastn_core::Result<fastn_core::Config> {
         let original_directory = fastn_ds::Path::new(std::env::current_dir()?.to_str().unwrap()); // todo: remove unwrap()
         let fastn_doc = utils::fastn_doc(&ds, &fastn_ds::Path::new("FASTN.ftd")).await?;
-        let config_temp = config_temp::ConfigTemp::read(&ds).await?;
         let mut package = fastn_core::Package::from_fastn_doc(&ds, &fastn_doc)?;
         let package_root = ds.root().join(".packages");
+        // Vendored packages are served at `.packages`, in place of whatever is installed there
+        let config_temp = match config_temp::ConfigTemp::read_vendored(&ds).await? {
+            Some(vendored) => {
+                ds.mount(
+                    &package_root,
+                    std::sync::Arc::new(fastn_ds::backend::Alias::new(
+                        &package_root,
+                        &ds.root().join(config_temp::VENDOR_DIRECTORY),
+                        ds.backend(),
+                    )),
+                );
+                vendored
+            }
+            None => config_temp::ConfigTemp::read(&ds).await?,
+        };
         let all_packages = {
             let mut all_packages = std::collections::BTreeMap::new();
             all_packages.insert(package.name.to_string(), package.to_owned());
@@ -1,44 +1,157 @@
-pub type PgPool = diesel_async::pooled_connection::deadpool::Pool<diesel_async::AsyncPgConnection>;
-pub type Conn = diesel_async::AsyncPgConnection;
+pub type PgConnection = diesel_async::AsyncPgConnection;
+pub type SqliteConnection =
+    diesel_async::sync_connection_wrapper::SyncConnectionWrapper<diesel::SqliteConnection>;
+pub type PgPool = diesel_async::pooled_connection::deadpool::Pool<PgConnection>;
+pub type SqlitePool = diesel_async::pooled_connection::deadpool::Pool<SqliteConnection>;
+pub type PoolError = diesel_async::pooled_connection::deadpool::PoolError;
 
-async fn create_pool(ds: &fastn_ds::DocumentStore) -> fastn_core::Result<PgPool> {
+/// The database of `FASTN_DB_URL`: postgres, or sqlite for urls like `sqlite:///auth.db`, a path
+/// relative to the package root, as in the `sql` processor.
+pub enum Pool {
+    Pg(PgPool),
+    Sqlite(SqlitePool),
+}
+
+/// A connection from `Pool`. Queries run on it with `fastn_core::with_conn!`.
+pub enum Conn {
+    Pg(diesel_async::pooled_connection::deadpool::Object<PgConnection>),
+    Sqlite(diesel_async::pooled_connection::deadpool::Object<SqliteConnection>),
+}
+
+impl Pool {
+    pub async fn get(&self) -> Result<Conn, PoolError> {
+        Ok(match self {
+            Pool::Pg(pool) => Conn::Pg(pool.get().await?),
+            Pool::Sqlite(pool) => Conn::Sqlite(pool.get().await?),
+        })
+    }
+}
+
+/// Runs `$body` with `$c` bound to the connection of `$conn`, a `&mut fastn_core::db::Conn`,
+/// whatever its database. The body is compiled once for each database, so queries have to be
+/// built in it:
+///
+/// ```ignore
+/// let name: String = fastn_core::with_conn!(&mut conn, |c| {
+///     fastn_core::schema::fastn_user::table
+///         .select(fastn_core::schema::fastn_user::name)
+///         .first(c)
+///         .await
+/// })?;
+/// ```
+#[macro_export]
+macro_rules! with_conn {
+    ($conn:expr, |$c:ident| $body:expr) => {
+        match $conn {
+            fastn_core::db::Conn::Pg($c) => {
+                let $c: &mut fastn_core::db::PgConnection = &mut **$c;
+                $body
+            }
+            fastn_core::db::Conn::Sqlite($c) => {
+                let $c: &mut fastn_core::db::SqliteConnection = &mut **$c;
+                $body
+            }
+        }
+    };
+}
+
+/// The path of the sqlite database of `db_url`, eg `<package-root>/auth.db` for
+/// `sqlite:///auth.db`. Both the pool and `migrate()` open the database at this path.
+fn sqlite_path(ds: &fastn_ds::DocumentStore, db_url: &str) -> Option<String> {
+    db_url
+        .strip_prefix("sqlite:///")
+        .map(|path| ds.root().join(path).to_string())
+}
+
+async fn create_pool(ds: &fastn_ds::DocumentStore) -> fastn_core::Result<Pool> {
     let db_url = ds.env("FASTN_DB_URL").await.map_err(|e| {
         fastn_core::error::Error::generic(format!("Failed to get db url from env: {e}"))
     })?;
 
+    if let Some(path) = sqlite_path(ds, db_url.as_str()) {
+        let mut config = diesel_async::pooled_connection::ManagerConfig::default();
+        config.custom_setup = Box::new(establish_sqlite);
+        let config = diesel_async::pooled_connection::AsyncDieselConnectionManager::new_with_config(
+            path, config,
+        );
+
+        return SqlitePool::builder(config)
+            .build()
+            .map(Pool::Sqlite)
+            .map_err(|e| {
+                fastn_core::error::Error::generic(format!("Failed to build db pool: {e}"))
+            });
+    }
+
     let config = diesel_async::pooled_connection::AsyncDieselConnectionManager::new(db_url);
 
     PgPool::builder(config)
         .build()
+        .map(Pool::Pg)
         .map_err(|e| fastn_core::error::Error::generic(format!("Failed to build db pool: {e}")))
 }
 
-static POOL_RESULT: tokio::sync::OnceCell<fastn_core::Result<PgPool>> =
+/// sqlite does not enforce foreign keys, eg `ON DELETE CASCADE`, unless asked to, on every
+/// connection. Concurrent writers wait for each other instead of failing.
+fn establish_sqlite(
+    path: &str,
+) -> futures::future::BoxFuture<'_, diesel::ConnectionResult<SqliteConnection>> {
+    Box::pin(async move {
+        use diesel_async::{AsyncConnection, SimpleAsyncConnection};
+
+        let mut conn = SqliteConnection::establish(path).await?;
+        conn.batch_execute("PRAGMA foreign_keys = ON; PRAGMA busy_timeout = 5000;")
+            .await
+            .map_err(diesel::ConnectionError::CouldntSetupConfiguration)?;
+        Ok(conn)
+    })
+}
+
+static POOL_RESULT: tokio::sync::OnceCell<fastn_core::Result<Pool>> =
     tokio::sync::OnceCell::const_new();
 
-pub async fn pool(ds: &fastn_ds::DocumentStore) -> &'static fastn_core::Result<PgPool> {
+pub async fn pool(ds: &fastn_ds::DocumentStore) -> &'static fastn_core::Result<Pool> {
     POOL_RESULT.get_or_init(|| create_pool(ds)).await
 }
 
-static MIGRATIONS: diesel_async_migrations::EmbeddedMigrations =
-    diesel_async_migrations::embed_migrations!();
+static MIGRATIONS: diesel_migrations::EmbeddedMigrations =
+    diesel_migrations::embed_migrations!("../migrations");
 
-/// run migrations on `db_url`
-pub async fn migrate(db_url: impl AsRef<str>) -> fastn_core::Result<()> {
-    use diesel_async::AsyncConnection;
+static SQLITE_MIGRATIONS: diesel_migrations::EmbeddedMigrations =
+    diesel_migrations::embed_migrations!("../migrations-sqlite");
 
-    let mut conn = diesel_async::AsyncPgConnection::establish(db_url.as_ref())
-        .await
-        .map_err(|e| fastn_core::Error::DatabaseError {
-            message: format!("Failed to connect to db. {:?}", e),
-        })?;
+/// run migrations on `db_url`, a sqlite database is created if it does not exist
+pub async fn migrate(
+    ds: &fastn_ds::DocumentStore,
+    db_url: impl AsRef<str>,
+) -> fastn_core::Result<()> {
+    use diesel::Connection;
+    use diesel_migrations::MigrationHarness;
 
-    MIGRATIONS
-        .run_pending_migrations(&mut conn)
-        .await
-        .map_err(|e| fastn_core::Error::DatabaseError {
-            message: format!("Failed to run migrations. {:?}", e),
-        })?;
+    let db_url = db_url.as_ref().to_string();
+    let sqlite_db = sqlite_path(ds, db_url.as_str());
+
+    // migrations are run by the sync diesel api
+    let migrated = tokio::task::spawn_blocking(move || {
+        let result =
+            match sqlite_db {
+                Some(path) => diesel::SqliteConnection::establish(path.as_str())
+                    .map_err(|e| format!("Failed to connect to db. {:?}", e))?
+                    .run_pending_migrations(SQLITE_MIGRATIONS)
+                    .map(|_| ()),
+                None => diesel_async::async_connection_wrapper::AsyncConnectionWrapper::<
+                    PgConnection,
+                >::establish(db_url.as_str())
+                .map_err(|e| format!("Failed to connect to db. {:?}", e))?
+                .run_pending_migrations(MIGRATIONS)
+                .map(|_| ()),
+            };
+        result.map_err(|e| format!("Failed to run migrations. {:?}", e))
+    })
+    .await
+    .map_err(|e| fastn_core::Error::DatabaseError {
+        message: format!("Failed to run migrations. {:?}", e),
+    })?;
 
-    Ok(())
+    migrated.map_err(|message| fastn_core::Error::DatabaseError { message })
 }
@@ -84,10 +84,16 @@ pub async fn interpret_helper(
                 let value = lib
                     .process(
                         ast.clone(),
-                        processor,
+                        processor.clone(),
                         &mut state.tdoc(doc.as_str(), line_number)?,
                     )
                     .await?;
+                lib.processors_during_render
+                    .push(fastn_core::library2022::ProcessorRun {
+                        processor,
+                        module: doc.to_string(),
+                        line_number,
+                    });
                 s = state.continue_after_processor(value, ast)?;
             }
             ftd::interpreter::Interpreter::StuckOnForeignVariable {
@@ -183,6 +189,7 @@ pub async fn resolve_import_2022(
                 "figma-cs-token".to_string(),
                 "figma-cs-token-old".to_string(),
                 "http".to_string(),
+                "graphql".to_string(),
                 "get-data".to_string(),
                 "toc".to_string(),
                 "sitemap".to_string(),
@@ -198,9 +205,11 @@ pub async fn resolve_import_2022(
                 "document-suffix".to_string(),
                 "document-name".to_string(),
                 "user-details".to_string(),
+                "user-sessions".to_string(),
                 "fastn-apps".to_string(),
                 "is-reader".to_string(),
                 "sql".to_string(),
+                "sql-execute".to_string(),
                 "package-query".to_string(),
                 "pg".to_string(),
                 "package-tree".to_string(),
@@ -254,7 +263,9 @@ pub async fn resolve_import_2022(
                 "figma-cs-token".to_string(),
                 "figma-cs-token-old".to_string(),
                 "http".to_string(),
+                "graphql".to_string(),
                 "sql".to_string(),
+                "sql-execute".to_string(),
                 "package-query".to_string(),
                 "pg".to_string(),
                 "toc".to_string(),
@@ -278,6 +289,7 @@ pub async fn resolve_import_2022(
                 "cr-meta".to_string(),
                 "request-data".to_string(),
                 "user-details".to_string(),
+                "user-sessions".to_string(),
                 "fastn-apps".to_string(),
                 "is-reader".to_string(),
                 "current-language".to_string(),
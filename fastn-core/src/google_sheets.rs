@@ -48,3 +48,207 @@ pub(crate) fn prepare_query_url(url: &str, query: &str, sheet: &Option<String>)
 
     query_url.finish()
 }
+
+const SHEETS_READONLY_SCOPE: &str = "https://www.googleapis.com/auth/spreadsheets.readonly";
+
+/// The access token of the service account and when it expires, see `access_token()`.
+static ACCESS_TOKEN: once_cell::sync::Lazy<
+    tokio::sync::Mutex<Option<(String, std::time::Instant)>>,
+> = once_cell::sync::Lazy::new(Default::default);
+
+/// The json response of the query at `url`, a url made by `prepare_query_url()`.
+///
+/// - Private sheets are read with the service account of `FASTN_GOOGLE_SERVICE_ACCOUNT`, the json
+///   key of the account, or the path of the key file in the package. The sheet has to be shared
+///   with the account. `FASTN_GOOGLE_SHEETS_API_KEY` is sent as the api key of the requests, it
+///   does not give access to private sheets.
+/// - Responses are not kept, use the `cache` header of the section to reuse them, see
+///   `fastn_core::library2022::cache`.
+/// - With `FASTN_GOOGLE_SHEETS_FIXTURES`, a directory of the package, responses are read from the
+///   files fetched responses are written to, so tests can run without network access once they
+///   ran with it. With `FASTN_GOOGLE_SHEETS_OFFLINE=true` as well, a query without a file is an
+///   error instead of being fetched.
+pub(crate) async fn fetch(ds: &fastn_ds::DocumentStore, url: &str) -> fastn_core::Result<String> {
+    let fixtures = match ds.env("FASTN_GOOGLE_SHEETS_FIXTURES").await {
+        Ok(dir) => Some(ds.root().join(dir)),
+        Err(_) => None,
+    };
+    let offline = ds.env_bool("FASTN_GOOGLE_SHEETS_OFFLINE", false).await?;
+    fetch_with_fixtures(ds, url, fixtures.as_ref(), offline).await
+}
+
+/// `fetch()`, with the responses in the `fixtures` directory, if any.
+async fn fetch_with_fixtures(
+    ds: &fastn_ds::DocumentStore,
+    url: &str,
+    fixtures: Option<&fastn_ds::Path>,
+    offline: bool,
+) -> fastn_core::Result<String> {
+    let fixture =
+        fixtures.map(|dir| dir.join(format!("{}.json", fastn_core::utils::generate_hash(url))));
+    if let Some(fixture) = fixture.as_ref() {
+        if ds.exists(fixture).await {
+            return Ok(ds.read_to_string(fixture).await?);
+        }
+        if offline {
+            return Err(fastn_core::Error::GenericError(format!(
+                "offline, and the response of {} is not in {}, run once online to record it",
+                url, fixture
+            )));
+        }
+    }
+
+    let json = fetch_(ds, url).await?;
+
+    if let Some(fixture) = fixture {
+        ds.write_content(&fixture, json.clone().into_bytes())
+            .await?;
+    }
+    Ok(json)
+}
+
+async fn fetch_(ds: &fastn_ds::DocumentStore, url: &str) -> fastn_core::Result<String> {
+    let mut url = url.to_string();
+    if let Ok(key) = ds.env("FASTN_GOOGLE_SHEETS_API_KEY").await {
+        url = url::form_urlencoded::Serializer::new(url)
+            .append_pair("key", key.as_str())
+            .finish();
+    }
+
+    let mut headers = std::collections::HashMap::new();
+    if let Some(token) = access_token(ds).await? {
+        headers.insert("authorization".to_string(), format!("Bearer {}", token));
+    }
+
+    let response = fastn_core::http::http_get_with_cookie(url.as_str(), None, &headers, false)
+        .await?
+        .0?;
+    let response = String::from_utf8(response).map_err(|e| fastn_core::Error::UsageError {
+        message: format!("Cannot convert the response to string: {}", e),
+    })?;
+
+    match extract_json(response.as_str())? {
+        Some(json) => Ok(json),
+        None => Err(fastn_core::Error::APIResponseError(
+            "Invalid Query Response. Please ensure that your Google Sheet is public, or shared \
+            with the service account of FASTN_GOOGLE_SERVICE_ACCOUNT."
+                .to_string(),
+        )),
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct ServiceAccountKey {
+    client_email: String,
+    private_key: String,
+    token_uri: String,
+}
+
+#[derive(serde::Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(serde::Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+/// An access token of the service account of `FASTN_GOOGLE_SERVICE_ACCOUNT`, if it is set,
+/// see <https://developers.google.com/identity/protocols/oauth2/service-account#httprest>.
+/// Tokens are reused until a minute before they expire.
+async fn access_token(ds: &fastn_ds::DocumentStore) -> fastn_core::Result<Option<String>> {
+    let key = match ds.env("FASTN_GOOGLE_SERVICE_ACCOUNT").await {
+        Ok(key) => key,
+        Err(_) => return Ok(None),
+    };
+
+    let mut token = ACCESS_TOKEN.lock().await;
+    if let Some((access_token, expires_at)) = token.as_ref() {
+        if std::time::Instant::now() < *expires_at {
+            return Ok(Some(access_token.to_string()));
+        }
+    }
+
+    let key = match key.trim_start().starts_with('{') {
+        true => key,
+        false => ds.read_to_string(&ds.root().join(key)).await?,
+    };
+    let key: ServiceAccountKey = serde_json::from_str(key.as_str())?;
+
+    let now = chrono::Utc::now().timestamp();
+    let assertion = jsonwebtoken::encode(
+        &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256),
+        &Claims {
+            iss: key.client_email.as_str(),
+            scope: SHEETS_READONLY_SCOPE,
+            aud: key.token_uri.as_str(),
+            iat: now,
+            exp: now + 3600,
+        },
+        &jsonwebtoken::EncodingKey::from_rsa_pem(key.private_key.as_bytes()).map_err(|e| {
+            fastn_core::Error::GenericError(format!(
+                "invalid private key in FASTN_GOOGLE_SERVICE_ACCOUNT: {}",
+                e
+            ))
+        })?,
+    )
+    .map_err(|e| fastn_core::Error::GenericError(format!("failed to sign the token: {}", e)))?;
+
+    let response: TokenResponse = reqwest::Client::new()
+        .post(key.token_uri.as_str())
+        .form(&[
+            ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+            ("assertion", assertion.as_str()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    *token = Some((
+        response.access_token.clone(),
+        std::time::Instant::now()
+            + std::time::Duration::from_secs(response.expires_in.saturating_sub(60)),
+    ));
+    Ok(Some(response.access_token))
+}
+
+#[cfg(test)]
+mod test {
+    #[tokio::test]
+    async fn fetch_fixtures() {
+        let ds = fastn_ds::DocumentStore::with_backend(
+            "/p",
+            std::sync::Arc::new(fastn_ds::backend::Memory::new()),
+        );
+        let url = super::prepare_query_url(
+            super::generate_google_sheet_url("sheet-0").as_str(),
+            "SELECT *",
+            &None,
+        );
+        let fixtures = fastn_ds::Path::new("/p/fixtures");
+
+        let error = super::fetch_with_fixtures(&ds, url.as_str(), Some(&fixtures), true)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("run once online to record it"));
+
+        let fixture = fixtures.join(format!("{}.json", fastn_core::utils::generate_hash(&url)));
+        ds.write_content(&fixture, br#"{"table": {"cols": [], "rows": []}}"#.to_vec())
+            .await
+            .unwrap();
+        assert_eq!(
+            super::fetch_with_fixtures(&ds, url.as_str(), Some(&fixtures), true)
+                .await
+                .unwrap(),
+            r#"{"table": {"cols": [], "rows": []}}"#
+        );
+    }
+}
@@ -243,44 +243,17 @@ impl Request {
     }
 
     pub async fn ud(&self, ds: &fastn_ds::DocumentStore) -> Option<fastn_core::UserData> {
-        let session_data = match self.cookie(fastn_core::auth::SESSION_COOKIE_NAME) {
-            Some(c) => {
-                if c.is_empty() {
-                    return None;
-                } else {
-                    c
-                }
-            }
-            None => return None,
-        };
-
-        let session_data = match fastn_core::auth::utils::decrypt(ds, &session_data).await {
-            Ok(v) => v,
-            Err(e) => {
-                tracing::warn!("failed to decrypt session data: {:?}", e);
-                return None;
-            }
-        };
-
-        #[derive(serde::Deserialize)]
-        struct SessionData {
-            session_id: i64,
-            user: fastn_core::UserData,
-        }
-
-        let session_data = match serde_json::from_str::<SessionData>(session_data.as_str()) {
-            Ok(sd) => sd,
-            Err(e) => {
-                tracing::warn!("failed to deserialize session data: {:?}", e);
-                return None;
-            }
-        };
-
-        // if the session does not exist, return None
-        match fastn_core::auth::get_authenticated_user_with_email(&session_data.session_id, ds)
-            .await
-        {
-            Ok(_) => Some(session_data.user),
+        let session_id = fastn_core::auth::session::session_id(self, ds).await?;
+
+        // if the session does not exist, has expired or was revoked, return None
+        match fastn_core::auth::get_authenticated_user_with_email(&session_id, ds).await {
+            Ok(user) => Some(fastn_core::UserData {
+                id: user.id,
+                username: user.username,
+                name: user.name,
+                email: user.email.0,
+                verified_email: user.verified_email,
+            }),
             Err(e) => {
                 tracing::warn!("failed to get user data from session: {e}");
                 None
@@ -532,6 +505,115 @@ pub(crate) async fn http_post_with_cookie(
     Ok((Ok(res.bytes().await?.into()), resp_cookies))
 }
 
+/// The response of `http_request_with_cookie()`, whatever its status.
+pub(crate) struct ProcessorResponse {
+    pub status: reqwest::StatusCode,
+    /// Lowercase header names, values of repeated headers are joined with `, `
+    pub headers: std::collections::BTreeMap<String, String>,
+    pub body: Vec<u8>,
+    pub cookies: Vec<String>,
+}
+
+/// Sends a `method` request to `url`, eg for the `http` processor. Requests that fail to connect
+/// or time out, and responses with a `429` or `5xx` status, are tried again up to `retries`
+/// times, waiting longer after each attempt. Requests that could not be built, eg because of an
+/// invalid url, are not. The last response is returned, the caller decides
+/// what to do with its status.
+pub(crate) async fn http_request_with_cookie(
+    method: reqwest::Method,
+    url: &str,
+    cookie: Option<String>,
+    headers: &std::collections::HashMap<String, String>,
+    body: Option<String>,
+    timeout: Option<std::time::Duration>,
+    retries: usize,
+) -> fastn_core::Result<ProcessorResponse> {
+    tracing::info!(url = url, method = method.as_str());
+    let mut req_headers = reqwest::header::HeaderMap::new();
+    req_headers.insert(
+        reqwest::header::USER_AGENT,
+        reqwest::header::HeaderValue::from_static("fastn"),
+    );
+    if let Some(cookie) = cookie {
+        req_headers.insert(
+            reqwest::header::COOKIE,
+            reqwest::header::HeaderValue::from_str(cookie.as_str()).map_err(|e| {
+                fastn_core::Error::GenericError(format!("invalid cookie header: {}", e))
+            })?,
+        );
+    }
+
+    for (key, value) in headers.iter() {
+        let name = reqwest::header::HeaderName::from_bytes(key.as_bytes()).map_err(|e| {
+            fastn_core::Error::GenericError(format!("invalid header name `{}`: {}", key, e))
+        })?;
+        let value = reqwest::header::HeaderValue::from_str(value.as_str()).map_err(|e| {
+            fastn_core::Error::GenericError(format!("invalid value of header `{}`: {}", key, e))
+        })?;
+        req_headers.insert(name, value);
+    }
+
+    let mut c = reqwest::Client::builder().default_headers(req_headers);
+    if let Some(timeout) = timeout {
+        c = c.timeout(timeout);
+    }
+    let c = c.build()?;
+
+    let mut attempt = 0;
+    let res = loop {
+        let mut req = c.request(method.clone(), url);
+        if let Some(body) = body.as_ref() {
+            req = req.body(body.to_string());
+        }
+        let retry = attempt < retries;
+        match req.send().await {
+            Ok(res)
+                if retry
+                    && (res.status().is_server_error()
+                        || res.status() == reqwest::StatusCode::TOO_MANY_REQUESTS) =>
+            {
+                tracing::warn!(url = url, status = res.status().as_u16(), attempt = attempt);
+            }
+            Ok(res) => break res,
+            Err(e) if retry && (e.is_timeout() || e.is_connect()) => {
+                tracing::warn!(url = url, error = %e, attempt = attempt);
+            }
+            Err(e) => return Err(e.into()),
+        }
+        attempt += 1;
+        tokio::time::sleep(std::time::Duration::from_millis(
+            250 * (1 << attempt.min(6)),
+        ))
+        .await;
+    };
+
+    let mut cookies = vec![];
+    let mut resp_headers: std::collections::BTreeMap<String, String> = Default::default();
+    for (k, v) in res.headers().iter() {
+        let v = match v.to_str() {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        if k.as_str().eq("set-cookie") {
+            cookies.push(v.to_string());
+        }
+        resp_headers
+            .entry(k.as_str().to_string())
+            .and_modify(|existing| {
+                existing.push_str(", ");
+                existing.push_str(v);
+            })
+            .or_insert_with(|| v.to_string());
+    }
+
+    Ok(ProcessorResponse {
+        status: res.status(),
+        headers: resp_headers,
+        body: res.bytes().await?.into(),
+        cookies,
+    })
+}
+
 pub async fn http_get(url: &str) -> fastn_core::Result<Vec<u8>> {
     tracing::debug!("http_get {}", &url);
 
@@ -909,6 +991,116 @@ mod test {
     use actix_web::body::MessageBody;
     use pretty_assertions::assert_eq;
 
+    /// Echoes the method, headers and body of the requests to `/echo` as json. `/flaky` responds
+    /// with a `503` the first time it is called.
+    fn mock_server() -> String {
+        async fn echo(req: actix_web::HttpRequest, body: String) -> actix_web::HttpResponse {
+            let header = |name: &str| {
+                req.headers()
+                    .get(name)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string)
+            };
+            actix_web::HttpResponse::Ok()
+                .insert_header(("set-cookie", "session=s-0"))
+                .json(serde_json::json!({
+                    "method": req.method().as_str(),
+                    "x-api-key": header("x-api-key"),
+                    "cookie": header("cookie"),
+                    "body": body,
+                }))
+        }
+
+        async fn flaky(
+            calls: actix_web::web::Data<std::sync::atomic::AtomicUsize>,
+        ) -> actix_web::HttpResponse {
+            if calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0 {
+                actix_web::HttpResponse::ServiceUnavailable().finish()
+            } else {
+                actix_web::HttpResponse::Ok().body("ok")
+            }
+        }
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let calls = actix_web::web::Data::new(std::sync::atomic::AtomicUsize::new(0));
+        let server = actix_web::HttpServer::new(move || {
+            actix_web::App::new()
+                .app_data(calls.clone())
+                .route("/echo", actix_web::web::route().to(echo))
+                .route("/flaky", actix_web::web::get().to(flaky))
+        })
+        .workers(1)
+        .disable_signals()
+        .listen(listener)
+        .unwrap()
+        .run();
+        actix_web::rt::spawn(server);
+
+        url
+    }
+
+    #[actix_web::test]
+    async fn http_request_with_cookie() {
+        let url = mock_server();
+
+        let response = fastn_core::http::http_request_with_cookie(
+            reqwest::Method::PUT,
+            format!("{url}/echo").as_str(),
+            Some("token=t-0".to_string()),
+            &std::collections::HashMap::from([("x-api-key".to_string(), "k-0".to_string())]),
+            Some(r#"{"name": "Alice"}"#.to_string()),
+            None,
+            0,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status, reqwest::StatusCode::OK);
+        assert_eq!(
+            response.headers.get("content-type").map(String::as_str),
+            Some("application/json")
+        );
+        assert_eq!(response.cookies, vec!["session=s-0".to_string()]);
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&response.body).unwrap(),
+            serde_json::json!({
+                "method": "PUT",
+                "x-api-key": "k-0",
+                "cookie": "token=t-0",
+                "body": r#"{"name": "Alice"}"#,
+            })
+        );
+    }
+
+    #[actix_web::test]
+    async fn http_request_with_cookie_retries() {
+        async fn get(url: &str, retries: usize) -> reqwest::StatusCode {
+            fastn_core::http::http_request_with_cookie(
+                reqwest::Method::GET,
+                format!("{url}/flaky").as_str(),
+                None,
+                &Default::default(),
+                None,
+                None,
+                retries,
+            )
+            .await
+            .unwrap()
+            .status
+        }
+
+        // the `503` is returned as is, the caller decides what to do with it
+        assert_eq!(
+            get(mock_server().as_str(), 0).await,
+            reqwest::StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            get(mock_server().as_str(), 1).await,
+            reqwest::StatusCode::OK
+        );
+    }
+
     #[tokio::test]
     async fn user_err() -> fastn_core::Result<()> {
         let user_err = vec!["invalid email".into()];
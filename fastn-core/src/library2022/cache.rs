@@ -0,0 +1,332 @@
+/// Results of processor sections with a `cache` header, eg:
+///
+/// ```ftd
+/// -- person list people:
+/// $processor$: pr.pg
+/// cache: 5m
+/// stale-while-revalidate: 1h
+///
+/// SELECT * FROM person;
+/// ```
+///
+/// A result is reused for `cache`. Once it is older than that, for `stale-while-revalidate`, the
+/// stale result is still returned, and the first request that finds it runs the processor again
+/// in the background, the new result replaces it if the processor does not fail. Outside of
+/// `fastn serve`, eg in `fastn build`, there is no background, the processor runs again first,
+/// and the stale result is only returned if it fails. Results are kept in memory, keyed on the
+/// processor, the kind of the variable, and its headers and body with the variables they refer
+/// to resolved. `/-/clear-cache/?processor=all` removes them, see `fastn_core::apis::cache`.
+///
+/// The results of `http` and `graphql`, which send the cookies of the visitor, are also keyed on
+/// the cookies. Processors whose results are different for each visitor, or that write, can
+/// not be cached, see `UNCACHEABLE`.
+static CACHE: once_cell::sync::Lazy<std::sync::Mutex<std::collections::HashMap<String, Entry>>> =
+    once_cell::sync::Lazy::new(Default::default);
+
+pub(crate) const CACHE_HEADER: &str = "cache";
+pub(crate) const STALE_WHILE_REVALIDATE_HEADER: &str = "stale-while-revalidate";
+
+/// A cached result of these would be returned to other visitors, or skip a write.
+const UNCACHEABLE: &[&str] = &[
+    "request-data",
+    "user-details",
+    "user-sessions",
+    "get-identities",
+    "is-reader",
+    "sql-execute",
+];
+
+/// These send the cookies of the visitor with their request.
+const WITH_VISITOR_COOKIES: &[&str] = &["http", "graphql"];
+
+struct Entry {
+    processor: String,
+    value: ftd::interpreter::Value,
+    fresh_until: std::time::Instant,
+    stale_until: std::time::Instant,
+    revalidating: bool,
+}
+
+#[derive(Debug)]
+pub(crate) struct Policy {
+    max_age: std::time::Duration,
+    stale_while_revalidate: std::time::Duration,
+}
+
+pub(crate) enum Lookup {
+    Hit(ftd::interpreter::Value),
+    /// The result is stale, it is returned, and this request runs the processor again in the
+    /// background
+    Revalidate(ftd::interpreter::Value),
+    Miss,
+}
+
+impl Policy {
+    /// The policy of the `cache` and `stale-while-revalidate` headers of `value`, if it has them, and
+    /// `value` without them, so processors do not see them, eg `http` would send them as query
+    /// parameters.
+    pub(crate) fn from_value(
+        processor: &str,
+        value: ftd::ast::VariableValue,
+        doc_name: &str,
+    ) -> ftd::interpreter::Result<(Option<Policy>, ftd::ast::VariableValue)> {
+        let (headers, line_number) = match value.get_record(doc_name) {
+            Ok(record) => (record.2, record.5),
+            Err(_) => return Ok((None, value)),
+        };
+        let max_age =
+            match headers.get_optional_string_by_key(CACHE_HEADER, doc_name, line_number)? {
+                Some(max_age) => parse_duration(max_age.as_str(), doc_name, line_number)?,
+                None => return Ok((None, value)),
+            };
+        if UNCACHEABLE.contains(&processor) {
+            return ftd::interpreter::utils::e2(
+                format!(
+                    "`{CACHE_HEADER}` can not be used with `{processor}`, its result is different \
+                    for each visitor, or it writes"
+                ),
+                doc_name,
+                line_number,
+            );
+        }
+        let stale_while_revalidate = match headers.get_optional_string_by_key(
+            STALE_WHILE_REVALIDATE_HEADER,
+            doc_name,
+            line_number,
+        )? {
+            Some(stale) => parse_duration(stale.as_str(), doc_name, line_number)?,
+            None => Default::default(),
+        };
+
+        let value = match value {
+            ftd::ast::VariableValue::Record {
+                name,
+                caption,
+                headers,
+                body,
+                values,
+                line_number,
+                condition,
+            } => ftd::ast::VariableValue::Record {
+                name,
+                caption,
+                headers: ftd::ast::HeaderValues::new(
+                    headers
+                        .0
+                        .into_iter()
+                        .filter(|header| {
+                            header.key.ne(CACHE_HEADER)
+                                && header.key.ne(STALE_WHILE_REVALIDATE_HEADER)
+                        })
+                        .collect(),
+                ),
+                body,
+                values,
+                line_number,
+                condition,
+            },
+            value => value,
+        };
+
+        Ok((
+            Some(Policy {
+                max_age,
+                stale_while_revalidate,
+            }),
+            value,
+        ))
+    }
+}
+
+/// `30s`, `5m`, `2h`, `1d`, or a number of seconds.
+pub(crate) fn parse_duration(
+    duration: &str,
+    doc_name: &str,
+    line_number: usize,
+) -> ftd::interpreter::Result<std::time::Duration> {
+    let duration = duration.trim();
+    let (number, unit) = match duration.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => duration.split_at(idx),
+        None => (duration, "s"),
+    };
+    let seconds = match (number.parse::<u64>(), unit.trim()) {
+        (Ok(n), "s") => n,
+        (Ok(n), "m") => n * 60,
+        (Ok(n), "h") => n * 60 * 60,
+        (Ok(n), "d") => n * 60 * 60 * 24,
+        _ => {
+            return ftd::interpreter::utils::e2(
+                format!("invalid duration `{duration}`, expected eg `30s`, `5m`, `2h` or `1d`"),
+                doc_name,
+                line_number,
+            )
+        }
+    };
+    Ok(std::time::Duration::from_secs(seconds))
+}
+
+/// The key of the result of `processor` for `value`. `$variable`s in the headers and the body,
+/// eg query parameters, are resolved, so the key changes with them, and so do the cookies of
+/// the visitor, `cookies`, for processors that send them.
+pub(crate) fn key(
+    processor: &str,
+    value: &ftd::ast::VariableValue,
+    kind: &ftd::interpreter::Kind,
+    doc: &ftd::interpreter::TDoc,
+    cookies: Option<String>,
+) -> String {
+    let mut parts = vec![processor.to_string(), format!("{kind:?}")];
+    if WITH_VISITOR_COOKIES.contains(&processor) {
+        parts.push(format!("cookies: {}", cookies.unwrap_or_default()));
+    }
+    match value.get_record(doc.name) {
+        Ok((_, caption, headers, body, _, line_number)) => {
+            if let Some(caption) = caption.as_ref() {
+                parts.push(resolved(format!("{caption:?}").as_str(), doc, line_number));
+            }
+            for header in headers.0.iter() {
+                parts.push(format!(
+                    "{}: {}",
+                    header.key,
+                    resolved(
+                        format!("{:?}", header.value).as_str(),
+                        doc,
+                        header.line_number
+                    )
+                ));
+            }
+            if let Some(body) = body {
+                parts.push(resolved(body.value.as_str(), doc, body.line_number));
+            }
+        }
+        Err(_) => parts.push(resolved(
+            format!("{value:?}").as_str(),
+            doc,
+            value.line_number(),
+        )),
+    }
+    fastn_core::utils::generate_hash(parts.join("\n"))
+}
+
+/// `text` followed by the values of the `$variable`s it refers to, those that are not
+/// variables, eg `$1` in a query, are left out.
+fn resolved(text: &str, doc: &ftd::interpreter::TDoc, line_number: usize) -> String {
+    let mut resolved = text.to_string();
+    for (idx, _) in text.match_indices('$') {
+        let name: String = text[idx + 1..]
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || ['-', '_', '.', '#'].contains(c))
+            .collect();
+        if name.is_empty() {
+            continue;
+        }
+        let value = match doc.get_value(line_number, format!("${name}").as_str()) {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+        if let Ok(Some(value)) = value.to_json_string(doc, false) {
+            resolved.push_str(format!("\n${name}={value}").as_str());
+        }
+    }
+    resolved
+}
+
+pub(crate) fn get(key: &str) -> Lookup {
+    let mut cache = CACHE.lock().unwrap();
+    let entry = match cache.get_mut(key) {
+        Some(entry) => entry,
+        None => return Lookup::Miss,
+    };
+    let now = std::time::Instant::now();
+    if now < entry.fresh_until {
+        return Lookup::Hit(entry.value.clone());
+    }
+    if now >= entry.stale_until {
+        cache.remove(key);
+        return Lookup::Miss;
+    }
+    if entry.revalidating {
+        return Lookup::Hit(entry.value.clone());
+    }
+    entry.revalidating = true;
+    Lookup::Revalidate(entry.value.clone())
+}
+
+pub(crate) fn insert(
+    key: String,
+    processor: &str,
+    value: ftd::interpreter::Value,
+    policy: &Policy,
+) {
+    let now = std::time::Instant::now();
+    let mut cache = CACHE.lock().unwrap();
+    cache.retain(|_, entry| now < entry.stale_until);
+    cache.insert(
+        key,
+        Entry {
+            processor: processor.to_string(),
+            value,
+            fresh_until: now + policy.max_age,
+            stale_until: now + policy.max_age + policy.stale_while_revalidate,
+            revalidating: false,
+        },
+    );
+}
+
+/// The processor failed to run again, the next request that finds the result stale tries again.
+pub(crate) fn revalidation_failed(key: &str) {
+    if let Some(entry) = CACHE.lock().unwrap().get_mut(key) {
+        entry.revalidating = false;
+    }
+}
+
+/// Removes the results of `processor`, or of all processors if it is `all`.
+pub(crate) fn clear(processor: &str) {
+    CACHE
+        .lock()
+        .unwrap()
+        .retain(|_, entry| processor.ne("all") && entry.processor.ne(processor));
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn parse_duration() {
+        let d = |s: &str| super::parse_duration(s, "foo", 1).map(|d| d.as_secs()).ok();
+        assert_eq!(d("30"), Some(30));
+        assert_eq!(d("30s"), Some(30));
+        assert_eq!(d("5m"), Some(300));
+        assert_eq!(d("2h"), Some(7200));
+        assert_eq!(d("1d"), Some(86400));
+        assert_eq!(d("5 m"), Some(300));
+        assert_eq!(d("m"), None);
+        assert_eq!(d("5w"), None);
+    }
+
+    #[test]
+    fn uncacheable() {
+        let value = |processor: &str| {
+            let section = ftd::p1::parse(
+                format!(
+                    "-- string list x:\n$processor$: {processor}\ncache: 5m\nstale-while-revalidate: 1h\n"
+                )
+                .as_str(),
+                "foo",
+            )
+            .unwrap()
+            .remove(0);
+            let ast = ftd::ast::AST::from_section(&section, "foo").unwrap();
+            ast.get_variable_definition("foo").unwrap().value
+        };
+
+        let (policy, value) = super::Policy::from_value("pg", value("pg"), "foo").unwrap();
+        let policy = policy.unwrap();
+        assert_eq!(policy.max_age.as_secs(), 300);
+        assert_eq!(policy.stale_while_revalidate.as_secs(), 3600);
+        // the processor does not see the headers of the cache
+        assert!(value.get_record("foo").unwrap().2 .0.is_empty());
+
+        assert!(super::Policy::from_value("user-details", value("user-details"), "foo").is_err());
+        assert!(super::Policy::from_value("sql-execute", value("sql-execute"), "foo").is_err());
+    }
+}
@@ -1,3 +1,4 @@
+pub(crate) mod cache;
 pub(crate) mod processor;
 pub(crate) mod utils;
 
@@ -13,6 +14,37 @@ impl KeyValueData {
     }
 }
 
+/// A processor run while rendering a document, see `RequestConfig::processors_during_render`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ProcessorRun {
+    pub processor: String,
+    pub module: String,
+    pub line_number: usize,
+}
+
+impl ProcessorRun {
+    /// Whether the processor returns the same value as long as the files of the packages do not
+    /// change. Others read a database, the network, the request etc.
+    pub fn is_stable(&self) -> bool {
+        matches!(
+            self.processor.as_str(),
+            "figma-typo-token"
+                | "figma-cs-token"
+                | "figma-cs-token-old"
+                | "toc"
+                | "sitemap"
+                | "full-sitemap"
+                | "document-id"
+                | "document-full-id"
+                | "document-suffix"
+                | "document-name"
+                | "fastn-apps"
+                | "current-language"
+                | "translation-info"
+        )
+    }
+}
+
 pub type Library2022 = fastn_core::RequestConfig;
 
 impl Library2022 {
@@ -168,6 +200,13 @@ impl Library2022 {
             if !file_path.ends_with(".ftd") {
                 return Ok(None);
             }
+            lib.files_during_render.insert(
+                lib.config
+                    .get_root_for_package(package)
+                    .join(file_path.as_str())
+                    .to_string(),
+                fastn_core::utils::generate_hash(&data),
+            );
             Ok(String::from_utf8(data).ok().map(|body| {
                 let body_with_prefix =
                     package.get_prefixed_body(body.as_str(), name.as_str(), true);
@@ -211,6 +250,100 @@ impl Library2022 {
         );
         let line_number = ast.line_number();
         let (_processor, variable_name, value, kind) = get_processor_data(ast, doc)?;
+        let (policy, value) = cache::Policy::from_value(processor.as_str(), value, doc.name)?;
+        let policy = match policy {
+            Some(policy) => policy,
+            None => {
+                return self
+                    .run_processor(processor, variable_name, value, kind, doc, line_number)
+                    .await
+            }
+        };
+
+        let key = cache::key(
+            processor.as_str(),
+            &value,
+            &kind,
+            doc,
+            self.request.cookies_string(),
+        );
+        let stale = match cache::get(key.as_str()) {
+            cache::Lookup::Hit(value) => return Ok(value),
+            cache::Lookup::Revalidate(stale) if actix_web::rt::System::try_current().is_some() => {
+                let mut req_config = self.clone();
+                let name = doc.name.to_string();
+                let aliases = doc.aliases.clone();
+                let bag = match &doc.bag {
+                    ftd::interpreter::BagOrState::Bag(bag) => (*bag).clone(),
+                    ftd::interpreter::BagOrState::State(state) => state.bag.clone(),
+                };
+                actix_web::rt::spawn(async move {
+                    let mut doc = ftd::interpreter::TDoc::new(&name, &aliases, &bag);
+                    let result = req_config
+                        .run_processor(
+                            processor.clone(),
+                            variable_name,
+                            value,
+                            kind,
+                            &mut doc,
+                            line_number,
+                        )
+                        .await;
+                    match result {
+                        Ok(value) => cache::insert(key, processor.as_str(), value, &policy),
+                        Err(e) => {
+                            fastn_core::warning!(
+                                "processor `{}` failed, keeping its stale result: {}",
+                                processor,
+                                e
+                            );
+                            cache::revalidation_failed(key.as_str());
+                        }
+                    }
+                });
+                return Ok(stale);
+            }
+            // not in the server, there is nothing to run the processor in the background
+            cache::Lookup::Revalidate(stale) => Some(stale),
+            cache::Lookup::Miss => None,
+        };
+        let result = self
+            .run_processor(
+                processor.clone(),
+                variable_name,
+                value,
+                kind,
+                doc,
+                line_number,
+            )
+            .await;
+        match (result, stale) {
+            (Ok(value), _) => {
+                cache::insert(key, processor.as_str(), value.clone(), &policy);
+                Ok(value)
+            }
+            (Err(e), Some(stale)) => {
+                fastn_core::warning!(
+                    "processor `{}` failed, using its stale result: {}",
+                    processor,
+                    e
+                );
+                cache::revalidation_failed(key.as_str());
+                Ok(stale)
+            }
+            (Err(e), None) => Err(e),
+        }
+    }
+
+    async fn run_processor<'a>(
+        &'a mut self,
+        processor: String,
+        variable_name: String,
+        value: ftd::ast::VariableValue,
+        kind: ftd::interpreter::Kind,
+        doc: &'a mut ftd::interpreter::TDoc<'a>,
+        line_number: usize,
+    ) -> ftd::interpreter::Result<ftd::interpreter::Value> {
         match processor.as_str() {
             "figma-typo-token" => {
                 processor::figma_typography_tokens::process_typography_tokens(value, kind, doc)
@@ -220,10 +353,11 @@ impl Library2022 {
                 processor::figma_tokens::process_figma_tokens_old(value, kind, doc)
             }
             "http" => processor::http::process(value, kind, doc, self).await,
+            "graphql" => processor::graphql::process(value, kind, doc, self).await,
             "translation-info" => processor::lang_details::process(value, kind, doc, self).await,
             "current-language" => processor::lang::process(value, kind, doc, self).await,
             "toc" => processor::toc::process(value, kind, doc),
-            "get-data" => processor::get_data::process(value, kind, doc, self),
+            "get-data" => processor::get_data::process(value, kind, doc, self).await,
             "sitemap" => processor::sitemap::process(value, kind, doc, self),
             "full-sitemap" => processor::sitemap::full_sitemap_process(value, kind, doc, self),
             "request-data" => {
@@ -253,9 +387,11 @@ impl Library2022 {
             "document-name" => processor::document::document_name(value, kind, doc, self).await,
             "fetch-file" => processor::fetch_file::fetch_files(value, kind, doc, self).await,
             "user-details" => processor::user_details::process(value, kind, doc, self).await,
+            "user-sessions" => processor::user_sessions::process(value, kind, doc, self).await,
             "fastn-apps" => processor::apps::process(value, kind, doc, self),
             "is-reader" => processor::user_group::is_reader(value, kind, doc, self).await,
             "sql" => processor::sql::process(value, kind, doc, self).await,
+            "sql-execute" => processor::sql::execute(value, kind, doc, self).await,
             "package-query" => processor::package_query::process(value, kind, doc, self).await,
             "pg" => processor::pg::process(value, kind, doc, self).await,
             "query" => processor::query::process(value, kind, doc, self).await,
@@ -333,3 +469,31 @@ fn get_processor_data(
         ))
     }
 }
+
+#[cfg(test)]
+mod test {
+    fn run(processor: &str) -> super::ProcessorRun {
+        super::ProcessorRun {
+            processor: processor.to_string(),
+            module: "foo.com/index".to_string(),
+            line_number: 1,
+        }
+    }
+
+    #[test]
+    fn is_stable() {
+        for processor in ["toc", "sitemap", "document-id", "translation-info"] {
+            assert!(run(processor).is_stable(), "{processor}");
+        }
+        for processor in [
+            "sql",
+            "pg",
+            "http",
+            "request-data",
+            "user-details",
+            "unknown",
+        ] {
+            assert!(!run(processor).is_stable(), "{processor}");
+        }
+    }
+}
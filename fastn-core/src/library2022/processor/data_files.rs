@@ -0,0 +1,363 @@
+/// In-memory sqlite databases with the data files of the package, eg `db: data/*.csv` of the
+/// `sql` processor, keyed on the pattern. A database is loaded again when any of its files
+/// change.
+static DATABASES: once_cell::sync::Lazy<
+    std::sync::Mutex<std::collections::HashMap<String, (String, DataFilesDatabase)>>,
+> = once_cell::sync::Lazy::new(Default::default);
+
+type DataFilesDatabase = std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>;
+
+const EXTENSIONS: [&str; 4] = ["csv", "json", "jsonl", "ndjson"];
+
+/// Whether the `db` header of the `sql` processor names data files, eg `data/*.csv`, rather
+/// than a sqlite database.
+pub(crate) fn is_data_files(db: &str) -> bool {
+    camino::Utf8Path::new(db)
+        .extension()
+        .map(|extension| EXTENSIONS.contains(&extension))
+        .unwrap_or_default()
+}
+
+/// Runs `query` on the files of the package that match `pattern`, eg `data/*.csv`. Each file is
+/// a table named after it, `data/blog-posts.csv` is `blog_posts`, with a column per csv column
+/// or json key, so queries can join and aggregate them. The files are read only.
+pub async fn process(
+    value: ftd::ast::VariableValue,
+    kind: ftd::interpreter::Kind,
+    doc: &ftd::interpreter::TDoc<'_>,
+    req_config: &mut fastn_core::RequestConfig,
+    pattern: &str,
+    headers: ftd::ast::HeaderValues,
+    query: &str,
+) -> ftd::interpreter::Result<ftd::interpreter::Value> {
+    let line_number = value.line_number();
+    let database =
+        load(pattern, req_config)
+            .await
+            .map_err(|message| ftd::interpreter::Error::ParseError {
+                message,
+                doc_id: doc.name.to_string(),
+                line_number,
+            })?;
+
+    let query_response = {
+        let conn = database.lock().unwrap();
+        super::sqlite::query_connection(&conn, query, doc, headers, line_number)
+    };
+
+    match query_response {
+        Ok(result) => {
+            super::sqlite::result_to_value(Ok(result), kind, doc, &value, super::sql::STATUS_OK)
+        }
+        Err(e) => super::sqlite::result_to_value(
+            Err(e.to_string()),
+            kind,
+            doc,
+            &value,
+            super::sql::STATUS_ERROR,
+        ),
+    }
+}
+
+async fn load(
+    pattern: &str,
+    req_config: &mut fastn_core::RequestConfig,
+) -> Result<DataFilesDatabase, String> {
+    let ds = &req_config.config.ds;
+    let root = ds.root();
+    let mut overrides = ignore::overrides::OverrideBuilder::new(root.to_string());
+    overrides
+        .add(pattern)
+        .map_err(|e| format!("invalid pattern `{}`: {}", pattern, e))?;
+    let overrides = overrides
+        .build()
+        .map_err(|e| format!("invalid pattern `{}`: {}", pattern, e))?;
+
+    // Only the directory the pattern is about is walked, not the whole package, the ignored
+    // paths of the package are relative to its root
+    let ignored = fastn_ds::backend::package_ignores(
+        &req_config.config.ignored_paths(&req_config.config.package),
+        &root.path,
+    )
+    .map_err(|e| format!("invalid ignored paths: {}", e))?;
+
+    let mut files = vec![];
+    for path in ds
+        .get_all_file_path(
+            &match walk_root(pattern).as_str() {
+                "" => root.clone(),
+                walk_root => root.join(walk_root),
+            },
+            &[],
+        )
+        .await
+    {
+        let relative = match path.strip_prefix(&root) {
+            Some(relative) => relative.to_string(),
+            None => continue,
+        };
+        if !is_data_files(relative.as_str())
+            || !overrides.matched(relative.as_str(), false).is_whitelist()
+            || fastn_ds::backend::is_ignored(&ignored, &root.path, &path.path)
+        {
+            continue;
+        }
+        let content = ds
+            .read_content(&path)
+            .await
+            .map_err(|e| format!("failed to read {}: {}", relative, e))?;
+        let checksum = fastn_core::utils::generate_hash(&content);
+        files.push((path, relative, content, checksum));
+    }
+    if files.is_empty() {
+        return Err(format!("no files match `{}`", pattern));
+    }
+    // the document has to be rebuilt when the files change
+    for (path, _, _, checksum) in files.iter() {
+        req_config
+            .files_during_render
+            .insert(path.to_string(), checksum.to_string());
+    }
+    let mut files = files
+        .into_iter()
+        .map(|(_, relative, content, checksum)| (relative, content, checksum))
+        .collect::<Vec<_>>();
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let checksum = fastn_core::utils::generate_hash(
+        files
+            .iter()
+            .map(|(path, _, checksum)| format!("{path}:{checksum}"))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    );
+    if let Some((loaded, database)) = DATABASES.lock().unwrap().get(pattern) {
+        if loaded.eq(&checksum) {
+            return Ok(database.clone());
+        }
+    }
+
+    let mut tables: std::collections::HashMap<String, String> = Default::default();
+    for (path, _, _) in files.iter() {
+        if let Some(other) = tables.insert(table_name(path.as_str()), path.to_string()) {
+            return Err(format!(
+                "{} and {} are both the table `{}`, rename one of them",
+                other,
+                path,
+                table_name(path.as_str())
+            ));
+        }
+    }
+
+    let conn = rusqlite::Connection::open_in_memory()
+        .map_err(|e| format!("failed to create the database: {}", e))?;
+    for (path, content, _) in files {
+        let rows = match super::get_data::parse_file(path.as_str(), content)? {
+            serde_json::Value::Array(rows) => rows,
+            _ => return Err(format!("{} is not a list", path)),
+        };
+        create_table(&conn, table_name(path.as_str()).as_str(), rows)
+            .map_err(|e| format!("failed to load {}: {}", path, e))?;
+    }
+    // The database is shared by all requests, a query writing to it would change what other
+    // visitors see till the files change
+    conn.execute_batch("PRAGMA query_only = ON")
+        .map_err(|e| format!("failed to create the database: {}", e))?;
+
+    let database = std::sync::Arc::new(std::sync::Mutex::new(conn));
+    DATABASES
+        .lock()
+        .unwrap()
+        .insert(pattern.to_string(), (checksum, database.clone()));
+    Ok(database)
+}
+
+/// The directory that has all the files `pattern` matches, relative to the package root: `data`
+/// for `data/*.csv`, the package root for `*.csv`.
+fn walk_root(pattern: &str) -> String {
+    if pattern.starts_with('!') {
+        return String::new();
+    }
+    let mut components = pattern
+        .trim_start_matches('/')
+        .split('/')
+        .collect::<Vec<_>>();
+    // the last component is the file name
+    components.pop();
+    components
+        .into_iter()
+        .take_while(|component| !component.contains(['*', '?', '[', '{']))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// `data/blog-posts.csv` is `blog_posts`.
+fn table_name(path: &str) -> String {
+    column_name(camino::Utf8Path::new(path).file_stem().unwrap_or(path))
+}
+
+/// csv columns are read as `full-name`, which is `full_name` in sql, see
+/// `get_data::parse_file()`.
+fn column_name(name: &str) -> String {
+    name.replace(['-', ' '], "_")
+}
+
+/// Columns whose values are all integers are `INTEGER`, all numbers `REAL`, else `TEXT`.
+/// Nested objects and lists are stored as json text.
+fn create_table(
+    conn: &rusqlite::Connection,
+    table: &str,
+    rows: Vec<serde_json::Value>,
+) -> Result<(), String> {
+    let rows = rows
+        .into_iter()
+        .map(|row| match row {
+            serde_json::Value::Object(row) => Ok(row),
+            row => Err(format!("expected an object, found: {}", row)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut columns: Vec<(String, &'static str)> = vec![];
+    for row in rows.iter() {
+        for (key, value) in row.iter() {
+            let type_ = match value {
+                serde_json::Value::Null => continue,
+                v if as_integer(v).is_some() => "INTEGER",
+                v if as_real(v).is_some() => "REAL",
+                _ => "TEXT",
+            };
+            match columns.iter_mut().find(|(name, _)| name.eq(key)) {
+                Some((_, existing)) => {
+                    *existing = match (*existing, type_) {
+                        (a, b) if a == b => a,
+                        ("INTEGER", "REAL") | ("REAL", "INTEGER") => "REAL",
+                        _ => "TEXT",
+                    }
+                }
+                None => columns.push((key.to_string(), type_)),
+            }
+        }
+    }
+    if columns.is_empty() {
+        return Err("the file has no columns".to_string());
+    }
+
+    let quoted = |name: &str| format!("\"{}\"", column_name(name).replace('"', "\"\""));
+    conn.execute(
+        format!(
+            "CREATE TABLE {} ({})",
+            quoted(table),
+            columns
+                .iter()
+                .map(|(name, type_)| format!("{} {}", quoted(name), type_))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+        .as_str(),
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let mut stmt = conn
+        .prepare(
+            format!(
+                "INSERT INTO {} VALUES ({})",
+                quoted(table),
+                vec!["?"; columns.len()].join(", ")
+            )
+            .as_str(),
+        )
+        .map_err(|e| e.to_string())?;
+    for row in rows.iter() {
+        let values = columns.iter().map(|(name, type_)| match row.get(name) {
+            None | Some(serde_json::Value::Null) => rusqlite::types::Value::Null,
+            Some(v) if *type_ == "INTEGER" => as_integer(v)
+                .map(rusqlite::types::Value::Integer)
+                .unwrap_or(rusqlite::types::Value::Null),
+            Some(v) if *type_ == "REAL" => as_real(v)
+                .map(rusqlite::types::Value::Real)
+                .unwrap_or(rusqlite::types::Value::Null),
+            Some(serde_json::Value::String(s)) => rusqlite::types::Value::Text(s.to_string()),
+            Some(v) => rusqlite::types::Value::Text(v.to_string()),
+        });
+        stmt.execute(rusqlite::params_from_iter(values))
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+/// Values of csv files are strings, `"42"` is an integer too.
+fn as_integer(value: &serde_json::Value) -> Option<i64> {
+    match value {
+        serde_json::Value::Number(n) => n.as_i64(),
+        serde_json::Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+fn as_real(value: &serde_json::Value) -> Option<f64> {
+    match value {
+        serde_json::Value::Number(n) => n.as_f64(),
+        serde_json::Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn walk_root() {
+        assert_eq!(super::walk_root("data/*.csv"), "data");
+        assert_eq!(super::walk_root("/data/2024/posts.json"), "data/2024");
+        assert_eq!(super::walk_root("data/**/*.csv"), "data");
+        assert_eq!(super::walk_root("data-*/*.csv"), "");
+        assert_eq!(super::walk_root("*.csv"), "");
+    }
+
+    #[test]
+    fn create_table() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        let rows = match super::super::get_data::parse_file(
+            "people.csv",
+            b"Full Name,age,score\nA,30,1.5\nB,,2\n".to_vec(),
+        ) {
+            Ok(serde_json::Value::Array(rows)) => rows,
+            v => panic!("{:?}", v),
+        };
+        super::create_table(&conn, super::table_name("data/people.csv").as_str(), rows).unwrap();
+
+        let mut stmt = conn
+            .prepare("SELECT full_name, age, typeof(age), typeof(score) FROM people")
+            .unwrap();
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<i64>>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                (
+                    "A".to_string(),
+                    Some(30),
+                    "integer".to_string(),
+                    "real".to_string()
+                ),
+                (
+                    "B".to_string(),
+                    None,
+                    "null".to_string(),
+                    "real".to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -1,8 +1,8 @@
-pub fn process(
+pub async fn process(
     value: ftd::ast::VariableValue,
     kind: ftd::interpreter::Kind,
-    doc: &ftd::interpreter::TDoc,
-    req_config: &fastn_core::RequestConfig,
+    doc: &ftd::interpreter::TDoc<'_>,
+    req_config: &mut fastn_core::RequestConfig,
 ) -> ftd::interpreter::Result<ftd::interpreter::Value> {
     let (section_name, headers, body, line_number) = match value.get_record(doc.name) {
         Ok(val) => (
@@ -66,40 +66,20 @@ pub fn process(
         };
     }
 
-    if let Ok(Some(path)) =
-        headers.get_optional_string_by_key("file", doc.name, value.line_number())
-    {
-        match camino::Utf8Path::new(path.as_str()).extension() {
-            Some(extension) => {
-                if !extension.eq("json") {
-                    return Err(ftd::interpreter::Error::ParseError {
-                        message: format!("only json file supported {}", path),
-                        doc_id: doc.name.to_string(),
-                        line_number,
-                    });
-                }
-            }
-            None => {
-                return Err(ftd::interpreter::Error::ParseError {
-                    message: format!("file does not have any extension {}", path),
-                    doc_id: doc.name.to_string(),
-                    line_number,
-                });
-            }
-        }
-
-        let file = std::fs::read_to_string(path.as_str()).map_err(|_e| {
-            ftd::interpreter::Error::ParseError {
-                message: format!("file path not found {}", path),
-                doc_id: doc.name.to_string(),
-                line_number,
-            }
+    if let Some(path) = headers.get_optional_string_by_key("file", doc.name, line_number)? {
+        let data = read_file(
+            path.as_str(),
+            &req_config.config.ds,
+            &mut req_config.files_during_render,
+        )
+        .await
+        .map_err(|message| ftd::interpreter::Error::ParseError {
+            message,
+            doc_id: doc.name.to_string(),
+            line_number,
         })?;
-        return doc.from_json(
-            &serde_json::from_str::<serde_json::Value>(&file)?,
-            &kind,
-            &value,
-        );
+        let data = filter_and_sort(data, &headers, doc, line_number)?;
+        return doc.from_json(&data, &kind, &value);
     }
 
     if let Some(b) = body {
@@ -135,3 +115,418 @@ pub fn process(
 
     doc.from_json(&serde_json::json!(caption), &kind, &value)
 }
+
+/// The content of the file at `path`, in the package, as json, see `parse_file()`. The file is
+/// recorded in `files_during_render`, the document has to be rebuilt when it changes.
+async fn read_file(
+    path: &str,
+    ds: &fastn_ds::DocumentStore,
+    files_during_render: &mut std::collections::BTreeMap<String, String>,
+) -> Result<serde_json::Value, String> {
+    let full_path = ds.root().join(path);
+    let content = ds
+        .read_content(&full_path)
+        .await
+        .map_err(|e| format!("failed to read {}: {}", path, e))?;
+    files_during_render.insert(
+        full_path.to_string(),
+        fastn_core::utils::generate_hash(&content),
+    );
+
+    parse_file(path, content)
+}
+
+/// `content` of the file at `path` as json: `.json` files as is, `.jsonl` and `.csv` files as a
+/// list with an object per line, and `.yaml` files converted to json. The header of a csv file
+/// names the fields, `Full Name` or `full_name` for the field `full-name`, and empty cells are
+/// `NULL`.
+pub(crate) fn parse_file(path: &str, content: Vec<u8>) -> Result<serde_json::Value, String> {
+    let content = String::from_utf8(content).map_err(|_| format!("{} is not utf-8", path))?;
+
+    match camino::Utf8Path::new(path).extension() {
+        Some("json") => {
+            serde_json::from_str(content.as_str()).map_err(|e| format!("{}: {}", path, e))
+        }
+        Some("jsonl" | "ndjson") => content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(idx, line)| {
+                serde_json::from_str(line).map_err(|e| format!("{}:{}: {}", path, idx + 1, e))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(serde_json::Value::Array),
+        Some("yaml" | "yml") => {
+            serde_yaml_ng::from_str(content.as_str()).map_err(|e| format!("{}: {}", path, e))
+        }
+        Some("csv") => {
+            let mut reader = csv::Reader::from_reader(content.as_bytes());
+            let fields = reader
+                .headers()
+                .map_err(|e| format!("{}: {}", path, e))?
+                .iter()
+                .map(|column| column.trim().replace(['_', ' '], "-").to_lowercase())
+                .collect::<Vec<_>>();
+            let mut rows = vec![];
+            for record in reader.records() {
+                let record = record.map_err(|e| format!("{}: {}", path, e))?;
+                rows.push(serde_json::Value::Object(
+                    fields
+                        .iter()
+                        .zip(record.iter())
+                        .map(|(field, cell)| {
+                            let cell = match cell.trim() {
+                                "" => serde_json::Value::Null,
+                                cell => serde_json::Value::String(cell.to_string()),
+                            };
+                            (field.to_string(), cell)
+                        })
+                        .collect(),
+                ));
+            }
+            Ok(serde_json::Value::Array(rows))
+        }
+        Some(extension) => Err(format!(
+            "`{}` files are not supported, only json, jsonl, csv and yaml are: {}",
+            extension, path
+        )),
+        None => Err(format!("file does not have any extension {}", path)),
+    }
+}
+
+/// Applies the headers that select the items of a list read from a file, see `Selection`.
+fn filter_and_sort(
+    data: serde_json::Value,
+    headers: &ftd::ast::HeaderValues,
+    doc: &ftd::interpreter::TDoc<'_>,
+    line_number: usize,
+) -> ftd::interpreter::Result<serde_json::Value> {
+    let mut selection = Selection::default();
+    for header in headers.0.iter() {
+        if let Some(field) = header.key.strip_prefix("column-") {
+            selection
+                .renames
+                .push((field.to_string(), header.value.string(doc.name)?));
+        } else if let Some(field) = header.key.strip_prefix("where-") {
+            let value = header.value.string(doc.name)?;
+            let value = match value.starts_with('$') {
+                true => doc
+                    .get_value(header.line_number, value.as_str())?
+                    .to_json_string(doc, false)?
+                    .unwrap_or_default(),
+                false => value,
+            };
+            selection.filters.push((field.to_string(), value));
+        }
+    }
+    selection.sort_by = headers.get_optional_string_by_key("sort-by", doc.name, line_number)?;
+    selection.descending =
+        match headers.get_optional_string_by_key("order", doc.name, line_number)? {
+            None => false,
+            Some(order) if order.eq("asc") => false,
+            Some(order) if order.eq("desc") => true,
+            Some(order) => {
+                return ftd::interpreter::utils::e2(
+                    format!("`order` is either `asc` or `desc`, found: {}", order),
+                    doc.name,
+                    line_number,
+                )
+            }
+        };
+    if let Some(limit) = headers.get_optional_string_by_key("limit", doc.name, line_number)? {
+        selection.limit =
+            Some(
+                limit
+                    .parse::<usize>()
+                    .map_err(|_| ftd::interpreter::Error::ParseError {
+                        message: format!("`limit` is a number, found: {}", limit),
+                        doc_id: doc.name.to_string(),
+                        line_number,
+                    })?,
+            );
+    }
+
+    selection
+        .apply(data)
+        .map_err(|message| ftd::interpreter::Error::ParseError {
+            message,
+            doc_id: doc.name.to_string(),
+            line_number,
+        })
+}
+
+/// The items of a list read from a file, as selected by the headers:
+///
+/// - `column-<field>: <name>`: the field `<field>` is read from `<name>`, eg `column-title:
+///   Post Title`
+/// - `where-<field>: <value>`: only the items whose `<field>` is `<value>`, which can be a
+///   `$variable`
+/// - `sort-by: <field>`, and `order: desc` to sort the other way, items without `<field>` come
+///   last either way
+/// - `limit: <n>`: at most `<n>` items
+#[derive(Debug, Default)]
+struct Selection {
+    renames: Vec<(String, String)>,
+    filters: Vec<(String, String)>,
+    sort_by: Option<String>,
+    descending: bool,
+    limit: Option<usize>,
+}
+
+impl Selection {
+    fn apply(&self, data: serde_json::Value) -> Result<serde_json::Value, String> {
+        if self.renames.is_empty()
+            && self.filters.is_empty()
+            && self.sort_by.is_none()
+            && self.limit.is_none()
+        {
+            return Ok(data);
+        }
+        let mut items = match data {
+            serde_json::Value::Array(items) => items,
+            _ => return Err("`column-*`, `where-*`, `sort-by` and `limit` need a list".to_string()),
+        };
+
+        for item in items.iter_mut() {
+            if let serde_json::Value::Object(object) = item {
+                for (field, name) in self.renames.iter() {
+                    if let Some(value) = object.remove(name) {
+                        object.insert(field.to_string(), value);
+                    }
+                }
+            }
+        }
+
+        items.retain(|item| {
+            self.filters.iter().all(|(field, value)| {
+                item.get(field).map(as_text).as_deref() == Some(value.as_str())
+            })
+        });
+
+        if let Some(sort_by) = self.sort_by.as_ref() {
+            items.sort_by(|a, b| compare(a.get(sort_by), b.get(sort_by), self.descending));
+        }
+
+        if let Some(limit) = self.limit {
+            items.truncate(limit);
+        }
+
+        Ok(serde_json::Value::Array(items))
+    }
+}
+
+fn as_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.to_string(),
+        v => v.to_string(),
+    }
+}
+
+/// Numbers, even in strings as read from csv files, are compared as numbers. Missing and `NULL`
+/// values come last, `descending` or not.
+fn compare(
+    a: Option<&serde_json::Value>,
+    b: Option<&serde_json::Value>,
+    descending: bool,
+) -> std::cmp::Ordering {
+    let number = |v: &serde_json::Value| match v {
+        serde_json::Value::Number(n) => n.as_f64(),
+        serde_json::Value::String(s) => s.parse::<f64>().ok(),
+        _ => None,
+    };
+    match (a.filter(|v| !v.is_null()), b.filter(|v| !v.is_null())) {
+        (None, None) => std::cmp::Ordering::Equal,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (Some(a), Some(b)) => {
+            let ordering = match (number(a), number(b)) {
+                (Some(a), Some(b)) => a.total_cmp(&b),
+                _ => as_text(a).cmp(&as_text(b)),
+            };
+            match descending {
+                true => ordering.reverse(),
+                false => ordering,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    fn selection(sort_by: &str, descending: bool) -> super::Selection {
+        super::Selection {
+            sort_by: Some(sort_by.to_string()),
+            descending,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn read_file() {
+        let ds = fastn_ds::DocumentStore::with_backend(
+            "/p",
+            std::sync::Arc::new(fastn_ds::backend::Memory::new()),
+        );
+        ds.write_content(
+            &fastn_ds::Path::new("data/posts.csv"),
+            b"Title,Published On\nhello,2024".to_vec(),
+        )
+        .await
+        .unwrap();
+
+        let mut files_during_render = Default::default();
+        assert_eq!(
+            super::read_file("data/posts.csv", &ds, &mut files_during_render)
+                .await
+                .unwrap(),
+            serde_json::json!([{"title": "hello", "published-on": "2024"}])
+        );
+        assert_eq!(
+            files_during_render,
+            std::collections::BTreeMap::from([(
+                ds.root().join("data/posts.csv").to_string(),
+                fastn_core::utils::generate_hash("Title,Published On\nhello,2024"),
+            )])
+        );
+
+        assert!(
+            super::read_file("data/missing.csv", &ds, &mut files_during_render)
+                .await
+                .unwrap_err()
+                .starts_with("failed to read data/missing.csv")
+        );
+        assert_eq!(files_during_render.len(), 1);
+    }
+
+    #[test]
+    fn parse_file() {
+        let parse =
+            |path: &str, content: &str| super::parse_file(path, content.as_bytes().to_vec());
+
+        assert_eq!(
+            parse("a.json", r#"{"a": [1, 2]}"#).unwrap(),
+            serde_json::json!({"a": [1, 2]})
+        );
+        assert_eq!(
+            parse("a.jsonl", "{\"a\": 1}\n\n{\"a\": 2}\n").unwrap(),
+            serde_json::json!([{"a": 1}, {"a": 2}])
+        );
+        assert_eq!(
+            parse("a.yaml", "a:\n  - 1\n  - b: two\n").unwrap(),
+            serde_json::json!({"a": [1, {"b": "two"}]})
+        );
+        assert_eq!(
+            parse("a.csv", "Full Name,user_id, Age \nJane Doe,1,\n").unwrap(),
+            serde_json::json!([{"full-name": "Jane Doe", "user-id": "1", "age": null}])
+        );
+
+        assert_eq!(
+            parse("a.jsonl", "{\"a\": 1}\n{").unwrap_err(),
+            "a.jsonl:2: EOF while parsing an object at line 1 column 1"
+        );
+        assert_eq!(
+            parse("a.txt", "a").unwrap_err(),
+            "`txt` files are not supported, only json, jsonl, csv and yaml are: a.txt"
+        );
+        assert_eq!(
+            parse("a", "a").unwrap_err(),
+            "file does not have any extension a"
+        );
+        assert_eq!(
+            super::parse_file("a.json", vec![0xff]).unwrap_err(),
+            "a.json is not utf-8"
+        );
+    }
+
+    #[test]
+    fn apply() {
+        let posts = serde_json::json!([
+            {"Post Title": "b", "tag": "rust", "views": "10"},
+            {"Post Title": "a", "tag": "ftd", "views": "9"},
+            {"Post Title": "c", "tag": "rust", "views": "100"},
+        ]);
+
+        assert_eq!(
+            super::Selection::default().apply(posts.clone()).unwrap(),
+            posts
+        );
+        assert_eq!(
+            super::Selection {
+                renames: vec![("title".to_string(), "Post Title".to_string())],
+                filters: vec![("tag".to_string(), "rust".to_string())],
+                sort_by: Some("views".to_string()),
+                descending: true,
+                limit: Some(1),
+            }
+            .apply(posts.clone())
+            .unwrap(),
+            serde_json::json!([{"title": "c", "tag": "rust", "views": "100"}])
+        );
+        assert_eq!(
+            super::Selection {
+                limit: Some(2),
+                ..Default::default()
+            }
+            .apply(serde_json::json!({"a": 1}))
+            .unwrap_err(),
+            "`column-*`, `where-*`, `sort-by` and `limit` need a list"
+        );
+    }
+
+    #[test]
+    fn apply_nulls() {
+        let items = serde_json::json!([{"n": 2}, {"n": null}, {}, {"n": 1}, {"n": 3}]);
+
+        assert_eq!(
+            selection("n", false).apply(items.clone()).unwrap(),
+            serde_json::json!([{"n": 1}, {"n": 2}, {"n": 3}, {"n": null}, {}])
+        );
+        // items without the field stay last, in their order in the file
+        assert_eq!(
+            selection("n", true).apply(items).unwrap(),
+            serde_json::json!([{"n": 3}, {"n": 2}, {"n": 1}, {"n": null}, {}])
+        );
+    }
+
+    #[test]
+    fn compare() {
+        use std::cmp::Ordering;
+
+        let compare = |a: serde_json::Value, b: serde_json::Value, descending: bool| {
+            super::compare(Some(&a), Some(&b), descending)
+        };
+
+        // numbers, in strings or not, compare as numbers
+        assert_eq!(compare("9".into(), "10".into(), false), Ordering::Less);
+        assert_eq!(compare(9.into(), "10".into(), false), Ordering::Less);
+        assert_eq!(compare(1.5.into(), 1.into(), false), Ordering::Greater);
+        assert_eq!(compare("9".into(), "10".into(), true), Ordering::Greater);
+        // anything else compares as text
+        assert_eq!(compare("b".into(), "a".into(), false), Ordering::Greater);
+        assert_eq!(compare("9".into(), "a".into(), false), Ordering::Less);
+        assert_eq!(
+            compare(true.into(), "false".into(), false),
+            Ordering::Greater
+        );
+
+        // missing and null values come last, descending or not
+        for descending in [false, true] {
+            assert_eq!(
+                compare(serde_json::Value::Null, "a".into(), descending),
+                Ordering::Greater
+            );
+            assert_eq!(
+                super::compare(None, Some(&"a".into()), descending),
+                Ordering::Greater
+            );
+            assert_eq!(
+                super::compare(Some(&"a".into()), None, descending),
+                Ordering::Less
+            );
+            assert_eq!(
+                super::compare(None, Some(&serde_json::Value::Null), descending),
+                Ordering::Equal
+            );
+        }
+    }
+}
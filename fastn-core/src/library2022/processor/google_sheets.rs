@@ -7,12 +7,30 @@ pub(crate) struct DataColumn {
     pattern: Option<String>,
 }
 
+/// The column of fields that have none, their value is `NULL`.
+const EMPTY_COLUMN: DataColumn = DataColumn {
+    id: String::new(),
+    label: String::new(),
+    r#type: String::new(),
+    pattern: None,
+};
+
+impl DataColumn {
+    /// A column is mapped to the record field of the same label, `Full Name`, `full_name` or
+    /// `full-name` for the field `full-name`, or the same id, `A` for the field `a`.
+    fn is_column_of_field(&self, field: &str) -> bool {
+        let label = self.label.trim().replace(['_', ' '], "-");
+        label.eq_ignore_ascii_case(field)
+            || (self.label.trim().is_empty() && self.id.eq_ignore_ascii_case(field))
+    }
+}
+
 #[derive(Debug, serde::Deserialize)]
 pub(crate) struct DataRow {
     c: Vec<Option<DataValue>>,
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Deserialize)]
 pub(crate) struct DataValue {
     v: serde_json::Value,
     #[serde(default)]
@@ -82,31 +100,56 @@ fn row_to_record(
     let mut fields: ftd::Map<ftd::interpreter::PropertyValue> = Default::default();
 
     for field in rec_fields.iter() {
-        let idx = match schema
+        let idx = schema
             .iter()
-            .position(|column| column.label.to_string().eq(&field.name))
-        {
-            Some(idx) => idx,
-            None => {
+            .position(|column| column.is_column_of_field(field.name.as_str()));
+        let data_value = idx.and_then(|idx| row.c.get(idx)).and_then(|v| v.as_ref());
+        let is_null = data_value
+            .map(|v| v.v.is_null() && v.f.is_none())
+            .unwrap_or(true);
+        if is_null {
+            if let Some(default) = field.value.as_ref() {
+                fields.insert(field.name.to_string(), default.to_owned());
+                continue;
+            }
+            if idx.is_none() && !field.kind.is_optional() {
                 return ftd::interpreter::utils::e2(
-                    format!("key not found: {}", field.name.as_str()),
+                    format!(
+                        "no column for `{}.{}`, the columns are: {}",
+                        name,
+                        field.name,
+                        schema
+                            .iter()
+                            .map(|column| column.label.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
                     doc.name,
                     value.line_number(),
-                )
+                );
             }
-        };
+        }
 
+        let column = match idx {
+            Some(idx) => &schema[idx],
+            None => &EMPTY_COLUMN,
+        };
         fields.insert(
             field.name.to_string(),
             to_interpreter_value(
                 doc,
                 &field.kind.kind,
-                &schema[idx],
-                &row.c[idx],
+                column,
+                &data_value.cloned(),
                 value.caption(),
                 value.record_name(),
                 value.line_number(),
-            )?
+            )
+            .map_err(|e| ftd::interpreter::Error::ParseError {
+                message: format!("`{}.{}`: {}", name, field.name, e),
+                doc_id: doc.name.to_string(),
+                line_number: value.line_number(),
+            })?
             .into_property_value(false, value.line_number()),
         );
     }
@@ -197,15 +240,15 @@ fn to_interpreter_value(
         ftd::interpreter::Kind::Integer => ftd::interpreter::Value::Integer {
             value: match column.r#type.as_str() {
                 "number" => match &val.v {
-                    serde_json::Value::Number(n) => {
-                        n.as_f64().map(|f| f as i64).ok_or_else(|| {
-                            ftd::interpreter::Error::ParseError {
-                                message: format!("Can't parse to integer, found: {}", &val.v),
-                                doc_id: doc.name.to_string(),
-                                line_number,
-                            }
-                        })?
-                    }
+                    serde_json::Value::Number(n) => n
+                        .as_f64()
+                        .filter(|f| f.fract() == 0.0)
+                        .map(|f| f as i64)
+                        .ok_or_else(|| ftd::interpreter::Error::ParseError {
+                            message: format!("Can't parse to integer, found: {}", &val.v),
+                            doc_id: doc.name.to_string(),
+                            line_number,
+                        })?,
                     serde_json::Value::String(s) => {
                         s.parse::<i64>()
                             .map_err(|_| ftd::interpreter::Error::ParseError {
@@ -575,6 +618,7 @@ pub(crate) async fn process(
     value: ftd::ast::VariableValue,
     kind: ftd::interpreter::Kind,
     doc: &ftd::interpreter::TDoc<'_>,
+    ds: &fastn_ds::DocumentStore,
     db_config: &fastn_core::library2022::processor::sql::DatabaseConfig,
     headers: ftd::ast::HeaderValues,
     query: &str,
@@ -584,23 +628,11 @@ pub(crate) async fn process(
     let request_url =
         fastn_core::google_sheets::prepare_query_url(&db_config.db_url, query.as_str(), sheet);
 
-    let response = match fastn_core::http::http_get_str(&request_url).await {
+    let json = match fastn_core::google_sheets::fetch(ds, &request_url).await {
         Ok(v) => v,
         Err(e) => {
             return ftd::interpreter::utils::e2(
-                format!("HTTP::get failed: {:?}", e),
-                doc.name,
-                value.line_number(),
-            )
-        }
-    };
-
-    let json = match fastn_core::google_sheets::extract_json(&response)? {
-        Some(json) => json,
-        None => {
-            return ftd::interpreter::utils::e2(
-                "Invalid Query Response. Please ensure that your Google Sheet is public."
-                    .to_string(),
+                format!("Failed to query the google sheet: {}", e),
                 doc.name,
                 value.line_number(),
             )
@@ -611,3 +643,29 @@ pub(crate) async fn process(
 
     result_to_value(result, kind, doc, &value)
 }
+
+#[cfg(test)]
+mod test {
+    fn column(id: &str, label: &str) -> super::DataColumn {
+        super::DataColumn {
+            id: id.to_string(),
+            label: label.to_string(),
+            r#type: "string".to_string(),
+            pattern: None,
+        }
+    }
+
+    #[test]
+    fn is_column_of_field() {
+        for label in ["full-name", "Full Name", "full_name", " FULL NAME "] {
+            assert!(
+                column("A", label).is_column_of_field("full-name"),
+                "{label}"
+            );
+        }
+        assert!(!column("A", "Full Name").is_column_of_field("name"));
+        // columns without a label are mapped by id
+        assert!(column("B", "").is_column_of_field("b"));
+        assert!(!column("B", "Name").is_column_of_field("b"));
+    }
+}
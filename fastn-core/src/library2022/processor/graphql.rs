@@ -0,0 +1,351 @@
+/// Runs the GraphQL query in the body against the `url` endpoint, eg:
+///
+/// ```ftd
+/// -- repository repo:
+/// $processor$: pr.graphql
+/// url: https://api.example.com/graphql
+/// $header-x-api-key$: $api-key
+/// data: repository
+/// owner: fastn-stack
+/// string name: $name
+/// integer first: 10
+///
+/// query Repo($owner: String!, $name: String!, $first: Int!) {
+///   repository(owner: $owner, name: $name) {
+///     name
+///     issues(first: $first) { nodes { title } }
+///   }
+/// }
+/// ```
+///
+/// Headers other than `url` and `data` are the variables of the query, `$variable`s are sent
+/// with their ftd value, `integer`, `decimal` and `boolean` headers as numbers and booleans, the
+/// rest as strings. `$header-<name>$` headers are request headers, as in the `http` processor.
+/// `data` picks the field of the response `data` the variable is read from, eg
+/// `repository.issues`, the whole `data` otherwise. `errors` in the response are reported as
+/// errors of the section, at the line of the query of the first error that has one.
+pub async fn process(
+    value: ftd::ast::VariableValue,
+    kind: ftd::interpreter::Kind,
+    doc: &ftd::interpreter::TDoc<'_>,
+    req_config: &mut fastn_core::RequestConfig,
+) -> ftd::interpreter::Result<ftd::interpreter::Value> {
+    let (headers, body, line_number) = {
+        let record = value.get_record(doc.name)?;
+        (
+            record.2.to_owned(),
+            record.3.to_owned(),
+            record.5.to_owned(),
+        )
+    };
+
+    let (query, body_line_number) = match body {
+        Some(body) if !body.value.trim().is_empty() => (body.value, body.line_number),
+        _ => {
+            return ftd::interpreter::utils::e2(
+                "the GraphQL query is required in the body of the section",
+                doc.name,
+                line_number,
+            )
+        }
+    };
+
+    let url = match headers.get_optional_string_by_key("url", doc.name, line_number)? {
+        Some(v) if v.starts_with('$') => doc
+            .get_value(line_number, v.as_str())?
+            .string(doc.name, line_number)?,
+        Some(v) => v,
+        None => {
+            return ftd::interpreter::utils::e2(
+                format!(
+                    "'url' key is required when using `{}: graphql`",
+                    ftd::PROCESSOR_MARKER
+                ),
+                doc.name,
+                line_number,
+            )
+        }
+    };
+    let path = headers.get_optional_string_by_key("data", doc.name, line_number)?;
+
+    let (url, mut conf) =
+        fastn_core::config::utils::get_clean_url(&req_config.config, url.as_str()).map_err(
+            |e| ftd::interpreter::Error::ParseError {
+                message: format!("invalid url: {:?}", e),
+                doc_id: doc.name.to_string(),
+                line_number,
+            },
+        )?;
+    conf.insert("content-type".to_string(), "application/json".to_string());
+
+    let mut variables = serde_json::Map::new();
+    for header in headers.0 {
+        if header.key.as_str() == ftd::PROCESSOR_MARKER
+            || header.key.as_str() == "url"
+            || header.key.as_str() == "data"
+        {
+            continue;
+        }
+
+        let value = header.value.string(doc.name)?;
+        let value = if value.starts_with('$') {
+            doc.get_value(header.line_number, value.as_str())?
+                .to_serde_value(doc)?
+                .unwrap_or(serde_json::Value::Null)
+        } else {
+            header_to_json(value, header.kind.as_deref(), doc.name, header.line_number)?
+        };
+
+        if let Some(key) = fastn_core::http::get_header_key(header.key.as_str()) {
+            let value = match value {
+                serde_json::Value::String(value) => value,
+                value => value.to_string(),
+            };
+            conf.insert(key.to_string(), value);
+            continue;
+        }
+        variables.insert(header.key, value);
+    }
+
+    if !req_config.config.test_command_running {
+        println!("calling `graphql` processor with url: {}", &url);
+    }
+
+    let request = serde_json::json!({
+        "query": query,
+        "variables": variables,
+    });
+    let response = match fastn_core::http::http_post_with_cookie(
+        url.as_str(),
+        req_config.request.cookies_string(),
+        &conf,
+        request.to_string().as_str(),
+    )
+    .await
+    {
+        Ok((Ok(v), cookies)) => {
+            req_config.processor_set_cookies.extend(cookies);
+            v
+        }
+        Ok((Err(e), cookies)) => {
+            req_config.processor_set_cookies.extend(cookies);
+            return ftd::interpreter::utils::e2(
+                format!("GraphQL request failed: {:?}", e),
+                doc.name,
+                line_number,
+            );
+        }
+        Err(e) => {
+            return ftd::interpreter::utils::e2(
+                format!("GraphQL request failed: {:?}", e),
+                doc.name,
+                line_number,
+            )
+        }
+    };
+
+    let response: Response = serde_json::from_slice(response.as_slice()).map_err(|e| {
+        ftd::interpreter::Error::ParseError {
+            message: format!("`graphql` processor API response error: {}", e),
+            doc_id: doc.name.to_string(),
+            line_number,
+        }
+    })?;
+
+    if !response.errors.is_empty() {
+        return ftd::interpreter::utils::e2(
+            format!(
+                "GraphQL query failed: {}",
+                response
+                    .errors
+                    .iter()
+                    .map(GraphQLError::to_string)
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            ),
+            doc.name,
+            response
+                .errors
+                .iter()
+                .find_map(|e| e.line_number(body_line_number))
+                .unwrap_or(line_number),
+        );
+    }
+
+    let mut data = response.data;
+    if let Some(path) = path {
+        for field in path.split('.').filter(|field| !field.is_empty()) {
+            data = match data {
+                serde_json::Value::Object(mut object) => match object.remove(field) {
+                    Some(data) => data,
+                    None => {
+                        return ftd::interpreter::utils::e2(
+                            format!("`{}` not found in the GraphQL response data", path),
+                            doc.name,
+                            line_number,
+                        )
+                    }
+                },
+                _ => {
+                    return ftd::interpreter::utils::e2(
+                        format!("`{}` not found in the GraphQL response data", path),
+                        doc.name,
+                        line_number,
+                    )
+                }
+            };
+        }
+    }
+
+    doc.from_json(&data, &kind, &value)
+}
+
+/// https://spec.graphql.org/October2021/#sec-Response-Format
+#[derive(Debug, serde::Deserialize)]
+struct Response {
+    #[serde(default)]
+    data: serde_json::Value,
+    #[serde(default)]
+    errors: Vec<GraphQLError>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct GraphQLError {
+    message: String,
+    #[serde(default)]
+    locations: Vec<Location>,
+    #[serde(default)]
+    path: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct Location {
+    line: usize,
+    column: usize,
+}
+
+impl GraphQLError {
+    /// The line of the document of the first location of the error in the query, the body of the
+    /// section starting at `body_line_number`.
+    fn line_number(&self, body_line_number: usize) -> Option<usize> {
+        self.locations
+            .first()
+            .map(|l| body_line_number + l.line.saturating_sub(1))
+    }
+}
+
+impl std::fmt::Display for GraphQLError {
+    /// `message (at repository.issues, line 3, column 5 of the query)`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        let mut location = vec![];
+        if !self.path.is_empty() {
+            location.push(format!(
+                "at {}",
+                self.path
+                    .iter()
+                    .map(|p| match p {
+                        serde_json::Value::String(p) => p.to_string(),
+                        p => p.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(".")
+            ));
+        }
+        if let Some(l) = self.locations.first() {
+            location.push(format!("line {}, column {} of the query", l.line, l.column));
+        }
+        if !location.is_empty() {
+            write!(f, " ({})", location.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// The value of a header that is not a `$variable`, as the json type of its ftd kind.
+fn header_to_json(
+    value: String,
+    kind: Option<&str>,
+    doc_name: &str,
+    line_number: usize,
+) -> ftd::interpreter::Result<serde_json::Value> {
+    let invalid = |kind: &str| {
+        ftd::interpreter::utils::e2(
+            format!("`{}` is not a valid {}", value, kind),
+            doc_name,
+            line_number,
+        )
+    };
+    Ok(match kind.map(str::trim) {
+        Some("integer") => match value.trim().parse::<i64>() {
+            Ok(v) => v.into(),
+            Err(_) => return invalid("integer"),
+        },
+        Some("decimal") => match value.trim().parse::<f64>() {
+            Ok(v) => v.into(),
+            Err(_) => return invalid("decimal"),
+        },
+        Some("boolean") => match value.trim().parse::<bool>() {
+            Ok(v) => v.into(),
+            Err(_) => return invalid("boolean"),
+        },
+        _ => serde_json::Value::String(value),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn header_to_json() {
+        let h = |value: &str, kind: Option<&str>| {
+            super::header_to_json(value.to_string(), kind, "foo", 1)
+        };
+        assert_eq!(h(" 10 ", Some("integer")).unwrap(), serde_json::json!(10));
+        assert_eq!(h("1.5", Some("decimal")).unwrap(), serde_json::json!(1.5));
+        assert_eq!(
+            h("true", Some(" boolean")).unwrap(),
+            serde_json::json!(true)
+        );
+        assert_eq!(h("10", Some("string")).unwrap(), serde_json::json!("10"));
+        assert_eq!(h("10", None).unwrap(), serde_json::json!("10"));
+        assert_eq!(
+            h("ten", Some("integer")).unwrap_err().to_string(),
+            "foo:1 -> `ten` is not a valid integer"
+        );
+    }
+
+    #[test]
+    fn graphql_error() {
+        let e = |error: serde_json::Value| {
+            serde_json::from_value::<super::GraphQLError>(error)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(e(serde_json::json!({"message": "denied"})), "denied");
+        assert_eq!(
+            e(serde_json::json!({
+                "message": "not found",
+                "locations": [{"line": 3, "column": 5}],
+                "path": ["repository", "issues", 0],
+            })),
+            "not found (at repository.issues.0, line 3, column 5 of the query)"
+        );
+    }
+
+    #[test]
+    fn graphql_error_line_number() {
+        let e = |error: serde_json::Value| {
+            serde_json::from_value::<super::GraphQLError>(error)
+                .unwrap()
+                .line_number(10)
+        };
+        assert_eq!(e(serde_json::json!({"message": "denied"})), None);
+        assert_eq!(
+            e(serde_json::json!({
+                "message": "not found",
+                "locations": [{"line": 3, "column": 5}, {"line": 1, "column": 1}],
+            })),
+            Some(12)
+        );
+    }
+}
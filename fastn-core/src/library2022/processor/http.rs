@@ -1,13 +1,45 @@
+/// Calls `url` and reads the variable from its json response, eg:
+///
+/// ```ftd
+/// -- api-response result:
+/// $processor$: pr.http
+/// url: https://api.example.com/people/1
+/// method: put
+/// header-authorization: $authorization
+/// timeout: 10s
+/// retries: 2
+/// response: full
+///
+/// {"name": "Alice", "tags": ["admin"]}
+/// ```
+///
+/// `method` is `get` (default), `post`, `put`, `patch` or `delete`. `header-<name>` headers,
+/// and `$header-<name>$` ones, are request headers. The body of the section, or a `body`
+/// header, eg `body: $person`, is sent as is as the json body of the request. Without one,
+/// the other headers are the fields of the json body of `post`, `put` and `patch` requests,
+/// and the query parameters of the rest. `timeout` is eg `30s`, a request that fails to
+/// connect, times out, or gets a `429` or `5xx` response is tried again `retries` times. A
+/// `post` or `patch` request sent again can repeat what it does, eg place an order twice, so
+/// they are only retried with `retry-non-idempotent: true`.
+///
+/// The variable is read from the json response, a response that is not a `2xx` is an error.
+/// With `response: full`, it is read from `{"status": 200, "ok": true, "headers": {..},
+/// "body": ..}` instead, for any status, with lowercase header names and the body as a string
+/// if it is not json.
 pub async fn process(
     value: ftd::ast::VariableValue,
     kind: ftd::interpreter::Kind,
     doc: &ftd::interpreter::TDoc<'_>,
     req_config: &mut fastn_core::RequestConfig,
 ) -> ftd::interpreter::Result<ftd::interpreter::Value> {
-    let (headers, line_number) = if let Ok(val) = value.get_record(doc.name) {
-        (val.2.to_owned(), val.5.to_owned())
+    let (headers, section_body, line_number) = if let Ok(val) = value.get_record(doc.name) {
+        (val.2.to_owned(), val.3.to_owned(), val.5.to_owned())
     } else {
-        (ftd::ast::HeaderValues::new(vec![]), value.line_number())
+        (
+            ftd::ast::HeaderValues::new(vec![]),
+            None,
+            value.line_number(),
+        )
     };
 
     let method = headers
@@ -15,13 +47,29 @@ pub async fn process(
         .unwrap_or_else(|| "GET".to_string())
         .to_lowercase();
 
-    if method.as_str().ne("get") && method.as_str().ne("post") {
-        return ftd::interpreter::utils::e2(
-            format!("only GET and POST methods are allowed, found: {}", method),
-            doc.name,
-            line_number,
-        );
-    }
+    let method = match method.as_str() {
+        "get" => reqwest::Method::GET,
+        "post" => reqwest::Method::POST,
+        "put" => reqwest::Method::PUT,
+        "patch" => reqwest::Method::PATCH,
+        "delete" => reqwest::Method::DELETE,
+        _ => {
+            return ftd::interpreter::utils::e2(
+                format!(
+                    "only GET, POST, PUT, PATCH and DELETE methods are allowed, found: {}",
+                    method
+                ),
+                doc.name,
+                line_number,
+            )
+        }
+    };
+    let has_body = [
+        reqwest::Method::POST,
+        reqwest::Method::PUT,
+        reqwest::Method::PATCH,
+    ]
+    .contains(&method);
 
     let url = match headers.get_optional_string_by_key("url", doc.name, line_number)? {
         Some(v) if v.starts_with('$') => match doc.get_thing(v.as_str(), line_number) {
@@ -57,6 +105,71 @@ pub async fn process(
         }
     };
 
+    let timeout = match headers.get_optional_string_by_key("timeout", doc.name, line_number)? {
+        Some(timeout) => Some(fastn_core::library2022::cache::parse_duration(
+            timeout.as_str(),
+            doc.name,
+            line_number,
+        )?),
+        None => None,
+    };
+
+    let retries = match headers.get_optional_string_by_key("retries", doc.name, line_number)? {
+        Some(retries) => match retries.trim().parse::<usize>() {
+            Ok(retries) => retries,
+            Err(_) => {
+                return ftd::interpreter::utils::e2(
+                    format!("`retries` must be a number, found: {}", retries),
+                    doc.name,
+                    line_number,
+                )
+            }
+        },
+        None => 0,
+    };
+
+    let retry_non_idempotent =
+        match headers.get_optional_string_by_key("retry-non-idempotent", doc.name, line_number)? {
+            None => false,
+            Some(v) if v.eq("false") => false,
+            Some(v) if v.eq("true") => true,
+            Some(v) => {
+                return ftd::interpreter::utils::e2(
+                    format!(
+                        "`retry-non-idempotent` must be `true` or `false`, found: {}",
+                        v
+                    ),
+                    doc.name,
+                    line_number,
+                )
+            }
+        };
+    if retries > 0 && !method.is_idempotent() && !retry_non_idempotent {
+        return ftd::interpreter::utils::e2(
+            format!(
+                "`retries` can send a {} request more than once, add \
+                `retry-non-idempotent: true` if that is safe",
+                method.as_str().to_lowercase()
+            ),
+            doc.name,
+            line_number,
+        );
+    }
+
+    let full_response =
+        match headers.get_optional_string_by_key("response", doc.name, line_number)? {
+            None => false,
+            Some(v) if v.eq("body") => false,
+            Some(v) if v.eq("full") => true,
+            Some(v) => {
+                return ftd::interpreter::utils::e2(
+                    format!("`response` must be `body` or `full`, found: {}", v),
+                    doc.name,
+                    line_number,
+                )
+            }
+        };
+
     let (mut url, mut conf) =
         fastn_core::config::utils::get_clean_url(&req_config.config, url.as_str()).map_err(
             |e| ftd::interpreter::Error::ParseError {
@@ -66,11 +179,17 @@ pub async fn process(
             },
         )?;
 
+    let mut raw_body = section_body.map(|body| body.value);
     let mut body = vec![];
+    let mut params = vec![];
     for header in headers.0 {
         if header.key.as_str() == ftd::PROCESSOR_MARKER
             || header.key.as_str() == "url"
             || header.key.as_str() == "method"
+            || header.key.as_str() == "timeout"
+            || header.key.as_str() == "retries"
+            || header.key.as_str() == "retry-non-idempotent"
+            || header.key.as_str() == "response"
         {
             continue;
         }
@@ -79,83 +198,122 @@ pub async fn process(
 
         // 1 id: $query.id
         // After resolve headers: id:1234(value of $query.id)
-        if value.starts_with('$') {
-            if let Some(value) = doc
+        let (value, is_json) = if value.starts_with('$') {
+            match doc
                 .get_value(header.line_number, value.as_str())?
                 .to_json_string(doc, true)?
             {
-                if let Some(key) = fastn_core::http::get_header_key(header.key.as_str()) {
-                    conf.insert(key.to_string(), value.trim_matches('"').to_string());
-                    continue;
-                }
-                if method.as_str().eq("post") {
-                    body.push(format!("\"{}\": {}", header.key, value));
-                    continue;
-                }
-                url.query_pairs_mut()
-                    .append_pair(header.key.as_str(), value.trim_matches('"'));
+                Some(value) => (value, true),
+                None => continue,
             }
         } else {
-            if let Some(key) = fastn_core::http::get_header_key(header.key.as_str()) {
-                conf.insert(key.to_string(), value);
-                continue;
+            (value, false)
+        };
+
+        if header.key.as_str() == "body" {
+            if raw_body.is_some() {
+                return ftd::interpreter::utils::e2(
+                    "the request body is given both as the `body` header and the section body",
+                    doc.name,
+                    header.line_number,
+                );
             }
-            if method.as_str().eq("post") {
+            raw_body = Some(value);
+            continue;
+        }
+
+        let key = match header.key.strip_prefix("header-") {
+            Some(key) => Some(key),
+            None => fastn_core::http::get_header_key(header.key.as_str()),
+        };
+        if let Some(key) = key {
+            let value = if is_json {
+                value.trim_matches('"').to_string()
+            } else {
+                value
+            };
+            conf.insert(key.to_lowercase(), value);
+            continue;
+        }
+
+        params.push((header.key, value, is_json));
+    }
+
+    for (key, value, is_json) in params {
+        if has_body && raw_body.is_none() {
+            if is_json {
+                body.push(format!("\"{}\": {}", key, value));
+            } else {
                 body.push(format!(
                     "\"{}\": \"{}\"",
-                    header.key,
+                    key,
                     fastn_core::utils::escape_string(value.as_str())
                 ));
-                continue;
             }
+            continue;
+        }
+        if is_json {
+            url.query_pairs_mut()
+                .append_pair(key.as_str(), value.trim_matches('"'));
+        } else {
             url.query_pairs_mut()
-                .append_pair(header.key.as_str(), value.as_str());
+                .append_pair(key.as_str(), value.as_str());
         }
     }
 
+    let request_body = match raw_body {
+        Some(raw_body) => Some(raw_body),
+        None if has_body => Some(format!("{{{}}}", body.join(","))),
+        None => None,
+    };
+    if request_body.is_some() && !conf.contains_key("content-type") {
+        conf.insert("content-type".to_string(), "application/json".to_string());
+    }
+
     if !req_config.config.test_command_running {
         println!("calling `http` processor with url: {}", &url);
     }
 
-    let resp = if method.as_str().eq("post") {
-        fastn_core::http::http_post_with_cookie(
-            url.as_str(),
-            req_config.request.cookies_string(),
-            &conf,
-            format!("{{{}}}", body.join(",")).as_str(),
-        )
-        .await
-    } else {
-        fastn_core::http::http_get_with_cookie(
-            url.as_str(),
-            req_config.request.cookies_string(),
-            &conf,
-            false, // disable cache
-        )
-        .await
-    };
-
-    let response = match resp {
-        Ok((Ok(v), cookies)) => {
-            req_config.processor_set_cookies.extend(cookies);
-            v
-        }
-        Ok((Err(e), cookies)) => {
-            req_config.processor_set_cookies.extend(cookies);
-            return ftd::interpreter::utils::e2(
-                format!("HTTP::get failed: {:?}", e),
-                doc.name,
-                line_number,
-            );
-        }
+    let fastn_core::http::ProcessorResponse {
+        status,
+        headers: response_headers,
+        body: response,
+        cookies,
+    } = match fastn_core::http::http_request_with_cookie(
+        method.clone(),
+        url.as_str(),
+        req_config.request.cookies_string(),
+        &conf,
+        request_body,
+        timeout,
+        retries,
+    )
+    .await
+    {
+        Ok(response) => response,
         Err(e) => {
             return ftd::interpreter::utils::e2(
-                format!("HTTP::get failed: {:?}", e),
+                format!("HTTP::{} failed: {:?}", method.as_str().to_lowercase(), e),
                 doc.name,
                 line_number,
             )
         }
     };
+    req_config.processor_set_cookies.extend(cookies);
+
+    if !full_response && !status.is_success() {
+        return ftd::interpreter::utils::e2(
+            format!(
+                "HTTP::{} failed: url: {}, response_status: {}, response: {:?}",
+                method.as_str().to_lowercase(),
+                url,
+                status,
+                String::from_utf8_lossy(response.as_slice())
+            ),
+            doc.name,
+            line_number,
+        );
+    }
 
     let response_string =
         String::from_utf8(response).map_err(|e| ftd::interpreter::Error::ParseError {
@@ -163,8 +321,86 @@ pub async fn process(
             doc_id: doc.name.to_string(),
             line_number,
         })?;
-    let response_json: serde_json::Value = serde_json::from_str(&response_string)
-        .map_err(|e| ftd::interpreter::Error::Serde { source: e })?;
+    if full_response {
+        return doc.from_json(
+            &full_response_json(status, &response_headers, response_string),
+            &kind,
+            &value,
+        );
+    }
+
+    let response_json: serde_json::Value = if response_string.trim().is_empty() {
+        // eg `204 No Content`
+        serde_json::Value::Null
+    } else {
+        serde_json::from_str(&response_string)
+            .map_err(|e| ftd::interpreter::Error::Serde { source: e })?
+    };
 
     doc.from_json(&response_json, &kind, &value)
 }
+
+/// What the variable is read from with `response: full`, the body is a string if it is not
+/// json, and `null` if it is empty.
+fn full_response_json(
+    status: reqwest::StatusCode,
+    headers: &std::collections::BTreeMap<String, String>,
+    body: String,
+) -> serde_json::Value {
+    let body = if body.trim().is_empty() {
+        serde_json::Value::Null
+    } else {
+        serde_json::from_str(&body).unwrap_or(serde_json::Value::String(body))
+    };
+    serde_json::json!({
+        "status": status.as_u16(),
+        "ok": status.is_success(),
+        "headers": headers,
+        "body": body,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn full_response_json() {
+        let headers = std::collections::BTreeMap::from([(
+            "content-type".to_string(),
+            "application/json".to_string(),
+        )]);
+        assert_eq!(
+            super::full_response_json(
+                reqwest::StatusCode::CREATED,
+                &headers,
+                r#"{"id": 1}"#.to_string()
+            ),
+            serde_json::json!({
+                "status": 201,
+                "ok": true,
+                "headers": {"content-type": "application/json"},
+                "body": {"id": 1},
+            })
+        );
+        assert_eq!(
+            super::full_response_json(
+                reqwest::StatusCode::NOT_FOUND,
+                &Default::default(),
+                "not found".to_string()
+            ),
+            serde_json::json!({
+                "status": 404,
+                "ok": false,
+                "headers": {},
+                "body": "not found",
+            })
+        );
+        assert_eq!(
+            super::full_response_json(
+                reqwest::StatusCode::NO_CONTENT,
+                &Default::default(),
+                "".to_string()
+            )["body"],
+            serde_json::Value::Null
+        );
+    }
+}
@@ -1,13 +1,16 @@
 pub(crate) mod apps;
+pub(crate) mod data_files;
 pub(crate) mod document;
 pub(crate) mod fetch_file;
 pub(crate) mod figma_tokens;
 pub(crate) mod figma_typography_tokens;
 pub(crate) mod get_data;
 pub(crate) mod google_sheets;
+pub(crate) mod graphql;
 pub(crate) mod http;
 pub(crate) mod lang;
 pub(crate) mod lang_details;
+pub(crate) mod mysql;
 pub(crate) mod package_query;
 pub(crate) mod pg;
 pub(crate) mod query;
@@ -18,6 +21,7 @@ pub(crate) mod sqlite;
 pub(crate) mod toc;
 pub(crate) mod user_details;
 pub(crate) mod user_group;
+pub(crate) mod user_sessions;
 
 // pub enum Processor {
 //     Toc,
@@ -1,6 +1,21 @@
+#[derive(thiserror::Error, Debug)]
+enum PoolError {
+    #[error("{0}")]
+    CreatePool(#[from] deadpool_postgres::CreatePoolError),
+
+    #[error("FASTN_PG_POOL_SIZE is set to {0}, it must be a number greater than 0")]
+    InvalidPoolSize(String),
+
+    #[error(
+        "FASTN_PG_RECYCLING_METHOD is set to {0}, which is invalid, only allowed values are \
+        fast, verified and clean"
+    )]
+    InvalidRecyclingMethod(String),
+}
+
 async fn create_pool(
     req_config: &fastn_core::RequestConfig,
-) -> Result<deadpool_postgres::Pool, deadpool_postgres::CreatePoolError> {
+) -> Result<deadpool_postgres::Pool, PoolError> {
     let mut cfg = deadpool_postgres::Config::new();
     cfg.libpq_style_connection_string = match req_config.config.ds.env("FASTN_DB_URL").await {
         Ok(v) => Some(v),
@@ -8,13 +23,32 @@ async fn create_pool(
             fastn_core::warning!("FASTN_DB_URL is not set");
             return Err(deadpool_postgres::CreatePoolError::Config(
                 deadpool_postgres::ConfigError::ConnectionStringInvalid,
-            ));
+            )
+            .into());
         }
     };
     cfg.manager = Some(deadpool_postgres::ManagerConfig {
-        // TODO: make this configurable
-        recycling_method: deadpool_postgres::RecyclingMethod::Verified,
+        recycling_method: parse_recycling_method(
+            req_config
+                .config
+                .ds
+                .env("FASTN_PG_RECYCLING_METHOD")
+                .await
+                .ok()
+                .as_deref(),
+        )?,
     });
+    if let Some(pool_size) = parse_pool_size(
+        req_config
+            .config
+            .ds
+            .env("FASTN_PG_POOL_SIZE")
+            .await
+            .ok()
+            .as_deref(),
+    )? {
+        cfg.pool = Some(deadpool_postgres::PoolConfig::new(pool_size));
+    }
     let runtime = Some(deadpool_postgres::Runtime::Tokio1);
 
     if let Ok(true) = req_config
@@ -27,7 +61,7 @@ async fn create_pool(
             "FASTN_PG_DANGER_DISABLE_SSL is set to false, this is not recommended for production use",
         );
         cfg.ssl_mode = Some(deadpool_postgres::SslMode::Disable);
-        return cfg.create_pool(runtime, tokio_postgres::NoTls);
+        return Ok(cfg.create_pool(runtime, tokio_postgres::NoTls)?);
     }
 
     let mut connector = native_tls::TlsConnector::builder();
@@ -58,7 +92,8 @@ async fn create_pool(
             );
             return Err(deadpool_postgres::CreatePoolError::Config(
                 deadpool_postgres::ConfigError::ConnectionStringInvalid,
-            ));
+            )
+            .into());
         }
     }
 
@@ -89,25 +124,137 @@ async fn create_pool(
     }
 
     let tls = postgres_native_tls::MakeTlsConnector::new(connector.build().unwrap());
-    cfg.create_pool(runtime, tls)
+    Ok(cfg.create_pool(runtime, tls)?)
+}
+
+/// `FASTN_PG_POOL_SIZE`: how many connections are open at most, deadpool's default, four per
+/// CPU, if not set.
+fn parse_pool_size(value: Option<&str>) -> Result<Option<usize>, PoolError> {
+    let value = match value {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    match value.parse::<usize>() {
+        Ok(size) if size > 0 => Ok(Some(size)),
+        _ => Err(PoolError::InvalidPoolSize(value.to_string())),
+    }
+}
+
+/// `FASTN_PG_RECYCLING_METHOD`: how a connection is checked before it is reused:
+/// - `fast`: not at all, only whether it was closed
+/// - `verified` (default): runs an empty query
+/// - `clean`: also discards the session state, eg prepared statements and temporary tables
+fn parse_recycling_method(
+    value: Option<&str>,
+) -> Result<deadpool_postgres::RecyclingMethod, PoolError> {
+    match value {
+        None | Some("verified") => Ok(deadpool_postgres::RecyclingMethod::Verified),
+        Some("fast") => Ok(deadpool_postgres::RecyclingMethod::Fast),
+        Some("clean") => Ok(deadpool_postgres::RecyclingMethod::Clean),
+        Some(v) => Err(PoolError::InvalidRecyclingMethod(v.to_string())),
+    }
 }
 
 // TODO: I am a little confused about the use of `tokio::sync` here, both sides are async, so why
 //       do we need to use `tokio::sync`? Am I doing something wrong? How do I prove/verify that
 //       this is correct?
-static POOL_RESULT: tokio::sync::OnceCell<
-    Result<deadpool_postgres::Pool, deadpool_postgres::CreatePoolError>,
-> = tokio::sync::OnceCell::const_new();
+//
+// Only a pool that was created is kept, a configuration error is reported to the query that ran
+// into it and the next query tries again, so fixing the environment does not need a restart.
+static POOL: tokio::sync::OnceCell<deadpool_postgres::Pool> = tokio::sync::OnceCell::const_new();
+
+static METRICS: once_cell::sync::Lazy<Metrics> = once_cell::sync::Lazy::new(Default::default);
+
+/// How the pool has been used since the server started, see `metrics()`.
+#[derive(Default)]
+struct Metrics {
+    queries: std::sync::atomic::AtomicU64,
+    failed_queries: std::sync::atomic::AtomicU64,
+    /// time spent waiting for a connection, a pool too small for the load makes it grow
+    wait_micros: std::sync::atomic::AtomicU64,
+    max_wait_micros: std::sync::atomic::AtomicU64,
+    query_micros: std::sync::atomic::AtomicU64,
+}
 
-static EXECUTE_QUERY_LOCK: once_cell::sync::Lazy<tokio::sync::Mutex<()>> =
-    once_cell::sync::Lazy::new(|| tokio::sync::Mutex::new(()));
+impl Metrics {
+    fn record(&self, wait: std::time::Duration, query: std::time::Duration, failed: bool) {
+        use std::sync::atomic::Ordering;
 
-async fn pool(
+        let wait = wait.as_micros() as u64;
+        self.queries.fetch_add(1, Ordering::Relaxed);
+        if failed {
+            self.failed_queries.fetch_add(1, Ordering::Relaxed);
+        }
+        self.wait_micros.fetch_add(wait, Ordering::Relaxed);
+        self.max_wait_micros.fetch_max(wait, Ordering::Relaxed);
+        self.query_micros
+            .fetch_add(query.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+/// `/-/pg/metrics/`: the state of the pool and how it has been used, as JSON. Only served when
+/// `FASTN_PG_METRICS` is set to `true`.
+pub async fn metrics(
     req_config: &fastn_core::RequestConfig,
-) -> &'static Result<deadpool_postgres::Pool, deadpool_postgres::CreatePoolError> {
-    POOL_RESULT
-        .get_or_init(|| async { create_pool(req_config).await })
+) -> fastn_core::Result<fastn_core::http::Response> {
+    use std::sync::atomic::Ordering;
+
+    if !req_config
+        .config
+        .ds
+        .env_bool("FASTN_PG_METRICS", false)
         .await
+        .unwrap_or(false)
+    {
+        return Ok(fastn_core::http::not_found_without_warning(
+            "FASTN_PG_METRICS is not enabled".to_string(),
+        ));
+    }
+
+    let pool = match POOL.get() {
+        Some(pool) => {
+            let status = pool.status();
+            serde_json::json!({
+                "max-size": status.max_size,
+                "size": status.size,
+                "available": status.available,
+            })
+        }
+        // created with the first query
+        None => serde_json::Value::Null,
+    };
+
+    let queries = METRICS.queries.load(Ordering::Relaxed);
+    let wait_micros = METRICS.wait_micros.load(Ordering::Relaxed);
+    let query_micros = METRICS.query_micros.load(Ordering::Relaxed);
+    let average = |micros: u64| {
+        if queries == 0 {
+            0.0
+        } else {
+            micros as f64 / queries as f64 / 1000.0
+        }
+    };
+
+    Ok(fastn_core::http::api_ok(serde_json::json!({
+        "pool": pool,
+        "queries": queries,
+        "failed-queries": METRICS.failed_queries.load(Ordering::Relaxed),
+        "wait-ms": {
+            "total": wait_micros as f64 / 1000.0,
+            "max": METRICS.max_wait_micros.load(Ordering::Relaxed) as f64 / 1000.0,
+            "average": average(wait_micros),
+        },
+        "query-ms": {
+            "total": query_micros as f64 / 1000.0,
+            "average": average(query_micros),
+        },
+    }))?)
+}
+
+async fn pool(
+    req_config: &fastn_core::RequestConfig,
+) -> Result<&'static deadpool_postgres::Pool, PoolError> {
+    POOL.get_or_try_init(|| create_pool(req_config)).await
 }
 
 pub async fn process(
@@ -177,7 +324,30 @@ fn resolve_variable_from_doc(
         }
     };
 
-    Ok(match (e, thing) {
+    value_to_pg(doc, var, e, thing, line_number)
+}
+
+/// `optional` variables that are `NULL` bind as a `NULL` of the parameter's type.
+fn value_to_pg(
+    doc: &ftd::interpreter::TDoc<'_>,
+    var: &str,
+    e: &postgres_types::Type,
+    value: ftd::interpreter::Value,
+    line_number: usize,
+) -> ftd::interpreter::Result<Box<PGData>> {
+    Ok(match (e, value) {
+        (e, ftd::interpreter::Value::Optional { data, .. }) => match *data {
+            Some(value) => value_to_pg(doc, var, e, value, line_number)?,
+            None => match *e {
+                postgres_types::Type::INT2 => Box::new(None::<i16>),
+                postgres_types::Type::INT4 => Box::new(None::<i32>),
+                postgres_types::Type::INT8 => Box::new(None::<i64>),
+                postgres_types::Type::FLOAT4 => Box::new(None::<f32>),
+                postgres_types::Type::FLOAT8 => Box::new(None::<f64>),
+                postgres_types::Type::BOOL => Box::new(None::<bool>),
+                _ => Box::new(None::<String>),
+            },
+        },
         (&postgres_types::Type::TEXT, ftd::interpreter::Value::String { text, .. }) => {
             Box::new(text)
         }
@@ -352,31 +522,261 @@ async fn execute_query(
     line_number: usize,
     headers: ftd::ast::HeaderValues,
     req_config: &fastn_core::RequestConfig,
-) -> ftd::interpreter::Result<Vec<Vec<serde_json::Value>>> {
-    let _lock = EXECUTE_QUERY_LOCK.lock().await;
-
+) -> ftd::interpreter::Result<super::sql::Rows> {
     let (query, query_args) = super::sql::extract_arguments(query)?;
-    let client = pool(req_config)
-        .await
-        .as_ref()
-        .unwrap()
-        .get()
-        .await
-        .unwrap();
+    let pool = match pool(req_config).await {
+        Ok(pool) => pool,
+        Err(e) => {
+            return ftd::interpreter::utils::e2(
+                format!("failed to create the postgres pool: {e}"),
+                doc.name,
+                line_number,
+            )
+        }
+    };
 
-    let stmt = client.prepare_cached(query.as_str()).await.unwrap();
+    // Every query gets a connection of its own, queries only wait for each other when all
+    // connections of the pool are in use
+    let start = std::time::Instant::now();
+    let client = match pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            METRICS.record(start.elapsed(), Default::default(), true);
+            return ftd::interpreter::utils::e2(
+                format!("failed to get a postgres connection: {e}"),
+                doc.name,
+                line_number,
+            );
+        }
+    };
+    let wait = start.elapsed();
 
-    let args = prepare_args(query_args, stmt.params(), doc, line_number, headers)?;
-    let rows = client.query(&stmt, &args.pg_args()).await.unwrap();
-    let mut result: Vec<Vec<serde_json::Value>> = vec![];
+    let start = std::time::Instant::now();
+    let rows = query_rows(
+        &client,
+        query.as_str(),
+        query_args,
+        doc,
+        line_number,
+        headers,
+    )
+    .await;
+    METRICS.record(wait, start.elapsed(), rows.is_err());
+    let (columns, rows) = rows?;
 
+    let mut result: Vec<Vec<serde_json::Value>> = vec![];
     for r in rows {
         result.push(row_to_json(r, doc.name, line_number)?)
     }
 
+    Ok(super::sql::Rows {
+        columns,
+        rows: result,
+    })
+}
+
+async fn query_rows(
+    client: &deadpool_postgres::Client,
+    query: &str,
+    query_args: Vec<String>,
+    doc: &ftd::interpreter::TDoc<'_>,
+    line_number: usize,
+    headers: ftd::ast::HeaderValues,
+) -> ftd::interpreter::Result<(Vec<String>, Vec<tokio_postgres::Row>)> {
+    let stmt = match client.prepare_cached(query).await {
+        Ok(stmt) => stmt,
+        Err(e) => {
+            return ftd::interpreter::utils::e2(
+                format!("failed to prepare query: {e}"),
+                doc.name,
+                line_number,
+            )
+        }
+    };
+
+    let args = prepare_args(query_args, stmt.params(), doc, line_number, headers)?;
+    match client.query(&stmt, &args.pg_args()).await {
+        Ok(rows) => Ok((column_names(&stmt), rows)),
+        Err(e) => ftd::interpreter::utils::e2(
+            format!("failed to execute query: {e}"),
+            doc.name,
+            line_number,
+        ),
+    }
+}
+
+/// Runs `statements` in a transaction, for `sql-execute`, nothing is written if one fails.
+pub(crate) async fn execute(
+    statements: &[String],
+    doc: &ftd::interpreter::TDoc<'_>,
+    line_number: usize,
+    headers: ftd::ast::HeaderValues,
+    req_config: &fastn_core::RequestConfig,
+) -> ftd::interpreter::Result<super::sql::ExecuteResult> {
+    let pool = match pool(req_config).await {
+        Ok(pool) => pool,
+        Err(e) => {
+            return ftd::interpreter::utils::e2(
+                format!("failed to create the postgres pool: {e}"),
+                doc.name,
+                line_number,
+            )
+        }
+    };
+
+    let start = std::time::Instant::now();
+    let mut client = match pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            METRICS.record(start.elapsed(), Default::default(), true);
+            return ftd::interpreter::utils::e2(
+                format!("failed to get a postgres connection: {e}"),
+                doc.name,
+                line_number,
+            );
+        }
+    };
+    let wait = start.elapsed();
+
+    let start = std::time::Instant::now();
+    let result = execute_in_transaction(&mut client, statements, doc, line_number, headers).await;
+    METRICS.record(wait, start.elapsed(), result.is_err());
+    result
+}
+
+async fn execute_in_transaction(
+    client: &mut deadpool_postgres::Client,
+    statements: &[String],
+    doc: &ftd::interpreter::TDoc<'_>,
+    line_number: usize,
+    headers: ftd::ast::HeaderValues,
+) -> ftd::interpreter::Result<super::sql::ExecuteResult> {
+    let failed = |message: String| -> ftd::interpreter::Result<super::sql::ExecuteResult> {
+        ftd::interpreter::utils::e2(message, doc.name, line_number)
+    };
+
+    // Rolled back when dropped, unless committed
+    let transaction = match client.transaction().await {
+        Ok(transaction) => transaction,
+        Err(e) => return failed(format!("failed to start transaction: {e}")),
+    };
+
+    let mut result = super::sql::ExecuteResult {
+        affected: 0,
+        rows: Default::default(),
+    };
+    for statement in statements {
+        let (query, query_args) = super::sql::extract_arguments(statement)?;
+        let stmt = match transaction.prepare_cached(query.as_str()).await {
+            Ok(stmt) => stmt,
+            Err(e) => return failed(format!("failed to prepare query: {e}")),
+        };
+        let args = prepare_args(query_args, stmt.params(), doc, line_number, headers.clone())?;
+
+        if stmt.columns().is_empty() {
+            match transaction.execute(&stmt, &args.pg_args()).await {
+                Ok(affected) => result.affected += affected as usize,
+                Err(e) => return failed(format!("failed to execute query: {e}")),
+            }
+            continue;
+        }
+
+        // eg `INSERT ... RETURNING id`
+        let rows = match transaction.query(&stmt, &args.pg_args()).await {
+            Ok(rows) => rows,
+            Err(e) => return failed(format!("failed to execute query: {e}")),
+        };
+        result.affected += rows.len();
+        if !rows.is_empty() {
+            result.rows = super::sql::Rows {
+                columns: column_names(&stmt),
+                rows: rows
+                    .into_iter()
+                    .map(|r| row_to_json(r, doc.name, line_number))
+                    .collect::<ftd::interpreter::Result<_>>()?,
+            };
+        }
+    }
+
+    if let Err(e) = transaction.commit().await {
+        return failed(format!("failed to commit transaction: {e}"));
+    }
+
     Ok(result)
 }
 
+/// The columns `query` returns, see `sql::describe()`, the query is only prepared.
+pub(crate) async fn describe(
+    query: &str,
+    doc_name: &str,
+    line_number: usize,
+    req_config: &fastn_core::RequestConfig,
+) -> ftd::interpreter::Result<Vec<super::sql::Column>> {
+    let (query, _) = super::sql::extract_arguments(query)?;
+    let pool = match pool(req_config).await {
+        Ok(pool) => pool,
+        Err(e) => {
+            return ftd::interpreter::utils::e2(
+                format!("failed to create the postgres pool: {e}"),
+                doc_name,
+                line_number,
+            )
+        }
+    };
+    let client = match pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            return ftd::interpreter::utils::e2(
+                format!("failed to get a postgres connection: {e}"),
+                doc_name,
+                line_number,
+            )
+        }
+    };
+    let stmt = match client.prepare(query.as_str()).await {
+        Ok(stmt) => stmt,
+        Err(e) => {
+            return ftd::interpreter::utils::e2(
+                format!("failed to prepare query: {e}"),
+                doc_name,
+                line_number,
+            )
+        }
+    };
+
+    Ok(stmt
+        .columns()
+        .iter()
+        .map(|column| super::sql::Column {
+            name: column.name().to_string(),
+            type_: match *column.type_() {
+                postgres_types::Type::TEXT
+                | postgres_types::Type::VARCHAR
+                | postgres_types::Type::BPCHAR
+                | postgres_types::Type::NAME => super::sql::ColumnType::Text,
+                postgres_types::Type::INT2
+                | postgres_types::Type::INT4
+                | postgres_types::Type::INT8 => super::sql::ColumnType::Integer,
+                postgres_types::Type::FLOAT4
+                | postgres_types::Type::FLOAT8
+                | postgres_types::Type::NUMERIC => super::sql::ColumnType::Decimal,
+                postgres_types::Type::BOOL => super::sql::ColumnType::Boolean,
+                postgres_types::Type::JSON | postgres_types::Type::JSONB => {
+                    super::sql::ColumnType::Json
+                }
+                _ => super::sql::ColumnType::Unknown,
+            },
+        })
+        .collect())
+}
+
+fn column_names(stmt: &tokio_postgres::Statement) -> Vec<String> {
+    stmt.columns()
+        .iter()
+        .map(|column| column.name().to_string())
+        .collect()
+}
+
 fn row_to_json(
     r: tokio_postgres::Row,
     doc_name: &str,
@@ -512,3 +912,45 @@ SELECT * FROM "users" where id >= $1;
 SELECT parameter_types FROM pg_prepared_statements WHERE name = 'my_query';
 DEALLOCATE my_query;
  */
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn parse_pool_size() {
+        assert_eq!(super::parse_pool_size(None).unwrap(), None);
+        assert_eq!(super::parse_pool_size(Some("10")).unwrap(), Some(10));
+        for value in ["0", "-1", "ten", ""] {
+            assert_eq!(
+                super::parse_pool_size(Some(value)).unwrap_err().to_string(),
+                format!("FASTN_PG_POOL_SIZE is set to {value}, it must be a number greater than 0")
+            );
+        }
+    }
+
+    #[test]
+    fn parse_recycling_method() {
+        assert!(matches!(
+            super::parse_recycling_method(None),
+            Ok(deadpool_postgres::RecyclingMethod::Verified)
+        ));
+        assert!(matches!(
+            super::parse_recycling_method(Some("verified")),
+            Ok(deadpool_postgres::RecyclingMethod::Verified)
+        ));
+        assert!(matches!(
+            super::parse_recycling_method(Some("fast")),
+            Ok(deadpool_postgres::RecyclingMethod::Fast)
+        ));
+        assert!(matches!(
+            super::parse_recycling_method(Some("clean")),
+            Ok(deadpool_postgres::RecyclingMethod::Clean)
+        ));
+        assert_eq!(
+            super::parse_recycling_method(Some("Fast"))
+                .unwrap_err()
+                .to_string(),
+            "FASTN_PG_RECYCLING_METHOD is set to Fast, which is invalid, only allowed values are \
+            fast, verified and clean"
+        );
+    }
+}
@@ -35,21 +35,12 @@ pub async fn process(
     value: ftd::ast::VariableValue,
     kind: ftd::interpreter::Kind,
     doc: &ftd::interpreter::TDoc<'_>,
-    config: &fastn_core::RequestConfig,
+    config: &mut fastn_core::RequestConfig,
 ) -> ftd::interpreter::Result<ftd::interpreter::Value> {
     let (headers, query) = super::sqlite::get_p1_data("sql", &value, doc.name)?;
 
-    let db_config = match headers.get_optional_string_by_key("db", doc.name, value.line_number())? {
-        Some(url) => match fastn_core::google_sheets::extract_google_sheets_id(url.as_str()) {
-            Some(google_sheet_id) => {
-                let db_url =
-                    fastn_core::google_sheets::generate_google_sheet_url(google_sheet_id.as_str());
-                DatabaseConfig::new(db_url, "google_sheets".to_string())
-            }
-            None => DatabaseConfig::new(url, "sqlite".to_string()),
-        },
-        None => fastn_core::library2022::processor::sql::get_db_config(&config.config.ds).await?,
-    };
+    let db_config =
+        query_db_config(&headers, doc.name, value.line_number(), &config.config.ds).await?;
 
     let db_type = db_config.db_type.as_str();
 
@@ -68,6 +59,16 @@ pub async fn process(
         )
         .await?),
         "google_sheets" => Ok(fastn_core::library2022::processor::google_sheets::process(
+            value,
+            kind,
+            doc,
+            &config.config.ds,
+            &db_config,
+            headers,
+            query.as_str(),
+        )
+        .await?),
+        "mysql" => Ok(fastn_core::library2022::processor::mysql::process(
             value,
             kind,
             doc,
@@ -76,10 +77,277 @@ pub async fn process(
             query.as_str(),
         )
         .await?),
-        _ => unimplemented!("Database currently not supported."),
+        "data_files" => Ok(fastn_core::library2022::processor::data_files::process(
+            value,
+            kind,
+            doc,
+            config,
+            db_config.db_url.as_str(),
+            headers,
+            query.as_str(),
+        )
+        .await?),
+        t => ftd::interpreter::utils::e2(
+            format!("`{}` databases are not supported", t),
+            doc.name,
+            value.line_number(),
+        ),
     }
 }
 
+/// The database the `sql` processor queries: the `db` header, a google sheet, data files of the
+/// package, eg `data/*.csv`, or a sqlite file, else `FASTN_DB_URL`.
+async fn query_db_config(
+    headers: &ftd::ast::HeaderValues,
+    doc_name: &str,
+    line_number: usize,
+    ds: &fastn_ds::DocumentStore,
+) -> ftd::interpreter::Result<DatabaseConfig> {
+    Ok(
+        match headers.get_optional_string_by_key("db", doc_name, line_number)? {
+            Some(url) => match fastn_core::google_sheets::extract_google_sheets_id(url.as_str()) {
+                Some(google_sheet_id) => {
+                    let db_url = fastn_core::google_sheets::generate_google_sheet_url(
+                        google_sheet_id.as_str(),
+                    );
+                    DatabaseConfig::new(db_url, "google_sheets".to_string())
+                }
+                None if super::data_files::is_data_files(url.as_str()) => {
+                    DatabaseConfig::new(url, "data_files".to_string())
+                }
+                None => DatabaseConfig::new(url, "sqlite".to_string()),
+            },
+            None => fastn_core::library2022::processor::sql::get_db_config(ds).await?,
+        },
+    )
+}
+
+/// `sql-execute`: runs the statements of the body, eg INSERT, UPDATE or DELETE, in a transaction.
+/// An `integer` variable gets the number of affected rows, other kinds get the rows returned by
+/// the last statement that returned any, eg with `RETURNING`.
+///
+/// Only runs on POST, PUT, PATCH and DELETE requests, so following a link, eg by a crawler, never
+/// writes to the database.
+pub async fn execute(
+    value: ftd::ast::VariableValue,
+    kind: ftd::interpreter::Kind,
+    doc: &ftd::interpreter::TDoc<'_>,
+    config: &fastn_core::RequestConfig,
+) -> ftd::interpreter::Result<ftd::interpreter::Value> {
+    let (headers, query) = super::sqlite::get_p1_data("sql-execute", &value, doc.name)?;
+
+    if !is_write_request(&config.request) {
+        return ftd::interpreter::utils::e2(
+            format!(
+                "sql-execute only runs on POST, PUT, PATCH and DELETE requests, not on `{}`",
+                config.request.method()
+            ),
+            doc.name,
+            value.line_number(),
+        );
+    }
+
+    let db_config = match headers.get_optional_string_by_key("db", doc.name, value.line_number())? {
+        Some(url)
+            if fastn_core::google_sheets::extract_google_sheets_id(url.as_str()).is_some() =>
+        {
+            return ftd::interpreter::utils::e2(
+                "sql-execute can not write to google sheets",
+                doc.name,
+                value.line_number(),
+            );
+        }
+        Some(url) if super::data_files::is_data_files(url.as_str()) => {
+            return ftd::interpreter::utils::e2(
+                "sql-execute can not write to data files",
+                doc.name,
+                value.line_number(),
+            );
+        }
+        Some(url) => DatabaseConfig::new(url, "sqlite".to_string()),
+        None => fastn_core::library2022::processor::sql::get_db_config(&config.config.ds).await?,
+    };
+
+    let statements = split_statements(query.as_str());
+    let result = match db_config.db_type.as_str() {
+        "postgres" => {
+            fastn_core::library2022::processor::pg::execute(
+                &statements,
+                doc,
+                value.line_number(),
+                headers,
+                config,
+            )
+            .await
+        }
+        "sqlite" => {
+            fastn_core::library2022::processor::sqlite::execute(
+                &config.config.ds.root().join(&db_config.db_url),
+                &statements,
+                doc,
+                headers,
+                value.line_number(),
+            )
+            .await
+        }
+        "mysql" => {
+            fastn_core::library2022::processor::mysql::execute(
+                &db_config.db_url,
+                &statements,
+                doc,
+                headers,
+                value.line_number(),
+            )
+            .await
+        }
+        t => ftd::interpreter::utils::e2(
+            format!("sql-execute does not support `{}` databases", t),
+            doc.name,
+            value.line_number(),
+        ),
+    };
+
+    match result {
+        Ok(result) if kind.is_integer() && result.rows.is_empty() => {
+            Ok(ftd::interpreter::Value::Integer {
+                value: result.affected as i64,
+            })
+        }
+        Ok(result) => super::sqlite::result_to_value(Ok(result.rows), kind, doc, &value, STATUS_OK),
+        Err(e) => {
+            super::sqlite::result_to_value(Err(e.to_string()), kind, doc, &value, STATUS_ERROR)
+        }
+    }
+}
+
+/// What the statements run by `sql-execute` did.
+pub(crate) struct ExecuteResult {
+    /// rows inserted, updated or deleted by all statements
+    pub(crate) affected: usize,
+    /// rows returned by the last statement that returned any
+    pub(crate) rows: Rows,
+}
+
+/// Rows returned by a query, with the names of their columns, they are mapped to record fields
+/// by name, see `ftd::interpreter::TDoc::named_row_to_value()`.
+#[derive(Debug, Default)]
+pub(crate) struct Rows {
+    pub(crate) columns: Vec<String>,
+    pub(crate) rows: Vec<Vec<serde_json::Value>>,
+}
+
+impl Rows {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+}
+
+/// The type of a column returned by a query, as far as ftd kinds are concerned, see
+/// `describe()`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ColumnType {
+    Text,
+    Integer,
+    Decimal,
+    Boolean,
+    Json,
+    /// eg expressions in sqlite, which have no declared type
+    Unknown,
+}
+
+impl ColumnType {
+    /// Whether the values of this column convert to the ftd kind `kind`, eg `integer`. Kinds
+    /// other than `string`, `integer`, `decimal` and `boolean` are not checked.
+    pub(crate) fn converts_to(&self, kind: &str) -> bool {
+        match (kind, self) {
+            (_, ColumnType::Unknown) => true,
+            ("string", t) => matches!(t, ColumnType::Text | ColumnType::Json),
+            ("integer", t) => matches!(t, ColumnType::Integer),
+            ("decimal", t) => matches!(t, ColumnType::Decimal | ColumnType::Integer),
+            // sqlite and mysql store booleans as integers
+            ("boolean", t) => matches!(t, ColumnType::Boolean | ColumnType::Integer),
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Column {
+    pub(crate) name: String,
+    pub(crate) type_: ColumnType,
+}
+
+/// The columns `query` of the processor `processor`, eg `sql`, returns, by preparing it without
+/// running it, for `fastn check`. `None` if the database can not tell, eg a google sheet.
+pub(crate) async fn describe(
+    processor: &str,
+    headers: &ftd::ast::HeaderValues,
+    query: &str,
+    doc_name: &str,
+    line_number: usize,
+    req_config: &fastn_core::RequestConfig,
+) -> ftd::interpreter::Result<Option<Vec<Column>>> {
+    let ds = &req_config.config.ds;
+    let db_config = match processor {
+        "pg" => DatabaseConfig::new(String::new(), "postgres".to_string()),
+        "package-query" => match headers.get_optional_string_by_key("db", doc_name, line_number)? {
+            Some(db) => DatabaseConfig::new(db, "sqlite".to_string()),
+            None => {
+                return ftd::interpreter::utils::e2("`db` is not specified", doc_name, line_number)
+            }
+        },
+        _ => query_db_config(headers, doc_name, line_number, ds).await?,
+    };
+
+    Ok(Some(match db_config.db_type.as_str() {
+        "postgres" => super::pg::describe(query, doc_name, line_number, req_config).await?,
+        "sqlite" => super::sqlite::describe(
+            &ds.root().join(&db_config.db_url),
+            query,
+            doc_name,
+            line_number,
+        )?,
+        "mysql" => super::mysql::describe(&db_config.db_url, query, doc_name, line_number).await?,
+        _ => return Ok(None),
+    }))
+}
+
+fn is_write_request(request: &fastn_core::http::Request) -> bool {
+    ["POST", "PUT", "PATCH", "DELETE"]
+        .iter()
+        .any(|method| request.method().eq_ignore_ascii_case(method))
+}
+
+/// The statements of `query`, separated by `;` outside of quotes.
+pub(crate) fn split_statements(query: &str) -> Vec<String> {
+    let mut statements = vec![];
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for c in query.chars() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (_, BACKSLASH) => escaped = true,
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, ';') => {
+                statements.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    statements.push(current);
+
+    statements
+        .into_iter()
+        .map(|statement| statement.trim().to_string())
+        .filter(|statement| !statement.is_empty())
+        .collect()
+}
+
 pub const STATUS_OK: usize = 0;
 pub const STATUS_ERROR: usize = 1;
 const BACKSLASH: char = '\\';
@@ -177,6 +445,19 @@ mod test {
         assert_eq!(arguments, a);
     }
 
+    #[test]
+    fn split_statements() {
+        assert_eq!(
+            super::split_statements("INSERT INTO t VALUES ($a); DELETE FROM t;\n"),
+            vec!["INSERT INTO t VALUES ($a)", "DELETE FROM t"]
+        );
+        assert_eq!(
+            super::split_statements("UPDATE t SET a = 'x;y', b = \"z;\" WHERE c = 'it\\'s;'"),
+            vec!["UPDATE t SET a = 'x;y', b = \"z;\" WHERE c = 'it\\'s;'"]
+        );
+        assert!(super::split_statements(" ; ").is_empty());
+    }
+
     #[test]
     fn extract_arguments() {
         e("SELECT $val::FLOAT8;", "SELECT $1::FLOAT8;", vec!["val"]);
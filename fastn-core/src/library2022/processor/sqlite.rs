@@ -69,19 +69,22 @@ pub async fn process(
 }
 
 pub(crate) fn result_to_value(
-    result: Result<Vec<Vec<serde_json::Value>>, String>,
+    result: Result<super::sql::Rows, String>,
     kind: ftd::interpreter::Kind,
     doc: &ftd::interpreter::TDoc<'_>,
     value: &ftd::ast::VariableValue,
     status: usize,
 ) -> ftd::interpreter::Result<ftd::interpreter::Value> {
     match result {
-        Ok(result) => {
+        Ok(super::sql::Rows {
+            columns,
+            rows: result,
+        }) => {
             if kind.is_list() {
-                doc.rows_to_value(result.as_slice(), &kind, value)
+                doc.named_rows_to_value(&columns, result.as_slice(), &kind, value)
             } else {
                 match result.len() {
-                    1 => doc.row_to_value(&result[0], &kind, value),
+                    1 => doc.named_row_to_value(&columns, &result[0], &kind, value),
                     0 if kind.is_integer() => Ok(ftd::interpreter::Value::Integer {
                         value: status as i64,
                     }),
@@ -125,15 +128,34 @@ fn resolve_variable_from_doc(
         }
     };
 
-    let param_value: Box<dyn rusqlite::ToSql> = match thing {
-        ftd::interpreter::Value::String { text } => Box::new(text),
-        ftd::interpreter::Value::Integer { value } => Box::new(value as i32),
-        ftd::interpreter::Value::Decimal { value } => Box::new(value as f32),
-        ftd::interpreter::Value::Boolean { value } => Box::new(value as i32),
-        _ => unimplemented!(), // Handle other types as needed
-    };
+    value_to_sql(var, thing, doc, line_number)
+}
 
-    Ok(param_value)
+/// Binds ftd values with their own type, so `integer` and `decimal` variables are not
+/// truncated, and `optional` variables that are `NULL` bind as `NULL`.
+fn value_to_sql(
+    var: &str,
+    value: ftd::interpreter::Value,
+    doc: &ftd::interpreter::TDoc,
+    line_number: usize,
+) -> ftd::interpreter::Result<Box<dyn rusqlite::ToSql>> {
+    Ok(match value {
+        ftd::interpreter::Value::String { text } => Box::new(text),
+        ftd::interpreter::Value::Integer { value } => Box::new(value),
+        ftd::interpreter::Value::Decimal { value } => Box::new(value),
+        ftd::interpreter::Value::Boolean { value } => Box::new(value),
+        ftd::interpreter::Value::Optional { data, .. } => match *data {
+            Some(value) => value_to_sql(var, value, doc, line_number)?,
+            None => Box::new(rusqlite::types::Null),
+        },
+        v => {
+            return ftd::interpreter::utils::e2(
+                format!("${var} can not be passed to a query, it is a {v:?}"),
+                doc.name,
+                line_number,
+            )
+        }
+    })
 }
 
 fn resolve_variable_from_headers(
@@ -156,20 +178,69 @@ fn resolve_variable_from_headers(
         }
     }
 
-    let param_value: Box<dyn rusqlite::ToSql> = match (param_type, &header.value) {
-        ("TEXT", ftd::ast::VariableValue::String { value, .. }) => Box::new(value.clone()),
-        ("INTEGER", ftd::ast::VariableValue::String { value, .. }) => {
-            Box::new(value.parse::<i32>().unwrap())
+    let value = match &header.value {
+        ftd::ast::VariableValue::String { value, .. } => value,
+        v => {
+            return ftd::interpreter::utils::e2(
+                format!("${var} can not be passed to a query, it is a {v:?}"),
+                doc.name,
+                line_number,
+            )
         }
-        ("REAL", ftd::ast::VariableValue::String { value, .. }) => {
-            Box::new(value.parse::<f32>().unwrap())
+    };
+
+    let param_value: Box<dyn rusqlite::ToSql> = match param_type.to_uppercase().as_str() {
+        "" | "TEXT" => Box::new(value.clone()),
+        "INTEGER" => Box::new(parse_header::<i64>(
+            var,
+            value,
+            param_type,
+            doc,
+            line_number,
+        )?),
+        "REAL" => Box::new(parse_header::<f64>(
+            var,
+            value,
+            param_type,
+            doc,
+            line_number,
+        )?),
+        "BOOLEAN" => Box::new(parse_header::<bool>(
+            var,
+            value,
+            param_type,
+            doc,
+            line_number,
+        )?),
+        t => {
+            return ftd::interpreter::utils::e2(
+                format!("${var}: unknown type `{t}`, expected TEXT, INTEGER, REAL or BOOLEAN"),
+                doc.name,
+                line_number,
+            )
         }
-        _ => unimplemented!(), // Handle other types as needed
     };
 
     Ok(param_value)
 }
 
+fn parse_header<T: std::str::FromStr>(
+    var: &str,
+    value: &str,
+    param_type: &str,
+    doc: &ftd::interpreter::TDoc,
+    line_number: usize,
+) -> ftd::interpreter::Result<T> {
+    match value.parse::<T>() {
+        Ok(v) => Ok(v),
+        Err(_) => ftd::interpreter::utils::e2(
+            format!("${var}: `{value}` is not a valid {param_type}"),
+            doc.name,
+            line_number,
+        ),
+    }
+}
+
 fn resolve_param(
     param_name: &str,
     param_type: &str,
@@ -297,7 +368,7 @@ pub(crate) async fn execute_query(
     doc: &ftd::interpreter::TDoc<'_>,
     headers: ftd::ast::HeaderValues,
     line_number: usize,
-) -> ftd::interpreter::Result<Vec<Vec<serde_json::Value>>> {
+) -> ftd::interpreter::Result<super::sql::Rows> {
     let doc_name = doc.name;
 
     let conn = match rusqlite::Connection::open_with_flags(
@@ -314,6 +385,19 @@ pub(crate) async fn execute_query(
         }
     };
 
+    query_connection(&conn, query, doc, headers, line_number)
+}
+
+/// Runs `query` on `conn`, see `execute_query()`.
+pub(crate) fn query_connection(
+    conn: &rusqlite::Connection,
+    query: &str,
+    doc: &ftd::interpreter::TDoc<'_>,
+    headers: ftd::ast::HeaderValues,
+    line_number: usize,
+) -> ftd::interpreter::Result<super::sql::Rows> {
+    let doc_name = doc.name;
+
     let mut stmt = match conn.prepare(query) {
         Ok(v) => v,
         Err(e) => {
@@ -326,6 +410,7 @@ pub(crate) async fn execute_query(
     };
 
     let count = stmt.column_count();
+    let columns = column_names(&stmt);
 
     // let mut stmt = conn.prepare("SELECT * FROM test where name = :name")?;
     // let mut rows = stmt.query(rusqlite::named_params! { ":name": "one" })?
@@ -361,6 +446,164 @@ pub(crate) async fn execute_query(
             }
         }
     }
+    Ok(super::sql::Rows {
+        columns,
+        rows: result,
+    })
+}
+
+/// The columns `query` returns, see `sql::describe()`. The database is opened read only and
+/// the query is only prepared.
+pub(crate) fn describe(
+    database_path: &fastn_ds::Path,
+    query: &str,
+    doc_name: &str,
+    line_number: usize,
+) -> ftd::interpreter::Result<Vec<super::sql::Column>> {
+    let conn = match rusqlite::Connection::open_with_flags(
+        database_path.to_string(),
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+    ) {
+        Ok(conn) => conn,
+        Err(e) => {
+            return ftd::interpreter::utils::e2(
+                format!("Failed to open `{}`: {:?}", database_path, e),
+                doc_name,
+                line_number,
+            );
+        }
+    };
+
+    let stmt = match conn.prepare(query) {
+        Ok(v) => v,
+        Err(e) => {
+            return ftd::interpreter::utils::e2(
+                format!("Failed to prepare query: {:?}", e),
+                doc_name,
+                line_number,
+            )
+        }
+    };
+
+    Ok(stmt
+        .columns()
+        .iter()
+        .map(|column| super::sql::Column {
+            name: column.name().to_string(),
+            type_: column_type(column.decl_type()),
+        })
+        .collect())
+}
+
+/// Follows the type affinity rules of sqlite, <https://www.sqlite.org/datatype3.html>.
+fn column_type(decl_type: Option<&str>) -> super::sql::ColumnType {
+    let decl_type = match decl_type {
+        Some(t) => t.to_uppercase(),
+        None => return super::sql::ColumnType::Unknown,
+    };
+    if decl_type.contains("BOOL") {
+        super::sql::ColumnType::Boolean
+    } else if decl_type.contains("JSON") {
+        super::sql::ColumnType::Json
+    } else if decl_type.contains("INT") {
+        super::sql::ColumnType::Integer
+    } else if ["CHAR", "CLOB", "TEXT"]
+        .iter()
+        .any(|t| decl_type.contains(t))
+    {
+        super::sql::ColumnType::Text
+    } else if ["REAL", "FLOA", "DOUB"]
+        .iter()
+        .any(|t| decl_type.contains(t))
+    {
+        super::sql::ColumnType::Decimal
+    } else {
+        // NUMERIC affinity, values are stored as integers or reals
+        super::sql::ColumnType::Unknown
+    }
+}
+
+fn column_names(stmt: &rusqlite::Statement) -> Vec<String> {
+    stmt.column_names()
+        .into_iter()
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// Runs `statements` in a transaction, for `sql-execute`, nothing is written if one fails.
+pub(crate) async fn execute(
+    database_path: &fastn_ds::Path,
+    statements: &[String],
+    doc: &ftd::interpreter::TDoc<'_>,
+    headers: ftd::ast::HeaderValues,
+    line_number: usize,
+) -> ftd::interpreter::Result<super::sql::ExecuteResult> {
+    let doc_name = doc.name;
+    let failed = |message: String| -> ftd::interpreter::Result<super::sql::ExecuteResult> {
+        ftd::interpreter::utils::e2(message, doc_name, line_number)
+    };
+
+    let mut conn = match rusqlite::Connection::open_with_flags(
+        database_path.to_string(),
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE,
+    ) {
+        Ok(conn) => conn,
+        Err(e) => return failed(format!("Failed to open `{}`: {:?}", database_path, e)),
+    };
+
+    // Rolled back when dropped, unless committed
+    let transaction = match conn.transaction() {
+        Ok(transaction) => transaction,
+        Err(e) => return failed(format!("Failed to start transaction: {:?}", e)),
+    };
+
+    let mut result = super::sql::ExecuteResult {
+        affected: 0,
+        rows: Default::default(),
+    };
+    for statement in statements {
+        let mut stmt = match transaction.prepare(statement) {
+            Ok(v) => v,
+            Err(e) => return failed(format!("Failed to prepare query: {:?}", e)),
+        };
+        let params = extract_named_parameters(statement, doc, headers.clone(), line_number)?;
+
+        let count = stmt.column_count();
+        let columns = column_names(&stmt);
+        if count == 0 {
+            match stmt.execute(rusqlite::params_from_iter(params)) {
+                Ok(affected) => result.affected += affected,
+                Err(e) => return failed(format!("Failed to execute query: {:?}", e)),
+            }
+            continue;
+        }
+
+        // eg `INSERT ... RETURNING id`
+        let mut rows = match stmt.query(rusqlite::params_from_iter(params)) {
+            Ok(v) => v,
+            Err(e) => return failed(format!("Failed to execute query: {:?}", e)),
+        };
+        let mut returned = vec![];
+        loop {
+            match rows.next() {
+                Ok(None) => break,
+                Ok(Some(r)) => returned.push(row_to_json(r, count, doc_name, line_number)?),
+                Err(e) => return failed(format!("Failed to execute query: {:?}", e)),
+            }
+        }
+        result.affected += returned.len();
+        if !returned.is_empty() {
+            result.rows = super::sql::Rows {
+                columns,
+                rows: returned,
+            };
+        }
+    }
+
+    if let Err(e) = transaction.commit() {
+        return failed(format!("Failed to commit transaction: {:?}", e));
+    }
+
     Ok(result)
 }
 
@@ -0,0 +1,28 @@
+/// returns the active sessions of the logged in user, eg:
+///
+/// ```ftd
+/// -- import: fastn/processors as pr
+///
+/// -- pr.session list sessions:
+/// $processor$: pr.user-sessions
+/// ```
+///
+/// A session is revoked by posting `{"id": <id>}` to `/-/auth/revoke-session/`, and all of them
+/// by posting to `/-/auth/logout-everywhere/`. The list is empty if no one is logged in.
+pub async fn process(
+    value: ftd::ast::VariableValue,
+    kind: ftd::interpreter::Kind,
+    doc: &ftd::interpreter::TDoc<'_>,
+    req_config: &fastn_core::RequestConfig,
+) -> ftd::interpreter::Result<ftd::interpreter::Value> {
+    let sessions =
+        fastn_core::auth::session::user_sessions(&req_config.request, &req_config.config.ds)
+            .await
+            .map_err(|e| ftd::interpreter::Error::ParseError {
+                message: format!("failed to get the sessions of the user: {:?}", e),
+                doc_id: doc.name.to_string(),
+                line_number: value.line_number(),
+            })?;
+
+    doc.from_json(&sessions, &kind, &value)
+}
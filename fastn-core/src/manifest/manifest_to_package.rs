@@ -6,6 +6,8 @@ impl fastn_core::Manifest {
         ds: &fastn_ds::DocumentStore,
         main_package: &fastn_core::Package,
     ) -> fastn_core::Result<fastn_core::Package> {
+        self.mount_archive(ds, &package_root.join(package_name))
+            .await?;
         let mut package = fastn_core::Package::new(package_name);
         package
             .resolve(&package_root.join(package_name).join("FASTN.ftd"), ds)
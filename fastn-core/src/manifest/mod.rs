@@ -2,25 +2,71 @@ mod manifest_to_package;
 pub mod utils;
 
 pub const MANIFEST_FILE: &str = "manifest.json";
+/// Manifests of all published versions of a package, keyed by version. Packages that declare
+/// a `version` keep this file at their root, `fastn build` adds the current manifest to it.
+pub const VERSIONS_FILE: &str = "versions.json";
+pub const ARCHIVE_EXTENSION: &str = "zip";
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct Manifest {
     pub files: std::collections::BTreeMap<String, File>,
     pub zip_url: String,
     pub checksum: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
 }
 
+pub type Versions = std::collections::BTreeMap<String, Manifest>;
+
 impl Manifest {
     pub fn new(
         files: std::collections::BTreeMap<String, File>,
         zip_url: String,
         checksum: String,
+        version: Option<String>,
     ) -> Self {
         Manifest {
             files,
             zip_url,
             checksum,
+            version,
+        }
+    }
+
+    /// If the package was fetched as an archive (`.packages/<package-name>.zip`), mount it at
+    /// `package_root` so the package is read straight from the archive. Each file listed in the
+    /// manifest is verified against its checksum the first time it is read.
+    pub async fn mount_archive(
+        &self,
+        ds: &fastn_ds::DocumentStore,
+        package_root: &fastn_ds::Path,
+    ) -> fastn_core::Result<bool> {
+        let archive_path = archive_path(package_root);
+        if !ds.exists(&archive_path).await {
+            return Ok(false);
         }
+
+        let checksums = self
+            .files
+            .iter()
+            .map(|(name, file)| (name.to_string(), file.checksum.to_string()))
+            .collect();
+        let archive =
+            fastn_ds::backend::Zip::new(package_root, ds.read_content(&archive_path).await?)?
+                .with_checksums(checksums);
+        ds.mount(package_root, std::sync::Arc::new(archive));
+
+        Ok(true)
+    }
+}
+
+/// Path of the archive of the package stored at `package_root`, eg
+/// `.packages/fifthtry.github.io/doc-site.zip` for `.packages/fifthtry.github.io/doc-site`.
+pub fn archive_path(package_root: &fastn_ds::Path) -> fastn_ds::Path {
+    let name = package_root.file_name().unwrap_or_default();
+    match package_root.parent() {
+        Some(parent) => parent.join(format!("{}.{}", name, ARCHIVE_EXTENSION)),
+        None => fastn_ds::Path::new(format!("{}.{}", name, ARCHIVE_EXTENSION)),
     }
 }
 
@@ -45,7 +91,7 @@ pub async fn write_manifest_file(
     config: &fastn_core::Config,
     build_dir: &fastn_ds::Path,
     zip_url: Option<String>,
-) -> fastn_core::Result<()> {
+) -> fastn_core::Result<Manifest> {
     use sha2::digest::FixedOutput;
     use sha2::Digest;
 
@@ -98,7 +144,22 @@ pub async fn write_manifest_file(
 
     let checksum = format!("{:X}", hasher.finalize_fixed());
 
-    let manifest = fastn_core::Manifest::new(files, zip_url, checksum);
+    let version = match config.package.version {
+        Some(ref version) => match semver::Version::parse(version) {
+            Ok(v) => Some(v.to_string()),
+            Err(e) => {
+                return Err(fastn_core::error::Error::UsageError {
+                    message: format!(
+                        "Invalid version \"{}\" of package \"{}\": {}",
+                        version, &config.package.name, e
+                    ),
+                });
+            }
+        },
+        None => None,
+    };
+
+    let manifest = fastn_core::Manifest::new(files, zip_url, checksum, version);
 
     let mut serialized_manifest = serde_json::ser::to_vec_pretty(&manifest)?;
     // Append newline character
@@ -122,5 +183,37 @@ pub async fn write_manifest_file(
         start,
     );
 
+    Ok(manifest)
+}
+
+/// Writes `versions.json` to `build_dir` for packages with a version: the one at the package
+/// root, if any, with `manifest` added to it.
+pub async fn write_versions_file(
+    config: &fastn_core::Config,
+    build_dir: &fastn_ds::Path,
+    manifest: &Manifest,
+) -> fastn_core::Result<()> {
+    let version = match manifest.version {
+        Some(ref version) => version,
+        None => return Ok(()),
+    };
+
+    let versions_path = config.ds.root().join(VERSIONS_FILE);
+    let mut versions: Versions = if config.ds.exists(&versions_path).await {
+        serde_json::from_slice(&config.ds.read_content(&versions_path).await?)?
+    } else {
+        Default::default()
+    };
+    versions.insert(version.to_string(), manifest.clone());
+
+    let mut serialized_versions = serde_json::ser::to_vec_pretty(&versions)?;
+    // Append newline character
+    serialized_versions.push(b'\n');
+
+    config
+        .ds
+        .write_content(&build_dir.join(VERSIONS_FILE), serialized_versions)
+        .await?;
+
     Ok(())
 }
@@ -3,7 +3,8 @@
 #[derive(Debug, Clone)]
 pub struct Dependency {
     pub package: fastn_core::Package,
-    pub version: Option<String>,
+    /// Versions of the package this package works with, eg `>= 0.3, < 0.5`
+    pub version: Option<semver::VersionReq>,
     pub notes: Option<String>,
     pub alias: Option<String>,
     pub implements: Vec<String>,
@@ -83,13 +84,53 @@ pub(crate) struct DependencyTemp {
 
 impl DependencyTemp {
     pub(crate) fn into_dependency(self) -> fastn_core::Result<fastn_core::Dependency> {
+        // The version requirement can follow the package name, or its alias, in the caption:
+        // `-- fastn.dependency: foo.com/bar >= 0.3, < 0.5 as bar` or
+        // `-- fastn.dependency: foo.com/bar as bar >= 0.3, < 0.5`
         let (package_name, alias) = match self.name.as_str().split_once(" as ") {
-            Some((package, alias)) => (package, Some(alias.to_string())),
+            Some((package, alias)) => (package, Some(alias)),
             _ => (self.name.as_str(), None),
         };
+        let (package_name, package_version) = split_version(package_name);
+        let (alias, alias_version) = match alias.map(split_version) {
+            Some((alias, version)) => (Some(alias.to_string()), version),
+            None => (None, None),
+        };
+        let version = match (package_version, alias_version) {
+            (Some(_), Some(_)) => {
+                return Err(fastn_core::Error::PackageError {
+                    message: format!(
+                        "version of dependency {} is specified both before and after its alias",
+                        package_name
+                    ),
+                })
+            }
+            (version, None) | (None, version) => version,
+        };
+        let version = match (version, self.version.as_deref()) {
+            (Some(_), Some(_)) => {
+                return Err(fastn_core::Error::PackageError {
+                    message: format!(
+                        "version of dependency {} is specified both in caption and header",
+                        package_name
+                    ),
+                })
+            }
+            (Some(version), None) | (None, Some(version)) => {
+                Some(semver::VersionReq::parse(version).map_err(|e| {
+                    fastn_core::Error::PackageError {
+                        message: format!(
+                            "invalid version `{}` of dependency {}: {}",
+                            version, package_name, e
+                        ),
+                    }
+                })?)
+            }
+            (None, None) => None,
+        };
         Ok(fastn_core::Dependency {
             package: fastn_core::Package::new(package_name),
-            version: self.version,
+            version,
             notes: self.notes,
             alias,
             implements: self.implements,
@@ -99,6 +140,14 @@ impl DependencyTemp {
     }
 }
 
+/// `foo.com/bar >= 0.3, < 0.5` -> `("foo.com/bar", Some(">= 0.3, < 0.5"))`
+fn split_version(name: &str) -> (&str, Option<&str>) {
+    match name.trim().split_once(char::is_whitespace) {
+        Some((name, version)) => (name, Some(version.trim())),
+        None => (name.trim(), None),
+    }
+}
+
 impl fastn_core::Package {
     /*    /// `process()` checks the package exists in `.packages` or `fastn_HOME` folder (`fastn_HOME` not
     /// yet implemented), and if not downloads and unpacks the method.
@@ -740,3 +789,86 @@ impl fastn_core::Package {
         Ok(())
     }*/
 }
+
+#[cfg(test)]
+mod test {
+    fn dependency(
+        name: &str,
+        version: Option<&str>,
+    ) -> fastn_core::Result<(String, Option<String>, Option<String>)> {
+        super::DependencyTemp {
+            name: name.to_string(),
+            version: version.map(str::to_string),
+            notes: None,
+            implements: vec![],
+            provided_via: None,
+            required_as: None,
+        }
+        .into_dependency()
+        .map(|d| {
+            (
+                d.package.name,
+                d.alias,
+                d.version.map(|version| version.to_string()),
+            )
+        })
+    }
+
+    fn ok(
+        name: &str,
+        alias: Option<&str>,
+        version: Option<&str>,
+    ) -> (String, Option<String>, Option<String>) {
+        (
+            name.to_string(),
+            alias.map(str::to_string),
+            version.map(str::to_string),
+        )
+    }
+
+    #[test]
+    fn into_dependency() {
+        assert_eq!(
+            dependency("foo.com/bar", None).unwrap(),
+            ok("foo.com/bar", None, None)
+        );
+        assert_eq!(
+            dependency("foo.com/bar >= 0.3, < 0.5", None).unwrap(),
+            ok("foo.com/bar", None, Some(">=0.3, <0.5"))
+        );
+        assert_eq!(
+            dependency("foo.com/bar as bar", Some("^0.3")).unwrap(),
+            ok("foo.com/bar", Some("bar"), Some("^0.3"))
+        );
+        assert_eq!(
+            dependency("foo.com/bar >= 0.3 as bar", None).unwrap(),
+            ok("foo.com/bar", Some("bar"), Some(">=0.3"))
+        );
+        assert_eq!(
+            dependency("foo.com/bar as bar >= 0.3", None).unwrap(),
+            ok("foo.com/bar", Some("bar"), Some(">=0.3"))
+        );
+    }
+
+    #[test]
+    fn into_dependency_errors() {
+        assert_eq!(
+            dependency("foo.com/bar ^0.3 as bar ^0.4", None)
+                .unwrap_err()
+                .to_string(),
+            "PackageError: version of dependency foo.com/bar is specified both before and after \
+            its alias"
+        );
+        assert_eq!(
+            dependency("foo.com/bar as bar ^0.3", Some("^0.4"))
+                .unwrap_err()
+                .to_string(),
+            "PackageError: version of dependency foo.com/bar is specified both in caption and \
+            header"
+        );
+        assert!(dependency("foo.com/bar as bar latest", None)
+            .unwrap_err()
+            .to_string()
+            .contains("invalid version `latest` of dependency foo.com/bar"));
+    }
+}
@@ -10,6 +10,8 @@ pub struct Package {
     /// The `versioned` stores the boolean value storing of the fastn package is versioned or not
     pub files: Vec<String>,
     pub versioned: bool,
+    /// Semantic version of the package, published in its `manifest.json`.
+    pub version: Option<String>,
     pub translation_of: Box<Option<Package>>,
     pub translations: Vec<Package>,
     pub requested_language: Option<String>,
@@ -78,6 +80,7 @@ impl Package {
             name: name.to_string(),
             files: vec![],
             versioned: false,
+            version: None,
             translation_of: Box::new(None),
             translations: vec![],
             requested_language: None,
@@ -961,6 +964,7 @@ impl PackageTempIntoPackage for fastn_package::old_fastn::PackageTemp {
             name: self.name.clone(),
             files: vec![],
             versioned: self.versioned,
+            version: self.version,
             translation_of: Box::new(translation_of),
             translations,
             requested_language: None,
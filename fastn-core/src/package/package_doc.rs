@@ -546,6 +546,22 @@ pub(crate) async fn read_ftd_2023(
             EMPTY_HTML_BODY.to_string()
         };
 
+        // Ids of the documents the page is rendered from, live reload only reloads the page
+        // when one of them changes
+        let root = c.ds.root();
+        let documents = std::iter::once(main.parent_path.join(main.id.as_str()))
+            .chain(
+                config
+                    .files_during_render
+                    .keys()
+                    .map(|path| fastn_ds::Path::new(path.as_str())),
+            )
+            .map(|path| match path.strip_prefix(&root) {
+                Some(relative) => relative.to_string(),
+                None => path.to_string(),
+            })
+            .collect::<Vec<_>>();
+
         fastn_core::utils::replace_markers_2023(
             js_document_script.as_str(),
             js_ast_data.scripts.join("").as_str(),
@@ -553,6 +569,7 @@ pub(crate) async fn read_ftd_2023(
             config.config.get_font_style().as_str(),
             ftd::ftd_js_css(),
             base_url,
+            documents.as_slice(),
             c,
         )
         .await
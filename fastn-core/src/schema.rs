@@ -1,21 +1,112 @@
-// @generated automatically by Diesel CLI.
+// Generated by Diesel CLI, and edited so the same schema works on postgres and sqlite, see
+// `sql_types`. The sqlite tables are in `migrations-sqlite`.
 
 pub mod sql_types {
+    /// `citext` on postgres, `TEXT COLLATE NOCASE` on sqlite
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "citext"))]
+    #[diesel(sqlite_type(name = "Text"))]
     pub struct Citext;
+
+    /// `TIMESTAMP WITH TIME ZONE` on postgres, text on sqlite, `chrono::DateTime<chrono::Utc>`
+    /// in rust
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(oid = 1184, array_oid = 1185))]
+    #[diesel(sqlite_type(name = "Text"))]
+    pub struct Timestamptz;
+
+    macro_rules! timestamptz_as_expression {
+        ($($sql_type:ty),*) => {$(
+            impl diesel::expression::AsExpression<$sql_type> for chrono::DateTime<chrono::Utc> {
+                type Expression = diesel::internal::derives::as_expression::Bound<$sql_type, Self>;
+
+                fn as_expression(self) -> Self::Expression {
+                    diesel::internal::derives::as_expression::Bound::new(self)
+                }
+            }
+
+            impl<'a> diesel::expression::AsExpression<$sql_type>
+                for &'a chrono::DateTime<chrono::Utc>
+            {
+                type Expression = diesel::internal::derives::as_expression::Bound<$sql_type, Self>;
+
+                fn as_expression(self) -> Self::Expression {
+                    diesel::internal::derives::as_expression::Bound::new(self)
+                }
+            }
+        )*};
+    }
+
+    timestamptz_as_expression!(Timestamptz, diesel::sql_types::Nullable<Timestamptz>);
+
+    impl diesel::serialize::ToSql<Timestamptz, diesel::pg::Pg> for chrono::DateTime<chrono::Utc> {
+        fn to_sql<'b>(
+            &'b self,
+            out: &mut diesel::serialize::Output<'b, '_, diesel::pg::Pg>,
+        ) -> diesel::serialize::Result {
+            diesel::serialize::ToSql::<diesel::sql_types::Timestamptz, diesel::pg::Pg>::to_sql(
+                self, out,
+            )
+        }
+    }
+
+    impl diesel::deserialize::FromSql<Timestamptz, diesel::pg::Pg> for chrono::DateTime<chrono::Utc> {
+        fn from_sql(
+            bytes: <diesel::pg::Pg as diesel::backend::Backend>::RawValue<'_>,
+        ) -> diesel::deserialize::Result<Self> {
+            diesel::deserialize::FromSql::<diesel::sql_types::Timestamptz, diesel::pg::Pg>::from_sql(
+                bytes,
+            )
+        }
+    }
+
+    impl diesel::serialize::ToSql<Timestamptz, diesel::sqlite::Sqlite>
+        for chrono::DateTime<chrono::Utc>
+    {
+        fn to_sql<'b>(
+            &'b self,
+            out: &mut diesel::serialize::Output<'b, '_, diesel::sqlite::Sqlite>,
+        ) -> diesel::serialize::Result {
+            diesel::serialize::ToSql::<
+                diesel::sql_types::TimestamptzSqlite,
+                diesel::sqlite::Sqlite,
+            >::to_sql(self, out)
+        }
+    }
+
+    impl diesel::deserialize::FromSql<Timestamptz, diesel::sqlite::Sqlite>
+        for chrono::DateTime<chrono::Utc>
+    {
+        fn from_sql(
+            bytes: <diesel::sqlite::Sqlite as diesel::backend::Backend>::RawValue<'_>,
+        ) -> diesel::deserialize::Result<Self> {
+            diesel::deserialize::FromSql::<
+                diesel::sql_types::TimestamptzSqlite,
+                diesel::sqlite::Sqlite,
+            >::from_sql(bytes)
+        }
+    }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Timestamptz;
+
     fastn_auth_session (id) {
         id -> Int8,
         user_id -> Int8,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        expires_at -> Nullable<Timestamptz>,
+        revoked_at -> Nullable<Timestamptz>,
+        user_agent -> Nullable<Text>,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Timestamptz;
+
     fastn_email_confirmation (id) {
         id -> Int8,
         email_id -> Int8,
@@ -27,6 +118,22 @@ diesel::table! {
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Timestamptz;
+
+    fastn_login_link (id) {
+        id -> Int8,
+        user_id -> Int8,
+        created_at -> Timestamptz,
+        sent_at -> Timestamptz,
+        key -> Text,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Timestamptz;
+
     fastn_oauthtoken (id) {
         id -> Int8,
         session_id -> Int8,
@@ -34,10 +141,14 @@ diesel::table! {
         provider -> Text,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        claims -> Nullable<Text>,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Timestamptz;
+
     fastn_password_reset (id) {
         id -> Int8,
         user_id -> Int8,
@@ -49,7 +160,7 @@ diesel::table! {
 
 diesel::table! {
     use diesel::sql_types::*;
-    use super::sql_types::Citext;
+    use super::sql_types::{Citext, Timestamptz};
 
     fastn_user (id) {
         id -> Int8,
@@ -60,12 +171,15 @@ diesel::table! {
         name -> Text,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        totp_secret -> Nullable<Text>,
+        totp_enabled_at -> Nullable<Timestamptz>,
+        totp_last_step -> Nullable<Int8>,
     }
 }
 
 diesel::table! {
     use diesel::sql_types::*;
-    use super::sql_types::Citext;
+    use super::sql_types::{Citext, Timestamptz};
 
     fastn_user_email (id) {
         id -> Int8,
@@ -78,18 +192,35 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Timestamptz;
+
+    fastn_user_recovery_code (id) {
+        id -> Int8,
+        user_id -> Int8,
+        code -> Text,
+        used_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::joinable!(fastn_auth_session -> fastn_user (user_id));
 diesel::joinable!(fastn_email_confirmation -> fastn_auth_session (session_id));
 diesel::joinable!(fastn_email_confirmation -> fastn_user_email (email_id));
+diesel::joinable!(fastn_login_link -> fastn_user (user_id));
 diesel::joinable!(fastn_oauthtoken -> fastn_auth_session (session_id));
 diesel::joinable!(fastn_password_reset -> fastn_user (user_id));
 diesel::joinable!(fastn_user_email -> fastn_user (user_id));
+diesel::joinable!(fastn_user_recovery_code -> fastn_user (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     fastn_auth_session,
     fastn_email_confirmation,
+    fastn_login_link,
     fastn_oauthtoken,
     fastn_password_reset,
     fastn_user,
     fastn_user_email,
+    fastn_user_recovery_code,
 );
@@ -722,6 +722,7 @@ pub fn get_fastn_package_data(package: &fastn_core::Package) -> String {
     )
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn replace_markers_2023(
     js_script: &str,
     scripts: &str,
@@ -729,6 +730,7 @@ pub async fn replace_markers_2023(
     font_style: &str,
     default_css: &str,
     base_url: &str,
+    documents: &[String],
     config: &fastn_core::Config,
 ) -> String {
     format!(
@@ -764,20 +766,40 @@ pub async fn replace_markers_2023(
             scripts,
         )
         .as_str(),
-        extra_js = get_extra_js(
-            config,
-            config.ftd_external_js.as_slice(),
-            config.ftd_inline_js.as_slice(),
-            "",
-            "",
+        extra_js = format!(
+            "{}{}",
+            get_extra_js(
+                config,
+                config.ftd_external_js.as_slice(),
+                config.ftd_inline_js.as_slice(),
+                "",
+                "",
+            )
+            .await,
+            live_reload_script(documents)
         )
-        .await
         .as_str(),
         default_css = default_css,
         html_body = format!("{}{}", ssr_body, font_style).as_str(),
     )
 }
 
+/// The live reload client, only while `fastn serve` watches the package. The page reloads when
+/// one of `documents`, the ids of the documents it is rendered from, changes.
+fn live_reload_script(documents: &[String]) -> String {
+    if !fastn_core::watcher::is_running() {
+        return "".to_string();
+    }
+    format!(
+        "<script>let __fastn_live_reload_documents__ = {};\n{}</script>",
+        // a `</script>` in a document id would end the script
+        serde_json::to_string(documents)
+            .unwrap_or_else(|_| "[]".to_string())
+            .replace('<', "\\u003c"),
+        fastn_js::live_reload_js()
+    )
+}
+
 pub fn is_test() -> bool {
     cfg!(test) || std::env::args().any(|e| e == "--test")
 }
@@ -916,13 +938,7 @@ pub fn query(uri: &str) -> fastn_core::Result<Vec<(String, String)>> {
             .collect_vec(),
     )
 }
-pub fn generate_hash(content: impl AsRef<[u8]>) -> String {
-    use sha2::digest::FixedOutput;
-    use sha2::Digest;
-    let mut hasher = sha2::Sha256::new();
-    hasher.update(content);
-    format!("{:X}", hasher.finalize_fixed())
-}
+pub use fastn_ds::generate_hash;
 
 static CSS_HASH: once_cell::sync::Lazy<String> =
     once_cell::sync::Lazy::new(|| format!("default-{}.css", generate_hash(ftd::css())));
@@ -1059,6 +1075,32 @@ impl diesel::deserialize::FromSql<fastn_core::schema::sql_types::Citext, diesel:
     }
 }
 
+impl diesel::serialize::ToSql<fastn_core::schema::sql_types::Citext, diesel::sqlite::Sqlite>
+    for CiString
+{
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut diesel::serialize::Output<'b, '_, diesel::sqlite::Sqlite>,
+    ) -> diesel::serialize::Result {
+        diesel::serialize::ToSql::<diesel::sql_types::Text, diesel::sqlite::Sqlite>::to_sql(
+            &self.0, out,
+        )
+    }
+}
+
+impl diesel::deserialize::FromSql<fastn_core::schema::sql_types::Citext, diesel::sqlite::Sqlite>
+    for CiString
+{
+    fn from_sql(
+        bytes: <diesel::sqlite::Sqlite as diesel::backend::Backend>::RawValue<'_>,
+    ) -> diesel::deserialize::Result<Self> {
+        Ok(CiString(diesel::deserialize::FromSql::<
+            diesel::sql_types::Text,
+            diesel::sqlite::Sqlite,
+        >::from_sql(bytes)?))
+    }
+}
+
 pub(crate) fn is_static_path(path: &str) -> bool {
     assert!(path.starts_with('/'));
 
@@ -1,50 +1,92 @@
-pub type WatcherSender = (usize, tokio::sync::mpsc::Sender<()>);
-static WATCHER: once_cell::sync::Lazy<(
-    tokio::sync::mpsc::Sender<WatcherSender>,
-    tokio::sync::mpsc::Sender<usize>,
-)> = once_cell::sync::Lazy::new(watcher);
-const POLL_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(30 * 1000); // 30 seconds
-static GLOBAL_POLL_COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
-
-fn watcher() -> (
-    tokio::sync::mpsc::Sender<WatcherSender>,
-    tokio::sync::mpsc::Sender<usize>,
-) {
-    let (tx, mut rx) = tokio::sync::mpsc::channel::<WatcherSender>(32);
-    let (g_tx, mut g_rx) = tokio::sync::mpsc::channel::<usize>(32);
-    let (f_tx, mut f_rx) = tokio::sync::mpsc::channel::<()>(32);
+/// What `/-/live-reload/` tells the browser.
+#[derive(serde::Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub(crate) enum Event {
+    /// ids of the changed documents, eg `index.ftd`
+    FileChanged { documents: Vec<String> },
+    /// Only stylesheets changed, they can be swapped without reloading the page
+    CssChanged { files: Vec<String> },
+    /// The page at `url` failed to render
+    BuildError {
+        url: String,
+        document: Option<String>,
+        line_number: Option<usize>,
+        message: String,
+    },
+}
+
+static EVENTS: once_cell::sync::Lazy<tokio::sync::broadcast::Sender<Event>> =
+    once_cell::sync::Lazy::new(|| tokio::sync::broadcast::channel(32).0);
+static WATCHER: once_cell::sync::OnceCell<()> = once_cell::sync::OnceCell::new();
+/// Editors often write a file in several steps (truncate, write, rename), and save several
+/// files at once. Changes are only reported once no file has changed for this long.
+const DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(100);
+/// Proxies close idle connections, a comment is sent on an idle stream this often.
+const KEEP_ALIVE: std::time::Duration = std::time::Duration::from_secs(15);
+/// How long a `/-/poll/` request waits for a change.
+const POLL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
 
+/// Starts watching the files of the package served by `config`. Only `fastn serve` does this,
+/// `/-/live-reload/` never reports any change otherwise.
+pub(crate) fn start(config: &fastn_core::Config) {
     if fastn_core::utils::is_test() {
         // we do not want to run the watcher in tests
-        return (tx, g_tx);
+        return;
     }
 
+    let root = config.ds.root();
+    let ignored_paths = config.ignored_paths(&config.package);
+    WATCHER.get_or_init(|| watcher(root, ignored_paths));
+}
+
+/// Whether pages should include the live reload client, see `start()`.
+pub(crate) fn is_running() -> bool {
+    WATCHER.get().is_some()
+}
+
+/// Tells the browsers showing `url` that it failed to render, with the location of `error` if
+/// it is an ftd error.
+pub(crate) fn build_error(url: &str, error: &fastn_core::Error) {
+    let location = error_location(error);
+    broadcast(Event::BuildError {
+        url: format!("/{}", url.trim_start_matches('/')),
+        document: location.as_ref().map(|(document, _)| document.to_string()),
+        line_number: location.map(|(_, line_number)| line_number),
+        message: error.to_string(),
+    });
+}
+
+fn broadcast(event: Event) {
+    // Sending only fails when no browser is listening
+    let _ = EVENTS.send(event);
+}
+
+fn watcher(root: fastn_ds::Path, ignored_paths: Vec<String>) {
+    let (f_tx, mut f_rx) = tokio::sync::mpsc::channel::<Vec<String>>(32);
+
     tokio::spawn(async move {
-        let _watcher = create_watcher(f_tx); // watcher only works as long as it is not dropped
-        let mut polls: std::collections::HashMap<usize, tokio::sync::mpsc::Sender<()>> =
-            Default::default();
+        // watcher only works as long as it is not dropped
+        let _watcher = create_watcher(&root, &ignored_paths, f_tx);
+        let mut changed: std::collections::BTreeSet<String> = Default::default();
+        let debounce = tokio::time::sleep(DEBOUNCE);
+        tokio::pin!(debounce);
 
         loop {
             tokio::select! {
-                Some((id, v)) = rx.recv() => {
-                    // a new http poll request has joined, lets add them to pending watchers
-                    polls.insert(id, v);
-                    println!("enqueued new poll request");
-                }
-                Some(id) = g_rx.recv() => {
-                    // a poll request has been completed, lets remove it from pending watchers
-                    polls.remove(&id);
-                    println!("removed poll request");
+                Some(ids) = f_rx.recv() => {
+                    // some documents have changed, wait for things to settle before telling
+                    // the browsers
+                    changed.extend(ids);
+                    debounce.as_mut().reset(tokio::time::Instant::now() + DEBOUNCE);
                 }
-                Some(()) = f_rx.recv() => {
-                    // some file event has happened, lets inform all pending watchers
-                    println!("file event, informing {} pending polls", polls.len());
-                    for p in polls.values() {
-                        if let Err(e) = p.send(()).await {
-                            eprintln!("watcher: failed to send signal: {}", e);
-                        }
+                () = &mut debounce, if !changed.is_empty() => {
+                    let ids = std::mem::take(&mut changed).into_iter().collect::<Vec<_>>();
+                    println!("{} documents changed", ids.len());
+                    if ids.iter().all(|id| id.ends_with(".css")) {
+                        broadcast(Event::CssChanged { files: ids });
+                    } else {
+                        broadcast(Event::FileChanged { documents: ids });
                     }
-                    polls.clear();
                 }
                 else => {
                     println!("watcher: exiting");
@@ -53,44 +95,232 @@ fn watcher() -> (
             }
         }
     });
-
-    (tx, g_tx)
 }
 
-fn create_watcher(f_tx: tokio::sync::mpsc::Sender<()>) -> notify::RecommendedWatcher {
+fn create_watcher(
+    root: &fastn_ds::Path,
+    ignored_paths: &[String],
+    f_tx: tokio::sync::mpsc::Sender<Vec<String>>,
+) -> Option<notify::RecommendedWatcher> {
     use notify::Watcher;
 
-    let mut watcher = notify::recommended_watcher(move |_res| {
-        if let Err(e) = f_tx.blocking_send(()) {
-            eprintln!("watcher: failed to send signal: {}", e);
+    let root_path = std::path::PathBuf::from(root.to_string());
+    let overrides = match ignored_overrides(&root_path, ignored_paths) {
+        Ok(overrides) => overrides,
+        Err(e) => {
+            eprintln!("watcher: invalid ignored paths: {}", e);
+            return None;
         }
-    })
-    .expect("watcher: failed to create watcher");
+    };
 
-    watcher
-        .watch(
-            &std::path::PathBuf::from(""), // TODO: how to get the root path?
-            notify::RecursiveMode::Recursive,
-        )
-        .expect("watcher: failed to watch");
+    let watch_root = root_path.clone();
+    let mut watcher =
+        match notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let event = match res {
+                Ok(event) => event,
+                Err(e) => {
+                    eprintln!("watcher: {}", e);
+                    return;
+                }
+            };
+            if event.kind.is_access() {
+                return;
+            }
 
-    watcher
+            let ids = event
+                .paths
+                .iter()
+                .filter_map(|path| document_id(&watch_root, &overrides, path))
+                .collect::<Vec<_>>();
+            if ids.is_empty() {
+                return;
+            }
+            if let Err(e) = f_tx.blocking_send(ids) {
+                eprintln!("watcher: failed to send signal: {}", e);
+            }
+        }) {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                eprintln!("watcher: failed to create watcher: {}", e);
+                return None;
+            }
+        };
+
+    if let Err(e) = watcher.watch(&root_path, notify::RecursiveMode::Recursive) {
+        eprintln!("watcher: failed to watch {}: {}", root, e);
+        return None;
+    }
+
+    Some(watcher)
 }
 
-fn next_id() -> usize {
-    GLOBAL_POLL_COUNT.fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+fn ignored_overrides(
+    root: &std::path::Path,
+    ignored_paths: &[String],
+) -> Result<ignore::overrides::Override, ignore::Error> {
+    let mut overrides = ignore::overrides::OverrideBuilder::new(root);
+    for ignored_path in ignored_paths {
+        overrides.add(format!("!{}", ignored_path).as_str())?;
+    }
+    overrides.build()
 }
 
-pub async fn poll() -> fastn_core::Result<fastn_core::http::Response> {
-    let id = next_id();
-    let (tx, mut rx) = tokio::sync::mpsc::channel::<()>(32);
+/// The id of the document at `path`, eg `blog/index.ftd`, `None` for paths outside the package,
+/// hidden files and ignored paths, the same files `Config::get_files()` leaves out.
+fn document_id(
+    root: &std::path::Path,
+    overrides: &ignore::overrides::Override,
+    path: &std::path::Path,
+) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let components = relative
+        .components()
+        .map(|c| c.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()?;
+    let mut current = root.to_path_buf();
+    for (index, component) in components.iter().enumerate() {
+        current.push(component);
+        let is_dir = index + 1 < components.len();
+        if component.starts_with('.') || overrides.matched(&current, is_dir).is_ignore() {
+            return None;
+        }
+    }
+
+    if components.is_empty() {
+        return None;
+    }
+    Some(components.join("/"))
+}
 
-    WATCHER.0.send((id, tx)).await?;
+/// `(document id, line number)` of the ftd error, if `error` is one.
+fn error_location(error: &fastn_core::Error) -> Option<(&str, usize)> {
+    match error {
+        fastn_core::Error::FTDP1Error(e) => p1_error_location(e),
+        fastn_core::Error::FTDInterpreterError(e) => interpreter_error_location(e),
+        _ => None,
+    }
+}
+
+fn interpreter_error_location(error: &ftd::interpreter::Error) -> Option<(&str, usize)> {
+    match error {
+        ftd::interpreter::Error::InvalidKind {
+            doc_id,
+            line_number,
+            ..
+        }
+        | ftd::interpreter::Error::ValueNotFound {
+            doc_id,
+            line_number,
+            ..
+        }
+        | ftd::interpreter::Error::ParseError {
+            doc_id,
+            line_number,
+            ..
+        } => Some((doc_id, *line_number)),
+        ftd::interpreter::Error::P1Error(e) => p1_error_location(e),
+        ftd::interpreter::Error::ASTError(ftd::ast::Error::Parse {
+            doc_id,
+            line_number,
+            ..
+        }) => Some((doc_id, *line_number)),
+        ftd::interpreter::Error::ASTError(ftd::ast::Error::P1(e)) => p1_error_location(e),
+        _ => None,
+    }
+}
 
-    let got_something = tokio::time::timeout(POLL_TIMEOUT, rx.recv()).await.is_ok();
-    if !got_something {
-        WATCHER.1.send(id).await?;
+fn p1_error_location(error: &ftd::p1::Error) -> Option<(&str, usize)> {
+    match error {
+        ftd::p1::Error::SectionNotFound {
+            doc_id,
+            line_number,
+        }
+        | ftd::p1::Error::MoreThanOneCaption {
+            doc_id,
+            line_number,
+        }
+        | ftd::p1::Error::ParseError {
+            doc_id,
+            line_number,
+            ..
+        }
+        | ftd::p1::Error::MoreThanOneHeader {
+            doc_id,
+            line_number,
+            ..
+        }
+        | ftd::p1::Error::HeaderNotFound {
+            doc_id,
+            line_number,
+            ..
+        } => Some((doc_id, *line_number)),
+        _ => None,
     }
+}
+
+#[derive(serde::Serialize)]
+struct PollResponse {
+    changed: bool,
+    /// ids of the changed documents, eg `index.ftd`
+    documents: Vec<String>,
+}
+
+/// `/-/poll/`: waits for documents to change, for at most `POLL_TIMEOUT`.
+///
+/// Deprecated in favour of `/-/live-reload/`, only kept for pages served by older versions of
+/// fastn that still poll.
+pub async fn poll() -> fastn_core::Result<fastn_core::http::Response> {
+    static DEPRECATION_WARNING: std::sync::Once = std::sync::Once::new();
+    DEPRECATION_WARNING.call_once(|| {
+        fastn_core::warning!("/-/poll/ is deprecated, use the /-/live-reload/ event stream instead")
+    });
+
+    let mut rx = EVENTS.subscribe();
+    let deadline = tokio::time::Instant::now() + POLL_TIMEOUT;
+    let documents = loop {
+        match tokio::time::timeout_at(deadline, rx.recv()).await {
+            Ok(Ok(Event::FileChanged { documents })) => break documents,
+            Ok(Ok(Event::CssChanged { files })) => break files,
+            Ok(Ok(Event::BuildError { .. }))
+            | Ok(Err(tokio::sync::broadcast::error::RecvError::Lagged(_))) => continue,
+            Ok(Err(tokio::sync::broadcast::error::RecvError::Closed)) | Err(_) => break vec![],
+        }
+    };
+
+    Ok(fastn_core::http::api_ok(PollResponse {
+        changed: !documents.is_empty(),
+        documents,
+    })?)
+}
+
+/// `/-/live-reload/`: a server-sent events stream of `Event`s, as JSON.
+pub async fn live_reload() -> fastn_core::Result<fastn_core::http::Response> {
+    let rx = EVENTS.subscribe();
+
+    let stream = futures::stream::unfold(rx, |mut rx| async move {
+        let message = match tokio::time::timeout(KEEP_ALIVE, rx.recv()).await {
+            Ok(Ok(event)) => match serde_json::to_string(&event) {
+                Ok(data) => format!("data: {}\n\n", data),
+                Err(e) => {
+                    eprintln!("live-reload: failed to serialize event: {}", e);
+                    return None;
+                }
+            },
+            // The browser missed some events, it gets the next ones
+            Ok(Err(tokio::sync::broadcast::error::RecvError::Lagged(_))) => ":\n\n".to_string(),
+            Ok(Err(tokio::sync::broadcast::error::RecvError::Closed)) => return None,
+            Err(_) => ": keep-alive\n\n".to_string(),
+        };
+        Some((
+            Ok::<_, actix_web::Error>(actix_web::web::Bytes::from(message)),
+            rx,
+        ))
+    });
 
-    Ok(fastn_core::http::api_ok(got_something)?)
+    Ok(actix_web::HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((actix_web::http::header::CACHE_CONTROL, "no-cache"))
+        // Compressing would buffer the events
+        .insert_header((actix_web::http::header::CONTENT_ENCODING, "identity"))
+        .streaming(stream))
 }
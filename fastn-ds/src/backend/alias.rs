@@ -0,0 +1,228 @@
+/// Serves the directory `target` of another backend at `at`, eg the vendored packages in
+/// `vendor` at `.packages`.
+#[derive(Debug)]
+pub struct Alias {
+    at: fastn_ds::Path,
+    target: fastn_ds::Path,
+    inner: std::sync::Arc<dyn fastn_ds::Backend>,
+}
+
+impl Alias {
+    pub fn new(
+        at: &fastn_ds::Path,
+        target: &fastn_ds::Path,
+        inner: std::sync::Arc<dyn fastn_ds::Backend>,
+    ) -> Self {
+        Alias {
+            at: at.clone(),
+            target: target.clone(),
+            inner,
+        }
+    }
+
+    /// Path in the `inner` backend of `path`, `None` if `path` is not under `at`.
+    fn resolve(&self, path: &fastn_ds::Path) -> Option<fastn_ds::Path> {
+        let relative = path.strip_prefix(&self.at)?;
+        Some(match relative.path.as_str() {
+            "" => self.target.clone(),
+            relative => self.target.join(relative),
+        })
+    }
+
+    fn resolve_or_not_found(&self, path: &fastn_ds::Path) -> std::io::Result<fastn_ds::Path> {
+        self.resolve(path)
+            .ok_or_else(|| std::io::ErrorKind::NotFound.into())
+    }
+
+    /// Inverse of `resolve()`.
+    fn unresolve(&self, path: fastn_ds::Path) -> fastn_ds::Path {
+        match path.strip_prefix(&self.target) {
+            Some(relative) if !relative.path.as_str().is_empty() => self.at.join(relative.path),
+            Some(_) => self.at.clone(),
+            None => path,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl fastn_ds::Backend for Alias {
+    async fn read_content(&self, path: &fastn_ds::Path) -> Result<Vec<u8>, fastn_ds::ReadError> {
+        let path = self.resolve(path).ok_or(fastn_ds::ReadError::NotFound)?;
+        self.inner.read_content(&path).await
+    }
+
+    async fn write_content(
+        &self,
+        path: &fastn_ds::Path,
+        data: Vec<u8>,
+    ) -> Result<(), fastn_ds::WriteError> {
+        let path = self.resolve_or_not_found(path)?;
+        self.inner.write_content(&path, data).await
+    }
+
+    async fn copy(
+        &self,
+        from: &fastn_ds::Path,
+        to: &fastn_ds::Path,
+    ) -> Result<(), fastn_ds::WriteError> {
+        let from = self.resolve_or_not_found(from)?;
+        let to = self.resolve_or_not_found(to)?;
+        self.inner.copy(&from, &to).await
+    }
+
+    async fn read_dir(
+        &self,
+        path: &fastn_ds::Path,
+    ) -> Result<Vec<fastn_ds::Path>, fastn_ds::ReadError> {
+        let path = self.resolve(path).ok_or(fastn_ds::ReadError::NotFound)?;
+        Ok(self
+            .inner
+            .read_dir(&path)
+            .await?
+            .into_iter()
+            .map(|child| self.unresolve(child))
+            .collect())
+    }
+
+    async fn rename(
+        &self,
+        from: &fastn_ds::Path,
+        to: &fastn_ds::Path,
+    ) -> Result<(), fastn_ds::RenameError> {
+        let from = self.resolve_or_not_found(from)?;
+        let to = self.resolve_or_not_found(to)?;
+        self.inner.rename(&from, &to).await
+    }
+
+    async fn remove(&self, path: &fastn_ds::Path) -> Result<(), fastn_ds::RemoveError> {
+        let path = self.resolve_or_not_found(path)?;
+        self.inner.remove(&path).await
+    }
+
+    async fn get_all_file_path(
+        &self,
+        path: &fastn_ds::Path,
+        ignore_paths: &[String],
+    ) -> Vec<fastn_ds::Path> {
+        let resolved = match self.resolve(path) {
+            Some(resolved) => resolved,
+            // `path` contains `at`, eg all files of the package, `.packages` included
+            None if self.at.path.starts_with(&path.path) => self.target.clone(),
+            None => return vec![],
+        };
+        // Ignored paths are relative to `path`, so they are applied again once the files are
+        // back under `at`
+        let overrides = fastn_ds::backend::package_ignores(ignore_paths, &path.path).unwrap();
+        self.inner
+            .get_all_file_path(&resolved, &[])
+            .await
+            .into_iter()
+            .map(|file| self.unresolve(file))
+            .filter(|file| !fastn_ds::backend::is_ignored(&overrides, &path.path, &file.path))
+            .collect()
+    }
+
+    async fn exists(&self, path: &fastn_ds::Path) -> bool {
+        match self.resolve(path) {
+            Some(path) => self.inner.exists(&path).await,
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fastn_ds::Backend;
+
+    async fn vendored() -> (std::sync::Arc<super::super::Memory>, super::Alias) {
+        let inner = std::sync::Arc::new(super::super::Memory::new());
+        for (file, content) in [
+            ("/p/index.ftd", "index"),
+            ("/p/vendor/foo.com/index.ftd", "foo"),
+            ("/p/vendor/foo.com/ignored.ftd", "ignored"),
+        ] {
+            inner
+                .write_content(&fastn_ds::Path::new(file), content.as_bytes().to_vec())
+                .await
+                .unwrap();
+        }
+        let alias = super::Alias::new(
+            &fastn_ds::Path::new("/p/.packages"),
+            &fastn_ds::Path::new("/p/vendor"),
+            inner.clone(),
+        );
+        (inner, alias)
+    }
+
+    #[tokio::test]
+    async fn read() {
+        let (_, alias) = vendored().await;
+        assert_eq!(
+            alias
+                .read_content(&fastn_ds::Path::new("/p/.packages/foo.com/index.ftd"))
+                .await
+                .unwrap(),
+            b"foo"
+        );
+        assert!(alias.exists(&fastn_ds::Path::new("/p/.packages")).await);
+        // Only paths under `at` are served
+        assert!(!alias.exists(&fastn_ds::Path::new("/p/index.ftd")).await);
+        assert!(matches!(
+            alias
+                .read_content(&fastn_ds::Path::new("/p/vendor/foo.com/index.ftd"))
+                .await,
+            Err(fastn_ds::ReadError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn write() {
+        let (inner, alias) = vendored().await;
+        alias
+            .write_content(
+                &fastn_ds::Path::new("/p/.packages/bar.com/index.ftd"),
+                b"bar".to_vec(),
+            )
+            .await
+            .unwrap();
+        assert!(
+            inner
+                .exists(&fastn_ds::Path::new("/p/vendor/bar.com/index.ftd"))
+                .await
+        );
+        assert!(alias
+            .write_content(&fastn_ds::Path::new("/p/index.ftd"), b"index".to_vec())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn list() {
+        let (_, alias) = vendored().await;
+        assert_eq!(
+            alias
+                .read_dir(&fastn_ds::Path::new("/p/.packages"))
+                .await
+                .unwrap(),
+            vec![fastn_ds::Path::new("/p/.packages/foo.com")]
+        );
+        assert_eq!(
+            alias
+                .get_all_file_path(
+                    &fastn_ds::Path::new("/p/.packages"),
+                    &["foo.com/ignored.ftd".to_string()]
+                )
+                .await,
+            vec![fastn_ds::Path::new("/p/.packages/foo.com/index.ftd")]
+        );
+        // `at` is hidden, so a walk from above it skips the aliased files, as it would on disk
+        assert!(alias
+            .get_all_file_path(&fastn_ds::Path::new("/"), &[])
+            .await
+            .is_empty());
+        assert!(alias
+            .get_all_file_path(&fastn_ds::Path::new("/p/index.ftd"), &[])
+            .await
+            .is_empty());
+    }
+}
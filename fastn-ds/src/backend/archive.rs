@@ -0,0 +1,353 @@
+/// Read-only backend serving a package straight out of a zip archive.
+///
+/// Packages are usually published as archives with all files under a single top level
+/// directory (eg `repo-main/FASTN.ftd`), which is stripped. Archives of the package root itself
+/// (eg `FASTN.ftd`) are served as they are. Once
+/// `with_checksums()` is called only the listed files are visible, and each of them is
+/// verified against its checksum the first time it is read.
+pub struct Zip {
+    root: camino::Utf8PathBuf,
+    archive: std::sync::Mutex<zip::ZipArchive<std::io::Cursor<Vec<u8>>>>,
+    /// path of the file relative to `root` -> index of the entry in the archive
+    entries: std::collections::BTreeMap<String, usize>,
+    checksums: std::collections::BTreeMap<String, String>,
+    verified: std::sync::Mutex<std::collections::HashSet<String>>,
+}
+
+impl std::fmt::Debug for Zip {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Zip")
+            .field("root", &self.root)
+            .field("entries", &self.entries.len())
+            .finish()
+    }
+}
+
+impl Zip {
+    /// `root` is the directory the archive is served at.
+    pub fn new(root: &fastn_ds::Path, content: Vec<u8>) -> Result<Self, zip::result::ZipError> {
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(content))?;
+        let mut files = vec![];
+
+        for i in 0..archive.len() {
+            let entry = archive.by_index(i)?;
+            if !entry.is_file() {
+                continue;
+            }
+            match entry.enclosed_name() {
+                Some(path) => files.push((path.to_string_lossy().replace('\\', "/"), i)),
+                None => continue,
+            }
+        }
+
+        let prefix = top_level_directory(files.iter().map(|(path, _)| path.as_str()))
+            .map(|directory| format!("{}/", directory));
+        let entries = files
+            .into_iter()
+            .map(|(path, i)| match prefix.as_deref() {
+                Some(prefix) => (path[prefix.len()..].to_string(), i),
+                None => (path, i),
+            })
+            .collect();
+
+        Ok(Zip {
+            root: root.path.clone(),
+            archive: std::sync::Mutex::new(archive),
+            entries,
+            checksums: Default::default(),
+            verified: Default::default(),
+        })
+    }
+
+    /// `checksums` maps file names, relative to the package root, to the sha256 of their
+    /// content, as found in the `files` of a package's `manifest.json`.
+    pub fn with_checksums(mut self, checksums: std::collections::BTreeMap<String, String>) -> Self {
+        self.entries.retain(|name, _| checksums.contains_key(name));
+        self.checksums = checksums;
+        self
+    }
+
+    fn relative<'a>(&self, path: &'a fastn_ds::Path) -> Option<&'a str> {
+        path.path.strip_prefix(&self.root).ok().map(|v| v.as_str())
+    }
+
+    fn read_entry(&self, name: &str, index: usize) -> Result<Vec<u8>, fastn_ds::ReadError> {
+        let content = {
+            let mut archive = self.archive.lock().unwrap();
+            let mut entry = archive
+                .by_index(index)
+                .map_err(|e| fastn_ds::ReadError::IOError(e.into()))?;
+            let mut content = vec![];
+            std::io::Read::read_to_end(&mut entry, &mut content)?;
+            content
+        };
+
+        if let Some(checksum) = self.checksums.get(name) {
+            let mut verified = self.verified.lock().unwrap();
+            if !verified.contains(name) {
+                if !fastn_ds::generate_hash(&content).eq(checksum) {
+                    return Err(fastn_ds::ReadError::IOError(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("checksum mismatch for {} in {}", name, self.root),
+                    )));
+                }
+                verified.insert(name.to_string());
+            }
+        }
+
+        Ok(content)
+    }
+}
+
+/// The directory all of `paths` are in, if they are all in the same one.
+fn top_level_directory<'a>(mut paths: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let (directory, _) = paths.next()?.split_once('/')?;
+    paths
+        .all(|path| {
+            path.split_once('/')
+                .map_or(false, |(other, _)| other.eq(directory))
+        })
+        .then_some(directory)
+}
+
+fn read_only() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::PermissionDenied,
+        "zip archives are mounted read-only",
+    )
+}
+
+#[async_trait::async_trait]
+impl fastn_ds::Backend for Zip {
+    async fn read_content(&self, path: &fastn_ds::Path) -> Result<Vec<u8>, fastn_ds::ReadError> {
+        let name = self.relative(path).ok_or(fastn_ds::ReadError::NotFound)?;
+        let index = *self
+            .entries
+            .get(name)
+            .ok_or(fastn_ds::ReadError::NotFound)?;
+        self.read_entry(name, index)
+    }
+
+    async fn write_content(
+        &self,
+        _path: &fastn_ds::Path,
+        _data: Vec<u8>,
+    ) -> Result<(), fastn_ds::WriteError> {
+        Err(read_only().into())
+    }
+
+    async fn copy(
+        &self,
+        _from: &fastn_ds::Path,
+        _to: &fastn_ds::Path,
+    ) -> Result<(), fastn_ds::WriteError> {
+        Err(read_only().into())
+    }
+
+    async fn read_dir(
+        &self,
+        path: &fastn_ds::Path,
+    ) -> Result<Vec<fastn_ds::Path>, fastn_ds::ReadError> {
+        let name = self.relative(path).ok_or(fastn_ds::ReadError::NotFound)?;
+        let children = self
+            .entries
+            .keys()
+            .filter_map(|entry| match name {
+                "" => Some(entry.as_str()),
+                name => entry.strip_prefix(name)?.strip_prefix('/'),
+            })
+            .filter_map(|relative| relative.split('/').next())
+            .collect::<std::collections::BTreeSet<_>>();
+
+        if children.is_empty() {
+            return Err(fastn_ds::ReadError::NotFound);
+        }
+
+        Ok(children.into_iter().map(|child| path.join(child)).collect())
+    }
+
+    async fn rename(
+        &self,
+        _from: &fastn_ds::Path,
+        _to: &fastn_ds::Path,
+    ) -> Result<(), fastn_ds::RenameError> {
+        Err(read_only().into())
+    }
+
+    async fn remove(&self, _path: &fastn_ds::Path) -> Result<(), fastn_ds::RemoveError> {
+        Err(read_only().into())
+    }
+
+    async fn get_all_file_path(
+        &self,
+        path: &fastn_ds::Path,
+        ignore_paths: &[String],
+    ) -> Vec<fastn_ds::Path> {
+        let overrides = fastn_ds::backend::package_ignores(ignore_paths, &path.path).unwrap();
+        self.entries
+            .keys()
+            .map(|entry| self.root.join(entry))
+            .filter(|file| {
+                file.starts_with(&path.path)
+                    && !fastn_ds::backend::is_ignored(&overrides, &path.path, file)
+            })
+            .map(|path| fastn_ds::Path { path })
+            .collect()
+    }
+
+    async fn exists(&self, path: &fastn_ds::Path) -> bool {
+        match self.relative(path) {
+            Some("") => true,
+            Some(name) => self.entries.keys().any(|entry| {
+                entry.eq(name)
+                    || entry
+                        .strip_prefix(name)
+                        .map_or(false, |rest| rest.starts_with('/'))
+            }),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fastn_ds::Backend;
+
+    fn archive(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+        for (name, content) in files {
+            writer
+                .start_file(*name, zip::write::FileOptions::default())
+                .unwrap();
+            std::io::Write::write_all(&mut writer, content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn zip(files: &[(&str, &str)]) -> super::Zip {
+        super::Zip::new(&fastn_ds::Path::new("/p/.packages/foo.com"), archive(files)).unwrap()
+    }
+
+    async fn files(zip: &super::Zip) -> Vec<String> {
+        zip.get_all_file_path(&fastn_ds::Path::new("/p/.packages/foo.com"), &[])
+            .await
+            .into_iter()
+            .map(|path| path.to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn top_level_directory() {
+        let zip = zip(&[("foo-main/FASTN.ftd", "-"), ("foo-main/a/index.ftd", "-")]);
+        assert_eq!(
+            files(&zip).await,
+            vec![
+                "/p/.packages/foo.com/FASTN.ftd",
+                "/p/.packages/foo.com/a/index.ftd"
+            ]
+        );
+
+        // Files of the package root are not in a directory to strip
+        let zip = zip(&[("FASTN.ftd", "-"), ("a/index.ftd", "-")]);
+        assert_eq!(
+            files(&zip).await,
+            vec![
+                "/p/.packages/foo.com/FASTN.ftd",
+                "/p/.packages/foo.com/a/index.ftd"
+            ]
+        );
+        let zip = zip(&[("a/index.ftd", "-"), ("b/index.ftd", "-")]);
+        assert_eq!(
+            files(&zip).await,
+            vec![
+                "/p/.packages/foo.com/a/index.ftd",
+                "/p/.packages/foo.com/b/index.ftd"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn with_checksums() {
+        let zip = zip(&[
+            ("foo-main/FASTN.ftd", "fastn"),
+            ("foo-main/index.ftd", "index"),
+            ("foo-main/extra.ftd", "extra"),
+        ])
+        .with_checksums(std::collections::BTreeMap::from([
+            ("FASTN.ftd".to_string(), fastn_ds::generate_hash("fastn")),
+            ("index.ftd".to_string(), fastn_ds::generate_hash("changed")),
+        ]));
+
+        assert_eq!(
+            zip.read_content(&fastn_ds::Path::new("/p/.packages/foo.com/FASTN.ftd"))
+                .await
+                .unwrap(),
+            b"fastn"
+        );
+        assert!(matches!(
+            zip.read_content(&fastn_ds::Path::new("/p/.packages/foo.com/index.ftd"))
+                .await,
+            Err(fastn_ds::ReadError::IOError(e)) if e.kind() == std::io::ErrorKind::InvalidData
+        ));
+        // Files not in the manifest are not served
+        assert!(matches!(
+            zip.read_content(&fastn_ds::Path::new("/p/.packages/foo.com/extra.ftd"))
+                .await,
+            Err(fastn_ds::ReadError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn mount() {
+        let ds = fastn_ds::DocumentStore::with_backend(
+            "/p",
+            std::sync::Arc::new(super::super::Memory::new()),
+        );
+        ds.write_content(
+            &fastn_ds::Path::new(".packages/foo.com/local.ftd"),
+            b"local".to_vec(),
+        )
+        .await
+        .unwrap();
+        let at = fastn_ds::Path::new("/p/.packages/foo.com");
+        ds.mount(
+            &at,
+            std::sync::Arc::new(zip(&[("foo-main/index.ftd", "index")])),
+        );
+        assert!(ds.is_mounted(&at));
+
+        assert_eq!(
+            ds.read_content(&fastn_ds::Path::new(".packages/foo.com/index.ftd"))
+                .await
+                .unwrap(),
+            b"index"
+        );
+        // Files not in the archive are read from the document store's own backend
+        assert_eq!(
+            ds.read_content(&fastn_ds::Path::new(".packages/foo.com/local.ftd"))
+                .await
+                .unwrap(),
+            b"local"
+        );
+        let mut children = ds
+            .read_dir(&at)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|path| path.to_string())
+            .collect::<Vec<_>>();
+        children.sort();
+        assert_eq!(
+            children,
+            vec![
+                "/p/.packages/foo.com/index.ftd",
+                "/p/.packages/foo.com/local.ftd"
+            ]
+        );
+
+        // Removing the directory unmounts the archive
+        ds.remove(&at).await.unwrap();
+        assert!(!ds.is_mounted(&at));
+        assert!(!ds.exists(&at.join("index.ftd")).await);
+    }
+}
@@ -0,0 +1,108 @@
+/// The default backend, reads and writes files on the local disk.
+#[derive(Debug, Default)]
+pub struct FileSystem {}
+
+#[async_trait::async_trait]
+impl fastn_ds::Backend for FileSystem {
+    async fn read_content(&self, path: &fastn_ds::Path) -> Result<Vec<u8>, fastn_ds::ReadError> {
+        use tokio::io::AsyncReadExt;
+
+        let mut file = tokio::fs::File::open(&path.path).await?;
+        let mut contents = vec![];
+        file.read_to_end(&mut contents).await?;
+        Ok(contents)
+    }
+
+    async fn write_content(
+        &self,
+        path: &fastn_ds::Path,
+        data: Vec<u8>,
+    ) -> Result<(), fastn_ds::WriteError> {
+        use tokio::io::AsyncWriteExt;
+
+        // Create the directory if it doesn't exist
+        if let Some(parent) = path.parent() {
+            if !parent.path.exists() {
+                tokio::fs::create_dir_all(parent.path).await?;
+            }
+        }
+
+        let mut file = tokio::fs::File::create(&path.path).await?;
+        file.write_all(&data).await?;
+        Ok(())
+    }
+
+    async fn copy(
+        &self,
+        from: &fastn_ds::Path,
+        to: &fastn_ds::Path,
+    ) -> Result<(), fastn_ds::WriteError> {
+        tokio::fs::copy(&from.path, &to.path).await?;
+        Ok(())
+    }
+
+    async fn read_dir(
+        &self,
+        path: &fastn_ds::Path,
+    ) -> Result<Vec<fastn_ds::Path>, fastn_ds::ReadError> {
+        let mut entries = tokio::fs::read_dir(&path.path).await?;
+        let mut paths = vec![];
+        while let Some(entry) = entries.next_entry().await? {
+            // non utf-8 file names can not be represented by fastn_ds::Path
+            if let Ok(path) = camino::Utf8PathBuf::from_path_buf(entry.path()) {
+                paths.push(fastn_ds::Path { path });
+            }
+        }
+        Ok(paths)
+    }
+
+    async fn rename(
+        &self,
+        from: &fastn_ds::Path,
+        to: &fastn_ds::Path,
+    ) -> Result<(), fastn_ds::RenameError> {
+        Ok(tokio::fs::rename(&from.path, &to.path).await?)
+    }
+
+    async fn remove(&self, path: &fastn_ds::Path) -> Result<(), fastn_ds::RemoveError> {
+        if !path.path.exists() {
+            return Ok(());
+        }
+        if path.path.is_file() {
+            tokio::fs::remove_file(&path.path).await?;
+        } else if path.path.is_dir() {
+            tokio::fs::remove_dir_all(&path.path).await?
+        } else if path.path.is_symlink() {
+            // TODO:
+            // It can be a directory or a file
+        }
+        Ok(())
+    }
+
+    async fn get_all_file_path(
+        &self,
+        path: &fastn_ds::Path,
+        ignore_paths: &[String],
+    ) -> Vec<fastn_ds::Path> {
+        let path = &path.path;
+        let mut ignore_path = ignore::WalkBuilder::new(path);
+        // ignore_paths.hidden(false); // Allow the linux hidden files to be evaluated
+        ignore_path.overrides(fastn_ds::backend::package_ignores(ignore_paths, path).unwrap());
+        ignore_path
+            .build()
+            .flatten()
+            .filter_map(|x| {
+                let path = camino::Utf8PathBuf::from_path_buf(x.into_path()).unwrap();
+                if path.is_dir() {
+                    None
+                } else {
+                    Some(fastn_ds::Path { path })
+                }
+            }) //todo: improve error message
+            .collect::<Vec<fastn_ds::Path>>()
+    }
+
+    async fn exists(&self, path: &fastn_ds::Path) -> bool {
+        path.path.exists()
+    }
+}
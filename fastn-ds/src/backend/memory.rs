@@ -0,0 +1,251 @@
+/// Keeps files in memory, optionally as an overlay on top of another backend.
+///
+/// Writes and removals only ever touch the in-memory layer. Reads fall through to the
+/// `fallback` backend for paths that were neither written nor removed, so a package on disk
+/// can be served with a few files replaced without modifying the package itself.
+#[derive(Debug, Default)]
+pub struct Memory {
+    files: std::sync::RwLock<std::collections::BTreeMap<camino::Utf8PathBuf, Vec<u8>>>,
+    removed: std::sync::RwLock<std::collections::BTreeSet<camino::Utf8PathBuf>>,
+    fallback: Option<std::sync::Arc<dyn fastn_ds::Backend>>,
+}
+
+impl Memory {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_fallback(fallback: std::sync::Arc<dyn fastn_ds::Backend>) -> Self {
+        Memory {
+            fallback: Some(fallback),
+            ..Default::default()
+        }
+    }
+
+    fn is_removed(&self, path: &camino::Utf8Path) -> bool {
+        self.removed
+            .read()
+            .unwrap()
+            .iter()
+            .any(|removed| path.starts_with(removed))
+    }
+
+    /// Files written to the in-memory layer that live at or under `path`.
+    fn files_under(&self, path: &camino::Utf8Path) -> Vec<camino::Utf8PathBuf> {
+        self.files
+            .read()
+            .unwrap()
+            .keys()
+            .filter(|file| file.starts_with(path))
+            .cloned()
+            .collect()
+    }
+}
+
+fn into_io_error(e: fastn_ds::ReadError) -> std::io::Error {
+    match e {
+        fastn_ds::ReadError::IOError(e) => e,
+        fastn_ds::ReadError::NotFound => std::io::ErrorKind::NotFound.into(),
+    }
+}
+
+#[async_trait::async_trait]
+impl fastn_ds::Backend for Memory {
+    async fn read_content(&self, path: &fastn_ds::Path) -> Result<Vec<u8>, fastn_ds::ReadError> {
+        if let Some(content) = self.files.read().unwrap().get(&path.path) {
+            return Ok(content.clone());
+        }
+        match self.fallback {
+            Some(ref fallback) if !self.is_removed(&path.path) => fallback.read_content(path).await,
+            _ => Err(fastn_ds::ReadError::NotFound),
+        }
+    }
+
+    async fn write_content(
+        &self,
+        path: &fastn_ds::Path,
+        data: Vec<u8>,
+    ) -> Result<(), fastn_ds::WriteError> {
+        self.files.write().unwrap().insert(path.path.clone(), data);
+        Ok(())
+    }
+
+    async fn copy(
+        &self,
+        from: &fastn_ds::Path,
+        to: &fastn_ds::Path,
+    ) -> Result<(), fastn_ds::WriteError> {
+        let content = self.read_content(from).await.map_err(into_io_error)?;
+        self.write_content(to, content).await
+    }
+
+    async fn read_dir(
+        &self,
+        path: &fastn_ds::Path,
+    ) -> Result<Vec<fastn_ds::Path>, fastn_ds::ReadError> {
+        let mut children = std::collections::BTreeSet::new();
+        for file in self.files_under(&path.path) {
+            if let Some(name) = file
+                .strip_prefix(&path.path)
+                .ok()
+                .and_then(|relative| relative.components().next())
+            {
+                children.insert(path.path.join(name));
+            }
+        }
+
+        if let Some(ref fallback) = self.fallback {
+            if !self.is_removed(&path.path) && fallback.exists(path).await {
+                for child in fallback.read_dir(path).await? {
+                    if !self.is_removed(&child.path) {
+                        children.insert(child.path);
+                    }
+                }
+            }
+        }
+
+        if children.is_empty() && !self.exists(path).await {
+            return Err(fastn_ds::ReadError::NotFound);
+        }
+
+        Ok(children
+            .into_iter()
+            .map(|path| fastn_ds::Path { path })
+            .collect())
+    }
+
+    async fn rename(
+        &self,
+        from: &fastn_ds::Path,
+        to: &fastn_ds::Path,
+    ) -> Result<(), fastn_ds::RenameError> {
+        if !self.exists(from).await {
+            return Err(fastn_ds::RenameError::IOError(
+                std::io::ErrorKind::NotFound.into(),
+            ));
+        }
+
+        let mut sources = self.files_under(&from.path);
+        if let Some(ref fallback) = self.fallback {
+            if fallback.exists(from).await {
+                sources.extend(
+                    fallback
+                        .get_all_file_path(from, &[])
+                        .await
+                        .into_iter()
+                        .map(|p| p.path),
+                );
+            }
+            if !sources.contains(&from.path) && fallback.read_content(from).await.is_ok() {
+                sources.push(from.path.clone());
+            }
+        }
+
+        for source in sources {
+            let source = fastn_ds::Path { path: source };
+            let content = match self.read_content(&source).await {
+                Ok(content) => content,
+                Err(fastn_ds::ReadError::NotFound) => continue,
+                Err(e) => return Err(fastn_ds::RenameError::IOError(into_io_error(e))),
+            };
+            let target = match source.strip_prefix(from) {
+                Some(relative) if !relative.path.as_str().is_empty() => to.join(relative.path),
+                _ => to.clone(),
+            };
+            self.files.write().unwrap().insert(target.path, content);
+        }
+
+        self.remove(from)
+            .await
+            .map_err(|fastn_ds::RemoveError::IOError(e)| fastn_ds::RenameError::IOError(e))
+    }
+
+    async fn remove(&self, path: &fastn_ds::Path) -> Result<(), fastn_ds::RemoveError> {
+        self.files
+            .write()
+            .unwrap()
+            .retain(|file, _| !file.starts_with(&path.path));
+        if self.fallback.is_some() {
+            self.removed.write().unwrap().insert(path.path.clone());
+        }
+        Ok(())
+    }
+
+    async fn get_all_file_path(
+        &self,
+        path: &fastn_ds::Path,
+        ignore_paths: &[String],
+    ) -> Vec<fastn_ds::Path> {
+        let overrides = fastn_ds::backend::package_ignores(ignore_paths, &path.path).unwrap();
+        let mut files = self
+            .files_under(&path.path)
+            .into_iter()
+            .filter(|file| !fastn_ds::backend::is_ignored(&overrides, &path.path, file))
+            .collect::<std::collections::BTreeSet<_>>();
+
+        if let Some(ref fallback) = self.fallback {
+            for file in fallback.get_all_file_path(path, ignore_paths).await {
+                if !self.is_removed(&file.path) {
+                    files.insert(file.path);
+                }
+            }
+        }
+
+        files
+            .into_iter()
+            .map(|path| fastn_ds::Path { path })
+            .collect()
+    }
+
+    async fn exists(&self, path: &fastn_ds::Path) -> bool {
+        if !self.files_under(&path.path).is_empty() {
+            return true;
+        }
+        match self.fallback {
+            Some(ref fallback) if !self.is_removed(&path.path) => fallback.exists(path).await,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fastn_ds::Backend;
+
+    #[tokio::test]
+    async fn overlay() {
+        let base = std::sync::Arc::new(super::Memory::new());
+        base.write_content(&fastn_ds::Path::new("/p/index.ftd"), b"base".to_vec())
+            .await
+            .unwrap();
+        base.write_content(&fastn_ds::Path::new("/p/about.ftd"), b"about".to_vec())
+            .await
+            .unwrap();
+
+        let overlay = super::Memory::with_fallback(base.clone());
+        overlay
+            .write_content(&fastn_ds::Path::new("/p/index.ftd"), b"overlay".to_vec())
+            .await
+            .unwrap();
+        overlay
+            .remove(&fastn_ds::Path::new("/p/about.ftd"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            overlay
+                .read_content(&fastn_ds::Path::new("/p/index.ftd"))
+                .await
+                .unwrap(),
+            b"overlay"
+        );
+        assert!(!overlay.exists(&fastn_ds::Path::new("/p/about.ftd")).await);
+        assert!(base.exists(&fastn_ds::Path::new("/p/about.ftd")).await);
+        assert_eq!(
+            overlay
+                .get_all_file_path(&fastn_ds::Path::new("/p"), &[])
+                .await,
+            vec![fastn_ds::Path::new("/p/index.ftd")]
+        );
+    }
+}
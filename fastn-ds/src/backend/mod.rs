@@ -0,0 +1,89 @@
+mod alias;
+mod archive;
+mod fs;
+mod memory;
+
+pub use alias::Alias;
+pub use archive::Zip;
+pub use fs::FileSystem;
+pub use memory::Memory;
+
+/// Storage used by `fastn_ds::DocumentStore` to access package content.
+///
+/// All paths given to a backend are already resolved against the document store root, so
+/// a backend never has to know where the package lives.
+#[async_trait::async_trait]
+pub trait Backend: std::fmt::Debug + Send + Sync {
+    async fn read_content(&self, path: &fastn_ds::Path) -> Result<Vec<u8>, fastn_ds::ReadError>;
+
+    async fn write_content(
+        &self,
+        path: &fastn_ds::Path,
+        data: Vec<u8>,
+    ) -> Result<(), fastn_ds::WriteError>;
+
+    async fn copy(
+        &self,
+        from: &fastn_ds::Path,
+        to: &fastn_ds::Path,
+    ) -> Result<(), fastn_ds::WriteError>;
+
+    /// Returns the immediate children (files and directories) of `path`.
+    async fn read_dir(
+        &self,
+        path: &fastn_ds::Path,
+    ) -> Result<Vec<fastn_ds::Path>, fastn_ds::ReadError>;
+
+    async fn rename(
+        &self,
+        from: &fastn_ds::Path,
+        to: &fastn_ds::Path,
+    ) -> Result<(), fastn_ds::RenameError>;
+
+    async fn remove(&self, path: &fastn_ds::Path) -> Result<(), fastn_ds::RemoveError>;
+
+    /// Returns every file under `path`, skipping hidden files and `ignore_paths`.
+    async fn get_all_file_path(
+        &self,
+        path: &fastn_ds::Path,
+        ignore_paths: &[String],
+    ) -> Vec<fastn_ds::Path>;
+
+    async fn exists(&self, path: &fastn_ds::Path) -> bool;
+}
+
+/// `ignore_paths` of a package, relative to `root_path`, see `is_ignored()`.
+pub fn package_ignores(
+    ignore_paths: &[String],
+    root_path: &camino::Utf8PathBuf,
+) -> Result<ignore::overrides::Override, ignore::Error> {
+    let mut overrides = ignore::overrides::OverrideBuilder::new(root_path);
+    for ignored_path in ignore_paths {
+        overrides.add(format!("!{}", ignored_path).as_str())?;
+    }
+    overrides.build()
+}
+
+/// Whether `path` is skipped when walking `root`: hidden files, and files matched by
+/// `overrides` (see `package_ignores()`), are left out, same as `ignore::WalkBuilder` does.
+pub fn is_ignored(
+    overrides: &ignore::overrides::Override,
+    root: &camino::Utf8Path,
+    path: &camino::Utf8Path,
+) -> bool {
+    let relative = match path.strip_prefix(root) {
+        Ok(relative) => relative,
+        Err(_) => return true,
+    };
+    let components = relative.components().collect::<Vec<_>>();
+    let mut current = root.to_path_buf();
+    for (index, component) in components.iter().enumerate() {
+        current.push(component);
+        let is_dir = index + 1 < components.len();
+        // ignore::WalkBuilder skips hidden files by default, the same is done here
+        if component.as_str().starts_with('.') || overrides.matched(&current, is_dir).is_ignore() {
+            return true;
+        }
+    }
+    false
+}
@@ -1,12 +1,19 @@
 extern crate self as fastn_ds;
 
+pub mod backend;
 pub mod http;
 pub mod mail;
 mod utils;
 
+pub use backend::Backend;
+pub use utils::generate_hash;
+
 #[derive(Debug, Clone)]
 pub struct DocumentStore {
     root: Path,
+    backend: std::sync::Arc<dyn fastn_ds::Backend>,
+    /// Read-only backends layered over directories of `backend`, see `DocumentStore::mount()`.
+    mounts: std::sync::Arc<std::sync::RwLock<Vec<(Path, std::sync::Arc<dyn fastn_ds::Backend>)>>>,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -72,17 +79,6 @@ impl std::fmt::Display for fastn_ds::Path {
     }
 }
 
-fn package_ignores(
-    ignore_paths: &[String],
-    root_path: &camino::Utf8PathBuf,
-) -> Result<ignore::overrides::Override, ignore::Error> {
-    let mut overrides = ignore::overrides::OverrideBuilder::new(root_path);
-    for ignored_path in ignore_paths {
-        overrides.add(format!("!{}", ignored_path).as_str())?;
-    }
-    overrides.build()
-}
-
 #[derive(thiserror::Error, Debug)]
 pub enum RemoveError {
     #[error("io error {0}")]
@@ -150,11 +146,57 @@ pub trait RequestType {
 
 impl DocumentStore {
     pub fn new<T: AsRef<camino::Utf8Path>>(root: T) -> Self {
+        Self::with_backend(
+            root,
+            std::sync::Arc::new(fastn_ds::backend::FileSystem::default()),
+        )
+    }
+
+    /// Create a document store rooted at `root` that keeps its content in `backend`.
+    pub fn with_backend<T: AsRef<camino::Utf8Path>>(
+        root: T,
+        backend: std::sync::Arc<dyn fastn_ds::Backend>,
+    ) -> Self {
         Self {
             root: Path::new(root.as_ref().as_str()),
+            backend,
+            mounts: Default::default(),
         }
     }
 
+    /// Serve the content of the directory `at` from `backend`, eg a package from its zip
+    /// archive. Files not found in `backend` are still read from the document store's own
+    /// backend, and all writes go there too.
+    pub fn mount(&self, at: &fastn_ds::Path, backend: std::sync::Arc<dyn fastn_ds::Backend>) {
+        tracing::debug!("mount {}", at);
+
+        let mut mounts = self.mounts.write().unwrap();
+        mounts.retain(|(path, _)| path.ne(at));
+        mounts.push((at.clone(), backend));
+    }
+
+    pub fn is_mounted(&self, at: &fastn_ds::Path) -> bool {
+        self.mounts
+            .read()
+            .unwrap()
+            .iter()
+            .any(|(path, _)| path.eq(at))
+    }
+
+    /// Mounted backends serving `path`, innermost mount first.
+    fn mounts_for(&self, path: &fastn_ds::Path) -> Vec<std::sync::Arc<dyn fastn_ds::Backend>> {
+        let mut mounts = self
+            .mounts
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(at, _)| path.path.starts_with(&at.path))
+            .cloned()
+            .collect::<Vec<_>>();
+        mounts.sort_by_key(|(at, _)| std::cmp::Reverse(at.path.as_str().len()));
+        mounts.into_iter().map(|(_, backend)| backend).collect()
+    }
+
     pub fn root(&self) -> fastn_ds::Path {
         self.root.clone()
     }
@@ -163,15 +205,22 @@ impl DocumentStore {
         fastn_ds::Path { path: home() }
     }
 
-    pub async fn read_content(&self, path: &fastn_ds::Path) -> Result<Vec<u8>, ReadError> {
-        use tokio::io::AsyncReadExt;
+    pub fn backend(&self) -> std::sync::Arc<dyn fastn_ds::Backend> {
+        self.backend.clone()
+    }
 
+    pub async fn read_content(&self, path: &fastn_ds::Path) -> Result<Vec<u8>, ReadError> {
         tracing::debug!("read_content {}", &path);
 
-        let mut file = tokio::fs::File::open(self.root.join(&path.path).path).await?;
-        let mut contents = vec![];
-        file.read_to_end(&mut contents).await?;
-        Ok(contents)
+        let path = self.root.join(&path.path);
+        for backend in self.mounts_for(&path) {
+            match backend.read_content(&path).await {
+                Err(ReadError::NotFound) => continue,
+                result => return result,
+            }
+        }
+
+        self.backend.read_content(&path).await
     }
 
     pub async fn read_to_string(&self, path: &fastn_ds::Path) -> Result<String, ReadStringError> {
@@ -184,8 +233,7 @@ impl DocumentStore {
     pub async fn copy(&self, from: &fastn_ds::Path, to: &fastn_ds::Path) -> Result<(), WriteError> {
         tracing::debug!("copy from {} to {}", from, to);
 
-        tokio::fs::copy(&from.path, &to.path).await?;
-        Ok(())
+        self.backend.copy(from, to).await
     }
 
     pub async fn write_content(
@@ -193,29 +241,34 @@ impl DocumentStore {
         path: &fastn_ds::Path,
         data: Vec<u8>,
     ) -> Result<(), WriteError> {
-        use tokio::io::AsyncWriteExt;
-
         tracing::debug!("write_content {}", &path);
 
-        let full_path = self.root.join(&path.path);
+        self.backend
+            .write_content(&self.root.join(&path.path), data)
+            .await
+    }
 
-        // Create the directory if it doesn't exist
-        if let Some(parent) = full_path.parent() {
-            if !parent.path.exists() {
-                tokio::fs::create_dir_all(parent.path).await?;
+    pub async fn read_dir(&self, path: &fastn_ds::Path) -> Result<Vec<fastn_ds::Path>, ReadError> {
+        tracing::debug!("read_dir {}", &path);
+
+        let mut children = match self.backend.read_dir(path).await {
+            Ok(children) => children,
+            Err(ReadError::NotFound) => vec![],
+            Err(e) => return Err(e),
+        };
+        for backend in self.mounts_for(path) {
+            for child in backend.read_dir(path).await.unwrap_or_default() {
+                if !children.contains(&child) {
+                    children.push(child);
+                }
             }
         }
 
-        let mut file = tokio::fs::File::create(full_path.path).await?;
-        file.write_all(&data).await?;
-        Ok(())
-    }
-
-    pub async fn read_dir(&self, path: &fastn_ds::Path) -> std::io::Result<tokio::fs::ReadDir> {
-        // Todo: Return type should be ftd::interpreter::Result<Vec<fastn_ds::Dir>> not ftd::interpreter::Result<tokio::fs::ReadDir>
-        tracing::debug!("read_dir {}", &path);
+        if children.is_empty() && !self.exists(path).await {
+            return Err(ReadError::NotFound);
+        }
 
-        tokio::fs::read_dir(&path.path).await
+        Ok(children)
     }
 
     pub async fn rename(
@@ -223,22 +276,15 @@ impl DocumentStore {
         from: &fastn_ds::Path,
         to: &fastn_ds::Path,
     ) -> Result<(), RenameError> {
-        Ok(tokio::fs::rename(&from.path, &to.path).await?)
+        self.backend.rename(from, to).await
     }
 
     pub async fn remove(&self, path: &fastn_ds::Path) -> Result<(), RemoveError> {
-        if !path.path.exists() {
-            return Ok(());
-        }
-        if path.path.is_file() {
-            tokio::fs::remove_file(&path.path).await?;
-        } else if path.path.is_dir() {
-            tokio::fs::remove_dir_all(&path.path).await?
-        } else if path.path.is_symlink() {
-            // TODO:
-            // It can be a directory or a file
-        }
-        Ok(())
+        self.mounts
+            .write()
+            .unwrap()
+            .retain(|(at, _)| !at.path.starts_with(&path.path));
+        self.backend.remove(path).await
     }
 
     pub async fn get_all_file_path(
@@ -246,26 +292,32 @@ impl DocumentStore {
         path: &fastn_ds::Path,
         ignore_paths: &[String],
     ) -> Vec<fastn_ds::Path> {
-        let path = &path.path;
-        let mut ignore_path = ignore::WalkBuilder::new(path);
-        // ignore_paths.hidden(false); // Allow the linux hidden files to be evaluated
-        ignore_path.overrides(package_ignores(ignore_paths, path).unwrap());
-        ignore_path
-            .build()
-            .flatten()
-            .filter_map(|x| {
-                let path = camino::Utf8PathBuf::from_path_buf(x.into_path()).unwrap();
-                if path.is_dir() {
-                    None
-                } else {
-                    Some(fastn_ds::Path { path })
+        let mut files = self.backend.get_all_file_path(path, ignore_paths).await;
+        let mounts = self
+            .mounts
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(at, _)| at.path.starts_with(&path.path) || path.path.starts_with(&at.path))
+            .map(|(_, backend)| backend.clone())
+            .collect::<Vec<_>>();
+        for backend in mounts {
+            for file in backend.get_all_file_path(path, ignore_paths).await {
+                if !files.contains(&file) {
+                    files.push(file);
                 }
-            }) //todo: improve error message
-            .collect::<Vec<fastn_ds::Path>>()
+            }
+        }
+        files
     }
 
     pub async fn exists(&self, path: &fastn_ds::Path) -> bool {
-        path.path.exists()
+        for backend in self.mounts_for(path) {
+            if backend.exists(path).await {
+                return true;
+            }
+        }
+        self.backend.exists(path).await
     }
 
     pub async fn env_bool(&self, key: &str, default: bool) -> Result<bool, BoolEnvironmentError> {
@@ -103,6 +103,7 @@ impl Mailer {
 pub enum EmailKind {
     AccountVerification,
     PasswordReset,
+    LoginLink,
 }
 
 impl std::fmt::Display for EmailKind {
@@ -110,6 +111,7 @@ impl std::fmt::Display for EmailKind {
         match self {
             EmailKind::AccountVerification => write!(f, "account-verification"),
             EmailKind::PasswordReset => write!(f, "password-reset"),
+            EmailKind::LoginLink => write!(f, "login-link"),
         }
     }
 }
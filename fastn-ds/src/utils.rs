@@ -1,3 +1,12 @@
 pub fn ignore_headers() -> Vec<&'static str> {
     vec!["host", "x-forwarded-ssl"]
 }
+
+/// The sha256 of `content`, as uppercase hex, the checksum of files in `manifest.json`.
+pub fn generate_hash(content: impl AsRef<[u8]>) -> String {
+    use sha2::digest::FixedOutput;
+    use sha2::Digest;
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(content);
+    format!("{:X}", hasher.finalize_fixed())
+}
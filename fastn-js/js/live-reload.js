@@ -0,0 +1,125 @@
+// Live reload for `fastn serve`. Listens to the events the server streams on
+// `/-/live-reload/`:
+// - `file-changed`: reload the page once it renders again, if it is rendered
+//   from one of the changed documents
+// - `css-changed`: swap the changed stylesheets without reloading the page
+// - `build-error`: show the error, with its location, over the page it is
+//   about
+(function () {
+    if (!window.EventSource) {
+        return;
+    }
+
+    const OVERLAY_ID = "fastn-live-reload-error";
+    let source = new EventSource("/-/live-reload/");
+
+    function hideError() {
+        let overlay = document.getElementById(OVERLAY_ID);
+        if (overlay) {
+            overlay.remove();
+        }
+    }
+
+    function showError(event) {
+        hideError();
+        let overlay = document.createElement("div");
+        overlay.id = OVERLAY_ID;
+        overlay.style.cssText =
+            "position: fixed; inset: 0; z-index: 2147483647; overflow: auto;" +
+            "padding: 32px; background: rgba(20, 20, 20, 0.92); color: #f8f8f8;" +
+            "font: 14px/1.5 monospace; white-space: pre-wrap;";
+
+        let title = document.createElement("div");
+        title.style.cssText =
+            "color: #ff6b6b; font-weight: bold; margin-bottom: 16px;";
+        // Only ftd errors have a location, other errors are about the page
+        let location = event.document || event.url;
+        title.textContent = event.line_number
+            ? `Error in ${location}:${event.line_number}`
+            : `Error in ${location}`;
+
+        let message = document.createElement("div");
+        message.textContent = event.message;
+
+        let close = document.createElement("button");
+        close.textContent = "Dismiss";
+        close.style.cssText = "margin-top: 16px;";
+        close.onclick = hideError;
+
+        overlay.appendChild(title);
+        overlay.appendChild(message);
+        overlay.appendChild(close);
+        document.body.appendChild(overlay);
+    }
+
+    // Only reload once the page renders, a page that fails to render is
+    // reported as a `build-error` event instead, so the current page stays.
+    function reloadPage() {
+        fetch(window.location.href, { cache: "no-store" })
+            .then(function (response) {
+                if (response.ok) {
+                    window.location.reload();
+                }
+            })
+            .catch(function () {});
+    }
+
+    function swapCss(files) {
+        let links = Array.from(
+            document.querySelectorAll("link[rel='stylesheet']"),
+        );
+        let swapped = false;
+        for (let link of links) {
+            let url = new URL(link.href, window.location.href);
+            let changed = files.some(function (file) {
+                return url.pathname.endsWith("/" + file);
+            });
+            if (!changed) {
+                continue;
+            }
+            url.searchParams.set("fastn-live-reload", Date.now().toString());
+            link.href = url.toString();
+            swapped = true;
+        }
+        // The stylesheet is not linked, eg it is inlined, reload the page
+        if (!swapped) {
+            reloadPage();
+        }
+    }
+
+    // `__fastn_live_reload_documents__`: ids of the documents the page is
+    // rendered from, set by the server along with this script
+    function isRenderedFrom(documents) {
+        if (typeof __fastn_live_reload_documents__ === "undefined") {
+            return true;
+        }
+        return documents.some(function (id) {
+            return __fastn_live_reload_documents__.includes(id);
+        });
+    }
+
+    function samePath(a, b) {
+        return a.replace(/\/+$/, "") === b.replace(/\/+$/, "");
+    }
+
+    source.onmessage = function (message) {
+        let event = JSON.parse(message.data);
+        switch (event.type) {
+            case "file-changed":
+                if (isRenderedFrom(event.documents)) {
+                    reloadPage();
+                }
+                break;
+            case "css-changed":
+                hideError();
+                swapCss(event.files);
+                break;
+            case "build-error":
+                // Errors of other pages are shown there
+                if (samePath(event.url, window.location.pathname)) {
+                    showError(event);
+                }
+                break;
+        }
+    };
+})();
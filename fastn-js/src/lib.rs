@@ -58,6 +58,10 @@ pub fn fastn_test_js() -> &'static str {
     include_str!("../js/fastn_test.js")
 }
 
+pub fn live_reload_js() -> &'static str {
+    include_str!("../js/live-reload.js")
+}
+
 pub fn all_js_without_test_and_ftd_langugage_js() -> String {
     let markdown_js = fastn_js::markdown_js();
     let fastn_js = include_str_with_debug!("../js/fastn.js");
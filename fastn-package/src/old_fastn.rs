@@ -79,6 +79,7 @@ pub struct EndpointData {
 pub struct PackageTemp {
     pub name: String,
     pub versioned: bool,
+    pub version: Option<String>,
     #[serde(rename = "translation-of")]
     pub translation_of: Option<String>,
     #[serde(rename = "translation")]
@@ -0,0 +1,291 @@
+const ARCHIVE_FILE: &str = "archive.zip";
+
+/// Packages fetched by any project on this machine, at `~/.fastn/packages`, so they are only
+/// downloaded once and can be installed without network access.
+///
+/// Entries are keyed by package name and manifest checksum,
+/// `~/.fastn/packages/<package-name>/<checksum>/{manifest.json,archive.zip}`. Only archives
+/// that were verified against their manifest are added, and `manifest.json` is written last so
+/// an entry without it is incomplete and ignored.
+pub(crate) struct Cache {
+    root: fastn_ds::Path,
+}
+
+impl Cache {
+    pub(crate) fn new(ds: &fastn_ds::DocumentStore) -> Self {
+        Cache {
+            root: ds.home().join(".fastn").join("packages"),
+        }
+    }
+
+    fn entry(&self, package_name: &str, checksum: &str) -> fastn_ds::Path {
+        self.root.join(package_name).join(checksum)
+    }
+
+    pub(crate) async fn get_archive(
+        &self,
+        ds: &fastn_ds::DocumentStore,
+        package_name: &str,
+        checksum: &str,
+    ) -> Option<Vec<u8>> {
+        let entry = self.entry(package_name, checksum);
+        if !ds
+            .exists(&entry.join(fastn_core::manifest::MANIFEST_FILE))
+            .await
+        {
+            return None;
+        }
+        ds.read_content(&entry.join(ARCHIVE_FILE)).await.ok()
+    }
+
+    pub(crate) async fn get_manifest(
+        &self,
+        ds: &fastn_ds::DocumentStore,
+        package_name: &str,
+        checksum: &str,
+    ) -> Option<fastn_core::Manifest> {
+        let bytes = ds
+            .read_content(
+                &self
+                    .entry(package_name, checksum)
+                    .join(fastn_core::manifest::MANIFEST_FILE),
+            )
+            .await
+            .ok()?;
+        serde_json::de::from_slice(&bytes).ok()
+    }
+
+    /// The cached manifest of the package to install when offline: the locked one if there is
+    /// a lock, else the highest cached version that satisfies all requirements.
+    pub(crate) async fn find_manifest(
+        &self,
+        ds: &fastn_ds::DocumentStore,
+        package_name: &str,
+        locked: Option<&fastn_update::LockedPackage>,
+        requirements: &fastn_update::version::Requirements,
+    ) -> Option<fastn_core::Manifest> {
+        if let Some(locked) = locked {
+            return self
+                .get_manifest(ds, package_name, &locked.checksum)
+                .await
+                .filter(|manifest| requirements.matches(package_name, manifest));
+        }
+
+        let mut candidates = vec![];
+        for entry in ds
+            .read_dir(&self.root.join(package_name))
+            .await
+            .unwrap_or_default()
+        {
+            let checksum = match entry.file_name() {
+                Some(checksum) => checksum,
+                None => continue,
+            };
+            if let Some(manifest) = self.get_manifest(ds, package_name, &checksum).await {
+                if requirements.matches(package_name, &manifest) {
+                    candidates.push(manifest);
+                }
+            }
+        }
+
+        candidates.into_iter().max_by_key(|manifest| {
+            manifest
+                .version
+                .as_deref()
+                .and_then(|v| semver::Version::parse(v).ok())
+        })
+    }
+
+    /// Failing to add a package to the cache does not fail the update, the package is
+    /// downloaded again next time.
+    pub(crate) async fn insert(
+        &self,
+        ds: &fastn_ds::DocumentStore,
+        package_name: &str,
+        manifest: &fastn_core::Manifest,
+        archive: &[u8],
+    ) {
+        let entry = self.entry(package_name, &manifest.checksum);
+        let manifest_bytes = match serde_json::ser::to_vec_pretty(manifest) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                tracing::warn!("failed to cache package {}: {}", package_name, e);
+                return;
+            }
+        };
+
+        if let Err(e) = ds
+            .write_content(&entry.join(ARCHIVE_FILE), archive.to_vec())
+            .await
+        {
+            tracing::warn!("failed to cache package {}: {}", package_name, e);
+            return;
+        }
+        if let Err(e) = ds
+            .write_content(
+                &entry.join(fastn_core::manifest::MANIFEST_FILE),
+                manifest_bytes,
+            )
+            .await
+        {
+            tracing::warn!("failed to cache package {}: {}", package_name, e);
+        }
+    }
+
+    pub(crate) async fn remove(
+        &self,
+        ds: &fastn_ds::DocumentStore,
+        package_name: &str,
+        checksum: &str,
+    ) {
+        if let Err(e) = ds.remove(&self.entry(package_name, checksum)).await {
+            tracing::warn!("failed to remove cached package {}: {}", package_name, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    fn manifest(checksum: &str, version: Option<&str>) -> fastn_core::Manifest {
+        fastn_core::Manifest::new(
+            Default::default(),
+            format!("https://foo.com/{checksum}.zip"),
+            checksum.to_string(),
+            version.map(str::to_string),
+        )
+    }
+
+    fn cache() -> (fastn_ds::DocumentStore, super::Cache) {
+        let ds = fastn_ds::DocumentStore::with_backend(
+            "/p",
+            std::sync::Arc::new(fastn_ds::backend::Memory::new()),
+        );
+        let cache = super::Cache {
+            root: fastn_ds::Path::new("/home/.fastn/packages"),
+        };
+        (ds, cache)
+    }
+
+    async fn find(
+        ds: &fastn_ds::DocumentStore,
+        cache: &super::Cache,
+        locked: Option<&str>,
+        requirement: Option<&str>,
+    ) -> Option<String> {
+        let locked = locked.map(|checksum| fastn_update::LockedPackage {
+            zip_url: format!("https://foo.com/{checksum}.zip"),
+            checksum: checksum.to_string(),
+        });
+        let mut requirements = fastn_update::version::Requirements::default();
+        if let Some(requirement) = requirement {
+            requirements.add(
+                "foo.com",
+                fastn_update::version::Requirement {
+                    required_by: "a.com".to_string(),
+                    version: semver::VersionReq::parse(requirement).unwrap(),
+                },
+            );
+        }
+        cache
+            .find_manifest(ds, "foo.com", locked.as_ref(), &requirements)
+            .await
+            .map(|manifest| manifest.checksum)
+    }
+
+    #[tokio::test]
+    async fn insert_get_remove() {
+        let (ds, cache) = cache();
+        assert_eq!(cache.get_archive(&ds, "foo.com", "A").await, None);
+        assert!(cache.get_manifest(&ds, "foo.com", "A").await.is_none());
+
+        cache
+            .insert(&ds, "foo.com", &manifest("A", Some("0.3.0")), b"zip")
+            .await;
+        assert!(
+            ds.exists(&fastn_ds::Path::new(
+                "/home/.fastn/packages/foo.com/A/manifest.json"
+            ))
+            .await
+        );
+        assert_eq!(
+            cache.get_archive(&ds, "foo.com", "A").await,
+            Some(b"zip".to_vec())
+        );
+        let cached = cache.get_manifest(&ds, "foo.com", "A").await.unwrap();
+        assert_eq!(cached.zip_url, "https://foo.com/A.zip");
+        assert_eq!(cached.version.as_deref(), Some("0.3.0"));
+        // entries are keyed by checksum
+        assert_eq!(cache.get_archive(&ds, "foo.com", "B").await, None);
+        assert_eq!(cache.get_archive(&ds, "bar.com", "A").await, None);
+
+        cache.remove(&ds, "foo.com", "A").await;
+        assert_eq!(cache.get_archive(&ds, "foo.com", "A").await, None);
+        assert!(cache.get_manifest(&ds, "foo.com", "A").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn incomplete_entry() {
+        let (ds, cache) = cache();
+        // an interrupted insert leaves the archive without `manifest.json`
+        ds.write_content(
+            &fastn_ds::Path::new("/home/.fastn/packages/foo.com/A/archive.zip"),
+            b"zip".to_vec(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(cache.get_archive(&ds, "foo.com", "A").await, None);
+        assert_eq!(find(&ds, &cache, None, None).await, None);
+
+        // so is a `manifest.json` that can not be read
+        ds.write_content(
+            &fastn_ds::Path::new("/home/.fastn/packages/foo.com/A/manifest.json"),
+            b"{".to_vec(),
+        )
+        .await
+        .unwrap();
+        assert!(cache.get_manifest(&ds, "foo.com", "A").await.is_none());
+        assert_eq!(find(&ds, &cache, Some("A"), None).await, None);
+    }
+
+    #[tokio::test]
+    async fn find_manifest() {
+        let (ds, cache) = cache();
+        assert_eq!(find(&ds, &cache, None, None).await, None);
+
+        for (checksum, version) in [("A", "0.3.0"), ("B", "0.4.1"), ("C", "0.5.0")] {
+            cache
+                .insert(&ds, "foo.com", &manifest(checksum, Some(version)), b"zip")
+                .await;
+        }
+
+        // the highest cached version that satisfies the requirements
+        assert_eq!(find(&ds, &cache, None, None).await.as_deref(), Some("C"));
+        assert_eq!(
+            find(&ds, &cache, None, Some(">= 0.3, < 0.5"))
+                .await
+                .as_deref(),
+            Some("B")
+        );
+        assert_eq!(find(&ds, &cache, None, Some("^0.6")).await, None);
+
+        // the locked one, if it is cached and still satisfies them
+        assert_eq!(
+            find(&ds, &cache, Some("A"), Some("^0.3")).await.as_deref(),
+            Some("A")
+        );
+        assert_eq!(find(&ds, &cache, Some("A"), Some("^0.4")).await, None);
+        assert_eq!(find(&ds, &cache, Some("D"), None).await, None);
+    }
+
+    #[tokio::test]
+    async fn find_manifest_unversioned() {
+        let (ds, cache) = cache();
+        cache
+            .insert(&ds, "foo.com", &manifest("A", None), b"zip")
+            .await;
+
+        // a manifest without a version is only picked when nobody asked for one
+        assert_eq!(find(&ds, &cache, None, None).await.as_deref(), Some("A"));
+        assert_eq!(find(&ds, &cache, None, Some("^0.3")).await, None);
+    }
+}
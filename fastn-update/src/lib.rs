@@ -2,7 +2,16 @@ use snafu::prelude::*;
 
 extern crate self as fastn_update;
 
+mod cache;
+mod lock;
 mod utils;
+mod vendor;
+mod version;
+
+pub use lock::{Lock, LockedPackage, LOCK_FILE};
+
+/// Directory under `.packages` where archives that failed verification are kept.
+pub const QUARANTINE_DIR: &str = ".quarantine";
 
 #[derive(Snafu, Debug)]
 pub enum ManifestError {
@@ -18,6 +27,11 @@ pub enum ManifestError {
         package: String,
         source: serde_json::Error,
     },
+    #[snafu(display("Failed to serialize manifest.json for package '{package}'"))]
+    SerializeManifest {
+        package: String,
+        source: serde_json::Error,
+    },
     #[snafu(display("Failed to read manifest content for package '{package}'"))]
     ReadManifest {
         package: String,
@@ -51,6 +65,30 @@ pub enum ArchiveError {
         package: String,
         source: fastn_ds::WriteError,
     },
+    #[snafu(display("Failed to remove unpacked content of package '{package}'"))]
+    RemoveUnpackedContent {
+        package: String,
+        source: fastn_ds::RemoveError,
+    },
+    #[snafu(display(
+        "Checksum mismatch for '{file}' in the archive of package '{package}' (expected: {expected}, found: {found})"
+    ))]
+    ChecksumMismatch {
+        package: String,
+        file: String,
+        expected: String,
+        found: String,
+    },
+    #[snafu(display(
+        "Archive of package '{package}' is missing files listed in its manifest.json: {}",
+        files.join(", ")
+    ))]
+    MissingArchiveEntries { package: String, files: Vec<String> },
+    #[snafu(display("Failed to mount archive for package '{package}'"))]
+    MountArchive {
+        package: String,
+        source: fastn_core::Error,
+    },
 }
 
 #[derive(Snafu, Debug)]
@@ -60,11 +98,99 @@ pub enum DependencyError {
         package: String,
         source: fastn_core::Error,
     },
+    #[snafu(display(
+        "Package '{package}' is neither installed nor in the package cache. Run `fastn update` without `--offline` to download it."
+    ))]
+    NotCached { package: String },
+}
+
+#[derive(Snafu, Debug)]
+pub enum LockError {
+    #[snafu(display(
+        "Package '{package}' has changed since it was locked in fastn.lock, and the locked version is neither in the package cache nor at its locked archive url anymore (locked checksum: {locked}, published checksum: {published}). Run `fastn update --upgrade` to update it."
+    ))]
+    PublishedChecksumMismatch {
+        package: String,
+        locked: String,
+        published: String,
+    },
+    #[snafu(display("Failed to read fastn.lock"))]
+    ReadLock { source: fastn_ds::ReadError },
+    #[snafu(display("Failed to deserialize fastn.lock"))]
+    DeserializeLock { source: serde_json::Error },
+    #[snafu(display("Failed to serialize fastn.lock"))]
+    SerializeLock { source: serde_json::Error },
+    #[snafu(display("Failed to write fastn.lock"))]
+    WriteLock { source: fastn_ds::WriteError },
+}
+
+#[derive(Snafu, Debug)]
+pub enum VersionError {
+    #[snafu(display("Failed to download versions.json for package '{package}'"))]
+    DownloadVersions {
+        package: String,
+        source: fastn_core::Error,
+    },
+    #[snafu(display("Failed to deserialize versions.json for package '{package}'"))]
+    DeserializeVersions {
+        package: String,
+        source: serde_json::Error,
+    },
+    #[snafu(display("Invalid version '{version}' in versions.json of package '{package}'"))]
+    InvalidVersion {
+        package: String,
+        version: String,
+        source: semver::Error,
+    },
+    #[snafu(display("Failed to serialize manifest of a version of package '{package}'"))]
+    SerializeVersion {
+        package: String,
+        source: serde_json::Error,
+    },
+    #[snafu(display(
+        "No published version of package '{package}' satisfies all requirements: {requirements}"
+    ))]
+    NoMatchingVersion {
+        package: String,
+        requirements: String,
+    },
+}
+
+#[derive(Snafu, Debug)]
+pub enum VendorError {
+    #[snafu(display("Failed to read '{file}' of package '{package}' to vendor it"))]
+    ReadPackageFile {
+        package: String,
+        file: String,
+        source: fastn_ds::ReadError,
+    },
+    #[snafu(display("Failed to write vendored content of package '{package}'"))]
+    WriteVendored {
+        package: String,
+        source: fastn_ds::WriteError,
+    },
+    #[snafu(display("Failed to remove '{path}' while vendoring packages"))]
+    RemoveVendored {
+        path: String,
+        source: fastn_ds::RemoveError,
+    },
+    #[snafu(display(
+        "Packages vendored in {directory}/ do not match FASTN.ftd, run `fastn update --vendor` to vendor them again.\n\n{reason}"
+    ))]
+    VendoredOutOfSync { directory: String, reason: String },
+    #[snafu(display("Failed to move '{from}' to '{to}' while vendoring packages"))]
+    RenameVendored {
+        from: String,
+        to: String,
+        source: fastn_ds::RenameError,
+    },
 }
 
 #[derive(Debug)]
 pub enum CheckError {
     WriteDuringCheck { package: String, file: String },
+    LockOutOfSync { packages: Vec<String> },
+    MissingLock,
 }
 
 impl std::fmt::Display for CheckError {
@@ -82,6 +208,25 @@ impl std::fmt::Display for CheckError {
                     "Write Attempt".yellow()
                 )
             }
+            CheckError::LockOutOfSync { packages } => {
+                write!(
+                    f,
+                    "{}\n\n{} does not match the dependencies in the FASTN.ftd file.\n\nPackages: {}\nHelp: {}",
+                    "Error: Out of Sync Lock".red().bold(),
+                    fastn_update::LOCK_FILE,
+                    packages.join(", "),
+                    "Run `fastn update` and commit the updated lock.".yellow()
+                )
+            }
+            CheckError::MissingLock => {
+                write!(
+                    f,
+                    "{}\n\nThe package has dependencies but no {}.\n\nHelp: {}",
+                    "Error: Missing Lock".red().bold(),
+                    fastn_update::LOCK_FILE,
+                    "Run `fastn update` and commit the lock.".yellow()
+                )
+            }
         }
     }
 }
@@ -102,35 +247,286 @@ pub enum UpdateError {
     #[error("Check error: {0}")]
     Check(#[from] CheckError),
 
+    #[error("Lock error: {0}")]
+    Lock(#[from] LockError),
+
+    #[error("Version error: {0}")]
+    Version(#[from] VersionError),
+
+    #[error("Vendor error: {0}")]
+    Vendor(#[from] VendorError),
+
     #[error("Config error: {0}")]
     Config(#[from] fastn_core::config_temp::Error),
 }
 
+/// The flags of `fastn update`. Other commands update packages with the defaults, and
+/// `offline` if they are run with `--offline`.
+#[derive(Debug, Default, Clone)]
+pub struct UpdateOptions {
+    /// Install packages only from `.packages` and the package cache, never download them.
+    pub offline: bool,
+    /// Fail if packages or `fastn.lock` are out of sync with `FASTN.ftd`, without writing
+    /// anything.
+    pub check: bool,
+    /// Keep packages as `.packages/<package-name>.zip` instead of unpacking them.
+    pub archive: bool,
+    /// Ignore `fastn.lock`, update packages to their latest published versions and rewrite the
+    /// lock.
+    pub upgrade: bool,
+    /// Copy the resolved packages to `vendor/`, fastn then reads them from there only.
+    pub vendor: bool,
+}
+
 async fn update_dependencies(
     ds: &fastn_ds::DocumentStore,
     packages_root: fastn_ds::Path,
     current_package: &fastn_core::Package,
     pb: &indicatif::ProgressBar,
-    offline: bool,
-    check: bool,
+    options: &UpdateOptions,
 ) -> Result<usize, UpdateError> {
-    let mut stack = vec![current_package.clone()];
-    let mut resolved = std::collections::HashSet::new();
-    resolved.insert(current_package.name.to_string());
-    let mut all_packages: Vec<(String, fastn_core::Manifest)> = vec![];
-    let mut updated_packages: usize = 0;
-
-    while let Some(package) = stack.pop() {
-        for dependency in package.dependencies {
-            if resolved.contains(&dependency.package.name) {
-                continue;
-            }
+    let UpdateOptions {
+        offline,
+        check,
+        archive,
+        upgrade,
+        vendor,
+    } = *options;
+    let lock = if upgrade { None } else { Lock::read(ds).await? };
+    let mut requirements = version::Requirements::default();
+    let mut updated_packages = std::collections::HashSet::new();
+    // Packages installed from the package cache while offline
+    let mut cached_packages = std::collections::BTreeMap::new();
+
+    // Resolution starts over when a package resolved earlier turns out to not satisfy the
+    // version requirement of a package resolved later.
+    let all_packages = 'resolve: loop {
+        let mut stack = vec![current_package.clone()];
+        let mut resolved = std::collections::HashSet::new();
+        resolved.insert(current_package.name.to_string());
+        let mut all_packages: Vec<(String, fastn_core::Manifest)> = vec![];
+        pb.set_length(current_package.dependencies.len() as u64);
+        pb.set_position(0);
+
+        while let Some(package) = stack.pop() {
+            for dependency in package.dependencies {
+                let package_name = dependency.package.name.clone();
+                if let Some(ref version) = dependency.version {
+                    requirements.add(
+                        &package_name,
+                        version::Requirement {
+                            required_by: package.name.clone(),
+                            version: version.clone(),
+                        },
+                    );
+                }
+
+                if resolved.contains(&package_name) {
+                    let satisfied = all_packages
+                        .iter()
+                        .find(|(name, _)| name.eq(&package_name))
+                        .map_or(true, |(_, manifest)| {
+                            requirements.matches(&package_name, manifest)
+                        });
+                    if !satisfied {
+                        pb.set_message(format!(
+                            "Resolving {} again to satisfy all version requirements",
+                            &package_name
+                        ));
+                        continue 'resolve;
+                    }
+                    continue;
+                }
+
+                let dependency_path = &packages_root.join(&package_name);
+                let archive_path = fastn_core::manifest::archive_path(dependency_path);
+                let locked = lock.as_ref().and_then(|lock| lock.get(&package_name));
+
+                if offline {
+                    if package_name.eq(&fastn_core::FASTN_UI_INTERFACE) {
+                        resolved.insert(package_name.to_string());
+                        continue;
+                    }
+
+                    if !ds.exists(dependency_path).await && !ds.exists(&archive_path).await {
+                        let manifest = cache::Cache::new(ds)
+                            .find_manifest(ds, &package_name, locked, &requirements)
+                            .await
+                            .context(NotCachedSnafu {
+                                package: package_name.clone(),
+                            })?;
+                        pb.set_message(format!(
+                            "Installing {} from the package cache",
+                            &package_name
+                        ));
+                        install_archive(
+                            ds,
+                            dependency_path,
+                            &manifest,
+                            &package_name,
+                            archive,
+                            pb,
+                            check,
+                        )
+                        .await?;
+                        let manifest_bytes = serde_json::ser::to_vec_pretty(&manifest).context(
+                            SerializeManifestSnafu {
+                                package: package_name.clone(),
+                            },
+                        )?;
+                        write_archive_content(
+                            ds,
+                            &dependency_path.join(fastn_core::manifest::MANIFEST_FILE),
+                            manifest_bytes,
+                            &package_name,
+                            check,
+                        )
+                        .await?;
+                        updated_packages.insert(package_name.clone());
+                        cached_packages.insert(package_name.clone(), manifest);
+                    }
+
+                    if ds.exists(&archive_path).await {
+                        let manifest_bytes = ds
+                            .read_content(
+                                &dependency_path.join(fastn_core::manifest::MANIFEST_FILE),
+                            )
+                            .await
+                            .context(ReadManifestSnafu {
+                                package: package_name.clone(),
+                            })?;
+                        let manifest = utils::read_manifest(&manifest_bytes, &package_name)?;
+                        mount_archive(ds, dependency_path, &manifest, &package_name).await?;
+                    }
+
+                    let dep_package =
+                        utils::resolve_dependency_package(ds, &dependency, dependency_path).await?;
+                    resolved.insert(package_name.to_string());
+                    pb.inc_length(1);
+                    stack.push(dep_package);
+                    continue;
+                }
+
+                let manifest_path = dependency_path.join(fastn_core::manifest::MANIFEST_FILE);
+                // Packages fetched as archives earlier stay archives
+                let keep_archive = archive || ds.exists(&archive_path).await;
+
+                let existing_manifest = if ds.exists(dependency_path).await {
+                    let existing_manifest_bytes =
+                        ds.read_content(&manifest_path)
+                            .await
+                            .context(ReadManifestSnafu {
+                                package: package_name.clone(),
+                            })?;
+                    Some(utils::read_manifest(
+                        &existing_manifest_bytes,
+                        &package_name,
+                    )?)
+                } else {
+                    None
+                };
+
+                let manifest = match (locked, existing_manifest) {
+                    // The installed package is the locked one, no need to look at what is published
+                    (Some(locked), Some(existing_manifest))
+                        if existing_manifest.checksum.eq(&locked.checksum)
+                            && requirements.matches(&package_name, &existing_manifest) =>
+                    {
+                        pb.set_message(format!(
+                            "Skipping download for package \"{}\" as it matches {}.",
+                            &package_name, LOCK_FILE
+                        ));
+                        existing_manifest
+                    }
+                    (locked, existing_manifest) => {
+                        pb.set_message(format!("Resolving {}/manifest.json", &package_name));
+
+                        let versioned = !requirements.get(&package_name).is_empty();
+                        let (manifest, manifest_bytes) = if versioned {
+                            version::get_manifest(&package_name, &requirements, locked).await?
+                        } else {
+                            utils::get_manifest(&package_name).await?
+                        };
+
+                        // A locked version that no longer satisfies the requirements in FASTN.ftd
+                        // files is replaced, and the lock updated. Otherwise the locked version
+                        // is installed, even if the package was published again since.
+                        let (manifest, manifest_bytes) = match locked.filter(|_| !versioned) {
+                            Some(locked) if locked.checksum.ne(&manifest.checksum) => {
+                                pb.set_message(format!(
+                                    "Installing {} as locked in {}",
+                                    &package_name, LOCK_FILE
+                                ));
+                                let locked_manifest =
+                                    locked_manifest(ds, &package_name, locked, check)
+                                        .await?
+                                        .context(PublishedChecksumMismatchSnafu {
+                                            package: package_name.clone(),
+                                            locked: locked.checksum.clone(),
+                                            published: manifest.checksum.clone(),
+                                        })?;
+                                let manifest_bytes =
+                                    serde_json::ser::to_vec_pretty(&locked_manifest).context(
+                                        SerializeManifestSnafu {
+                                            package: package_name.clone(),
+                                        },
+                                    )?;
+                                (locked_manifest, manifest_bytes)
+                            }
+                            _ => (manifest, manifest_bytes),
+                        };
+
+                        // Download the archive if:
+                        // 1. The package does not already exist
+                        // 2. The checksums of the downloaded package manifest and the existing manifest do not match
+                        let should_download_archive = match existing_manifest {
+                            Some(existing_manifest) => {
+                                existing_manifest.checksum.ne(manifest.checksum.as_str())
+                            }
+                            None => true,
+                        };
+
+                        if !should_download_archive {
+                            pb.set_message(format!(
+                                "Skipping download for package \"{}\" as it already exists.",
+                                &package_name
+                            ));
+                        } else {
+                            pb.set_message(format!("Downloading {} archive", &package_name));
+
+                            install_archive(
+                                ds,
+                                dependency_path,
+                                &manifest,
+                                &package_name,
+                                keep_archive,
+                                pb,
+                                check,
+                            )
+                            .await?;
+
+                            write_archive_content(
+                                ds,
+                                &manifest_path,
+                                manifest_bytes,
+                                &package_name,
+                                check,
+                            )
+                            .await?;
+
+                            updated_packages.insert(package_name.clone());
+                        }
+
+                        manifest
+                    }
+                };
+
+                if keep_archive {
+                    mount_archive(ds, dependency_path, &manifest, &package_name).await?;
+                }
 
-            let dep_package = &dependency.package;
-            let package_name = dep_package.name.clone();
-            let dependency_path = &packages_root.join(&package_name);
+                all_packages.push((package_name.to_string(), manifest));
 
-            if offline {
                 if package_name.eq(&fastn_core::FASTN_UI_INTERFACE) {
                     resolved.insert(package_name.to_string());
                     continue;
@@ -141,84 +537,102 @@ async fn update_dependencies(
                 resolved.insert(package_name.to_string());
                 pb.inc_length(1);
                 stack.push(dep_package);
-                continue;
             }
 
-            pb.set_message(format!("Resolving {}/manifest.json", &package_name));
-
-            let (manifest, manifest_bytes) = utils::get_manifest(&package_name).await?;
-
-            let manifest_path = dependency_path.join(fastn_core::manifest::MANIFEST_FILE);
-
-            // Download the archive if:
-            // 1. The package does not already exist
-            // 2. The checksums of the downloaded package manifest and the existing manifest do not match
-            let should_download_archive = if !ds.exists(dependency_path).await {
-                true
-            } else {
-                let existing_manifest_bytes =
-                    ds.read_content(&manifest_path)
-                        .await
-                        .context(ReadManifestSnafu {
-                            package: package_name.clone(),
-                        })?;
-                let existing_manifest: fastn_core::Manifest =
-                    utils::read_manifest(&existing_manifest_bytes, &package_name)?;
-
-                existing_manifest.checksum.ne(manifest.checksum.as_str())
-            };
-
-            if !should_download_archive {
-                pb.set_message(format!(
-                    "Skipping download for package \"{}\" as it already exists.",
-                    &package_name
-                ));
-            } else {
-                pb.set_message(format!("Downloading {} archive", &package_name));
-
-                download_and_unpack_zip(
-                    ds,
-                    &packages_root.join(&package_name),
-                    &manifest,
-                    &package_name,
-                    pb,
-                    check,
-                )
-                .await?;
+            pb.inc(1);
+        }
 
-                write_archive_content(ds, &manifest_path, manifest_bytes, &package_name, check)
-                    .await?;
+        break all_packages;
+    };
 
-                updated_packages += 1;
-            }
+    if !offline {
+        let new_lock = Lock::from_manifests(&all_packages);
+        if check {
+            new_lock.check(lock.as_ref())?;
+        } else if lock.as_ref().ne(&Some(&new_lock)) {
+            new_lock.write(ds).await?;
+        }
 
-            all_packages.push((package_name.to_string(), manifest));
+        let all_packages: std::collections::BTreeMap<_, _> = all_packages.into_iter().collect();
+        fastn_core::ConfigTemp::write(ds, current_package.name.clone(), all_packages.clone())
+            .await?;
+
+        if vendor && !check {
+            pb.set_message("Vendoring packages");
+            vendor::vendor(
+                ds,
+                &packages_root,
+                &current_package.name,
+                all_packages,
+                &new_lock,
+            )
+            .await?;
+        }
+    } else if !cached_packages.is_empty() && !check {
+        // Offline resolution does not know the manifests of all packages, the ones installed
+        // from the cache are added to those already in config.json
+        let mut known_packages = match fastn_core::ConfigTemp::read(ds).await {
+            Ok(config_temp) => config_temp.all_packages,
+            Err(fastn_core::config_temp::Error::NotFound(_)) => Default::default(),
+            Err(e) => return Err(e.into()),
+        };
+        known_packages.extend(cached_packages);
+        fastn_core::ConfigTemp::write(ds, current_package.name.clone(), known_packages).await?;
+    }
 
-            if package_name.eq(&fastn_core::FASTN_UI_INTERFACE) {
-                resolved.insert(package_name.to_string());
-                continue;
-            }
+    Ok(updated_packages.len())
+}
 
-            let dep_package =
-                utils::resolve_dependency_package(ds, &dependency, dependency_path).await?;
-            resolved.insert(package_name.to_string());
-            pb.inc_length(1);
-            stack.push(dep_package);
-        }
+/// The manifest of the version of a package locked in `fastn.lock`, for when the package was
+/// published again since: from the package cache, else from the `manifest.json` in the archive
+/// at the locked `zip_url`, whose files have to add up to the locked checksum. `None` if the
+/// locked version can not be found.
+async fn locked_manifest(
+    ds: &fastn_ds::DocumentStore,
+    package_name: &str,
+    locked: &LockedPackage,
+    check: bool,
+) -> Result<Option<fastn_core::Manifest>, UpdateError> {
+    let cache = cache::Cache::new(ds);
+    if let Some(manifest) = cache.get_manifest(ds, package_name, &locked.checksum).await {
+        return Ok(Some(manifest));
+    }
 
-        pb.inc(1);
+    let archive = utils::download_archive_content(locked.zip_url.as_str())
+        .await
+        .context(DownloadArchiveSnafu {
+            package: package_name,
+        })?;
+    let manifest = match utils::read_archive_file(
+        &archive,
+        fastn_core::manifest::MANIFEST_FILE,
+        package_name,
+    )? {
+        Some(bytes) => utils::read_manifest(&bytes, package_name)?,
+        None => return Ok(None),
+    };
+    let files = match utils::read_verified_archive(&archive, &manifest, package_name) {
+        Ok(files) => files,
+        Err(e) => {
+            tracing::warn!("locked archive of package {}: {}", package_name, e);
+            return Ok(None);
+        }
+    };
+    if utils::package_checksum(&files).ne(&locked.checksum) {
+        return Ok(None);
     }
 
-    if !offline {
-        fastn_core::ConfigTemp::write(
-            ds,
-            current_package.name.clone(),
-            all_packages.into_iter().collect(),
-        )
-        .await?;
+    let manifest = fastn_core::Manifest {
+        zip_url: locked.zip_url.clone(),
+        checksum: locked.checksum.clone(),
+        ..manifest
+    };
+    // `install_archive()` finds the archive in the cache instead of downloading it again
+    if !check {
+        cache.insert(ds, package_name, &manifest, &archive).await;
     }
 
-    Ok(updated_packages)
+    Ok(Some(manifest))
 }
 
 async fn write_archive_content(
@@ -243,61 +657,197 @@ async fn write_archive_content(
         })?)
 }
 
-async fn download_and_unpack_zip(
+/// Installs the package at `dependency_path`, from the package cache if it has the package,
+/// downloading (and caching) it otherwise. The archive is kept as `.packages/<package-name>.zip`
+/// if `keep_archive` is set, and unpacked otherwise.
+async fn install_archive(
     ds: &fastn_ds::DocumentStore,
     dependency_path: &fastn_ds::Path,
     manifest: &fastn_core::Manifest,
     package_name: &str,
+    keep_archive: bool,
     pb: &indicatif::ProgressBar,
     check: bool,
 ) -> Result<(), UpdateError> {
-    let mut archive = utils::download_archive(manifest.zip_url.clone())
+    let (archive, files) = fetch_archive(ds, manifest, package_name, check).await?;
+
+    if keep_archive {
+        write_zip(ds, dependency_path, archive, package_name, check).await
+    } else {
+        unpack_zip(ds, dependency_path, files, package_name, pb, check).await
+    }
+}
+
+/// The archive of the package and the files in it, verified against the manifest.
+async fn fetch_archive(
+    ds: &fastn_ds::DocumentStore,
+    manifest: &fastn_core::Manifest,
+    package_name: &str,
+    check: bool,
+) -> Result<(Vec<u8>, Vec<(String, Vec<u8>)>), UpdateError> {
+    let cache = cache::Cache::new(ds);
+
+    if let Some(archive) = cache
+        .get_archive(ds, package_name, &manifest.checksum)
+        .await
+    {
+        match utils::read_verified_archive(&archive, manifest, package_name) {
+            Ok(files) => return Ok((archive, files)),
+            Err(e) => {
+                tracing::warn!("dropping cached package {}: {}", package_name, e);
+                cache.remove(ds, package_name, &manifest.checksum).await;
+            }
+        }
+    }
+
+    let archive = utils::download_archive_content(manifest.zip_url.as_str())
         .await
         .context(DownloadArchiveSnafu {
             package: package_name,
         })?;
 
-    for i in 0..archive.len() {
-        let mut entry = archive.by_index(i).context(ArchiveEntryReadSnafu {
+    // Every file is verified before any of them is written, so a bad archive never ends up
+    // in .packages, not even partially unpacked over the installed package
+    let files = match utils::read_verified_archive(&archive, manifest, package_name) {
+        Ok(files) => files,
+        Err(e) => {
+            quarantine_archive(ds, archive, package_name, check).await?;
+            return Err(e.into());
+        }
+    };
+
+    if !check {
+        cache.insert(ds, package_name, manifest, &archive).await;
+    }
+
+    Ok((archive, files))
+}
+
+/// Store the package archive as `.packages/<package-name>.zip`, to be read without unpacking.
+/// Only `manifest.json` is kept in the package directory.
+async fn write_zip(
+    ds: &fastn_ds::DocumentStore,
+    dependency_path: &fastn_ds::Path,
+    archive: Vec<u8>,
+    package_name: &str,
+    check: bool,
+) -> Result<(), UpdateError> {
+    write_archive_content(
+        ds,
+        &fastn_core::manifest::archive_path(dependency_path),
+        archive,
+        package_name,
+        check,
+    )
+    .await?;
+
+    // Drop the files of an earlier unpacked copy of the package, if any
+    ds.remove(dependency_path)
+        .await
+        .context(RemoveUnpackedContentSnafu {
             package: package_name,
         })?;
 
-        if entry.is_file() {
-            let mut buffer = Vec::new();
-            std::io::Read::read_to_end(&mut entry, &mut buffer).context(ReadArchiveSnafu {
-                package: package_name,
-            })?;
-            let path = entry.enclosed_name().context(ArchiveEntryPathSnafu {
-                package: package_name,
-                name: entry.name(),
-            })?;
-            let path_string = path.to_string_lossy().into_owned();
-            let path_normalized = path_string.replace('\\', "/");
-            let path_without_prefix = match path_normalized.split_once('/') {
-                Some((_, path)) => path,
-                None => &path_normalized,
-            };
-            if manifest.files.get(path_without_prefix).is_none() {
-                continue;
-            }
-            let output_path = &dependency_path.join(path_without_prefix);
-            write_archive_content(ds, output_path, buffer, package_name, check).await?;
-            pb.tick();
-        }
+    Ok(())
+}
+
+async fn mount_archive(
+    ds: &fastn_ds::DocumentStore,
+    dependency_path: &fastn_ds::Path,
+    manifest: &fastn_core::Manifest,
+    package_name: &str,
+) -> Result<(), UpdateError> {
+    manifest
+        .mount_archive(ds, dependency_path)
+        .await
+        .context(MountArchiveSnafu {
+            package: package_name,
+        })?;
+
+    Ok(())
+}
+
+async fn unpack_zip(
+    ds: &fastn_ds::DocumentStore,
+    dependency_path: &fastn_ds::Path,
+    files: Vec<(String, Vec<u8>)>,
+    package_name: &str,
+    pb: &indicatif::ProgressBar,
+    check: bool,
+) -> Result<(), UpdateError> {
+    for (name, buffer) in files {
+        let output_path = &dependency_path.join(name);
+        write_archive_content(ds, output_path, buffer, package_name, check).await?;
+        pb.tick();
+    }
+
+    Ok(())
+}
+
+/// Keeps an archive that failed verification at `.packages/.quarantine/<package-name>.zip`, out
+/// of reach of anything reading packages. The installed copy of the package, if any, is left
+/// as it is.
+async fn quarantine_archive(
+    ds: &fastn_ds::DocumentStore,
+    archive: Vec<u8>,
+    package_name: &str,
+    check: bool,
+) -> Result<(), UpdateError> {
+    if check {
+        return Ok(());
     }
 
+    let quarantine_path = ds
+        .root()
+        .join(".packages")
+        .join(QUARANTINE_DIR)
+        .join(format!(
+            "{}.{}",
+            package_name,
+            fastn_core::manifest::ARCHIVE_EXTENSION
+        ));
+    write_archive_content(ds, &quarantine_path, archive, package_name, check).await?;
+    tracing::warn!(
+        "quarantined archive of package {} at {}",
+        package_name,
+        quarantine_path
+    );
+
     Ok(())
 }
 
 #[tracing::instrument(skip_all)]
 pub async fn update(
     ds: &fastn_ds::DocumentStore,
-    offline: bool,
-    check: bool,
+    mut options: UpdateOptions,
 ) -> fastn_core::Result<()> {
     let packages_root = ds.root().join(".packages");
     let current_package = utils::read_current_package(ds).await?;
 
+    let vendored = vendor::is_vendored(ds).await;
+    // Upgrading vendored packages vendors the upgraded ones
+    options.vendor |= vendored && options.upgrade;
+
+    // Without `--vendor`, vendored packages are used as they are. They are still checked against
+    // FASTN.ftd, without going to the network, so a dependency added since is not missed.
+    let use_vendored = vendored && !options.vendor && !options.check;
+    if use_vendored {
+        options.offline = true;
+        options.check = true;
+    }
+
+    if vendored && options.check {
+        // Vendored packages are checked where fastn reads them from, see `Config::read()`
+        ds.mount(
+            &packages_root,
+            std::sync::Arc::new(fastn_ds::backend::Alias::new(
+                &packages_root,
+                &ds.root().join(fastn_core::config_temp::VENDOR_DIRECTORY),
+                ds.backend(),
+            )),
+        );
+    }
+
     if current_package.dependencies.is_empty() {
         println!("No dependencies to update.");
         return Ok(());
@@ -313,8 +863,17 @@ pub async fn update(
     pb.set_prefix("Updating dependencies");
 
     let updated_packages =
-        match update_dependencies(ds, packages_root, &current_package, &pb, offline, check).await {
+        match update_dependencies(ds, packages_root, &current_package, &pb, &options).await {
             Ok(n) => n,
+            Err(e) if use_vendored => {
+                return Err(fastn_core::Error::UpdateError {
+                    message: UpdateError::from(VendorError::VendoredOutOfSync {
+                        directory: fastn_core::config_temp::VENDOR_DIRECTORY.to_string(),
+                        reason: e.to_string(),
+                    })
+                    .to_string(),
+                });
+            }
             Err(UpdateError::Check(e)) => {
                 eprintln!("{}", e);
                 std::process::exit(7);
@@ -336,3 +895,34 @@ pub async fn update(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    #[tokio::test]
+    async fn quarantine_archive() {
+        let ds = fastn_ds::DocumentStore::with_backend(
+            "/p",
+            std::sync::Arc::new(fastn_ds::backend::Memory::new()),
+        );
+        let archive = super::utils::test::archive(&[("index.ftd", "tampered")]);
+
+        // nothing is written by `fastn update --check`
+        super::quarantine_archive(&ds, archive.clone(), "foo.com", true)
+            .await
+            .unwrap();
+        assert!(!ds.exists(&ds.root().join(".packages")).await);
+
+        super::quarantine_archive(&ds, archive.clone(), "foo.com", false)
+            .await
+            .unwrap();
+        assert_eq!(
+            ds.read_content(&fastn_ds::Path::new(".packages/.quarantine/foo.com.zip"))
+                .await
+                .unwrap(),
+            archive
+        );
+        // out of reach of anything reading the package
+        assert!(!ds.exists(&ds.root().join(".packages/foo.com")).await);
+        assert!(!ds.exists(&ds.root().join(".packages/foo.com.zip")).await);
+    }
+}
@@ -0,0 +1,174 @@
+use snafu::ResultExt;
+
+pub const LOCK_FILE: &str = "fastn.lock";
+
+/// `fastn.lock`: the archive and checksum of every package the dependencies in `FASTN.ftd`
+/// resolved to. Once written, `fastn update` keeps installing exactly these packages till
+/// it is run with `--upgrade`.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Lock {
+    pub packages: std::collections::BTreeMap<String, LockedPackage>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct LockedPackage {
+    pub zip_url: String,
+    pub checksum: String,
+}
+
+impl Lock {
+    pub fn from_manifests(manifests: &[(String, fastn_core::Manifest)]) -> Self {
+        Lock {
+            packages: manifests
+                .iter()
+                .map(|(name, manifest)| {
+                    (
+                        name.to_string(),
+                        LockedPackage {
+                            zip_url: manifest.zip_url.to_string(),
+                            checksum: manifest.checksum.to_string(),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns `None` if the package has no `fastn.lock` yet.
+    pub async fn read(
+        ds: &fastn_ds::DocumentStore,
+    ) -> Result<Option<Lock>, fastn_update::LockError> {
+        let bytes = match ds.read_content(&fastn_ds::Path::new(LOCK_FILE)).await {
+            Ok(bytes) => bytes,
+            Err(fastn_ds::ReadError::NotFound) => return Ok(None),
+            Err(e) => return Err(e).context(fastn_update::ReadLockSnafu),
+        };
+
+        Ok(Some(
+            serde_json::de::from_slice(&bytes).context(fastn_update::DeserializeLockSnafu)?,
+        ))
+    }
+
+    pub async fn write(&self, ds: &fastn_ds::DocumentStore) -> Result<(), fastn_update::LockError> {
+        self.write_at(ds, &fastn_ds::Path::new(LOCK_FILE)).await
+    }
+
+    /// Writes the lock to `path` instead of the package's own `fastn.lock`, eg `vendor/fastn.lock`.
+    pub async fn write_at(
+        &self,
+        ds: &fastn_ds::DocumentStore,
+        path: &fastn_ds::Path,
+    ) -> Result<(), fastn_update::LockError> {
+        let mut content =
+            serde_json::ser::to_vec_pretty(self).context(fastn_update::SerializeLockSnafu)?;
+        // Append newline character
+        content.push(b'\n');
+
+        ds.write_content(path, content)
+            .await
+            .context(fastn_update::WriteLockSnafu)
+    }
+
+    pub fn get(&self, package_name: &str) -> Option<&LockedPackage> {
+        self.packages.get(package_name)
+    }
+
+    /// Names of the packages locked differently in `other`, or only in one of the two locks.
+    pub fn out_of_sync(&self, other: &Lock) -> Vec<String> {
+        self.packages
+            .keys()
+            .chain(other.packages.keys())
+            .filter(|name| self.get(name).ne(&other.get(name)))
+            .cloned()
+            .collect::<std::collections::BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// For `fastn update --check`: the package's `fastn.lock`, `locked`, has to be there and
+    /// match this lock of the packages just resolved.
+    pub(crate) fn check(&self, locked: Option<&Lock>) -> Result<(), fastn_update::CheckError> {
+        match locked {
+            None => Err(fastn_update::CheckError::MissingLock),
+            Some(locked) if locked.ne(self) => Err(fastn_update::CheckError::LockOutOfSync {
+                packages: locked.out_of_sync(self),
+            }),
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    fn manifest(zip_url: &str, checksum: &str) -> fastn_core::Manifest {
+        fastn_core::Manifest::new(
+            Default::default(),
+            zip_url.to_string(),
+            checksum.to_string(),
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let ds = fastn_ds::DocumentStore::with_backend(
+            "/p",
+            std::sync::Arc::new(fastn_ds::backend::Memory::new()),
+        );
+        assert_eq!(super::Lock::read(&ds).await.unwrap(), None);
+
+        let lock = super::Lock::from_manifests(&[
+            ("b.com".to_string(), manifest("https://b.com/b.zip", "B")),
+            ("a.com".to_string(), manifest("https://a.com/a.zip", "A")),
+        ]);
+        lock.write(&ds).await.unwrap();
+
+        let content = ds
+            .read_to_string(&fastn_ds::Path::new(super::LOCK_FILE))
+            .await
+            .unwrap();
+        // Packages are sorted, so the lock does not change when nothing else does
+        assert!(content.find("a.com").unwrap() < content.find("b.com").unwrap());
+        assert!(content.ends_with("}\n"));
+        assert_eq!(super::Lock::read(&ds).await.unwrap(), Some(lock));
+    }
+
+    #[test]
+    fn out_of_sync() {
+        let lock = super::Lock::from_manifests(&[
+            ("a.com".to_string(), manifest("https://a.com/a.zip", "A")),
+            ("b.com".to_string(), manifest("https://b.com/b.zip", "B")),
+        ]);
+        assert!(lock.out_of_sync(&lock.clone()).is_empty());
+
+        let other = super::Lock::from_manifests(&[
+            ("a.com".to_string(), manifest("https://a.com/a.zip", "A2")),
+            ("c.com".to_string(), manifest("https://c.com/c.zip", "C")),
+        ]);
+        assert_eq!(lock.out_of_sync(&other), vec!["a.com", "b.com", "c.com"]);
+    }
+
+    #[test]
+    fn check() {
+        let lock = super::Lock::from_manifests(&[(
+            "a.com".to_string(),
+            manifest("https://a.com/a.zip", "A"),
+        )]);
+        assert!(lock.check(Some(&lock.clone())).is_ok());
+
+        // a lock that was never committed is out of sync too
+        assert!(matches!(
+            lock.check(None),
+            Err(fastn_update::CheckError::MissingLock)
+        ));
+
+        let other = super::Lock::from_manifests(&[(
+            "a.com".to_string(),
+            manifest("https://a.com/a.zip", "A2"),
+        )]);
+        assert!(matches!(
+            lock.check(Some(&other)),
+            Err(fastn_update::CheckError::LockOutOfSync { packages }) if packages == vec!["a.com"]
+        ));
+    }
+}
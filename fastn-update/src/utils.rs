@@ -25,16 +25,139 @@ pub async fn read_current_package(
     from_fastn_doc(ds, &fastn_path).await
 }
 
-pub(crate) async fn download_archive(
-    url: String,
-) -> fastn_core::Result<zip::ZipArchive<std::io::Cursor<Vec<u8>>>> {
-    use std::io::Seek;
-
-    let zipball = fastn_core::http::http_get(&url).await?;
-    let mut zipball_cursor = std::io::Cursor::new(zipball);
-    zipball_cursor.seek(std::io::SeekFrom::Start(0))?;
-    let archive = zip::ZipArchive::new(zipball_cursor)?;
-    Ok(archive)
+pub(crate) async fn download_archive_content(url: &str) -> fastn_core::Result<Vec<u8>> {
+    fastn_core::http::http_get(url).await
+}
+
+/// Reads the files listed in the manifest out of a package archive, verifying each of them
+/// against its checksum. Files of the archive not listed in the manifest are skipped.
+pub(crate) fn read_verified_archive(
+    content: &[u8],
+    manifest: &fastn_core::Manifest,
+    package_name: &str,
+) -> Result<Vec<(String, Vec<u8>)>, fastn_update::ArchiveError> {
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(content)).context(
+        fastn_update::ArchiveEntryReadSnafu {
+            package: package_name,
+        },
+    )?;
+    let mut files = vec![];
+
+    for i in 0..archive.len() {
+        let mut entry = archive
+            .by_index(i)
+            .context(fastn_update::ArchiveEntryReadSnafu {
+                package: package_name,
+            })?;
+
+        if !entry.is_file() {
+            continue;
+        }
+        let path = entry
+            .enclosed_name()
+            .context(fastn_update::ArchiveEntryPathSnafu {
+                package: package_name,
+                name: entry.name(),
+            })?;
+        let path_normalized = path.to_string_lossy().replace('\\', "/");
+        let path_without_prefix = match path_normalized.split_once('/') {
+            Some((_, path)) => path.to_string(),
+            None => path_normalized,
+        };
+        let file = match manifest.files.get(&path_without_prefix) {
+            Some(file) => file,
+            None => continue,
+        };
+
+        let mut buffer = Vec::new();
+        std::io::Read::read_to_end(&mut entry, &mut buffer).context(
+            fastn_update::ReadArchiveSnafu {
+                package: package_name,
+            },
+        )?;
+        let checksum = fastn_core::utils::generate_hash(&buffer);
+        snafu::ensure!(
+            checksum.eq(&file.checksum),
+            fastn_update::ChecksumMismatchSnafu {
+                package: package_name,
+                file: path_without_prefix,
+                expected: file.checksum.as_str(),
+                found: checksum,
+            }
+        );
+        files.push((path_without_prefix, buffer));
+    }
+
+    let missing = manifest
+        .files
+        .keys()
+        .filter(|name| !files.iter().any(|(file, _)| file.eq(*name)))
+        .cloned()
+        .collect::<Vec<_>>();
+    snafu::ensure!(
+        missing.is_empty(),
+        fastn_update::MissingArchiveEntriesSnafu {
+            package: package_name,
+            files: missing,
+        }
+    );
+
+    Ok(files)
+}
+
+/// The content of the file `name` in a package archive, without verifying it. `None` if the
+/// archive has no such file.
+pub(crate) fn read_archive_file(
+    content: &[u8],
+    name: &str,
+    package_name: &str,
+) -> Result<Option<Vec<u8>>, fastn_update::ArchiveError> {
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(content)).context(
+        fastn_update::ArchiveEntryReadSnafu {
+            package: package_name,
+        },
+    )?;
+
+    for i in 0..archive.len() {
+        let mut entry = archive
+            .by_index(i)
+            .context(fastn_update::ArchiveEntryReadSnafu {
+                package: package_name,
+            })?;
+        let path_normalized = entry.name().replace('\\', "/");
+        let path_without_prefix = match path_normalized.split_once('/') {
+            Some((_, path)) => path,
+            None => path_normalized.as_str(),
+        };
+        if !entry.is_file() || path_without_prefix.ne(name) {
+            continue;
+        }
+
+        let mut buffer = Vec::new();
+        std::io::Read::read_to_end(&mut entry, &mut buffer).context(
+            fastn_update::ReadArchiveSnafu {
+                package: package_name,
+            },
+        )?;
+        return Ok(Some(buffer));
+    }
+
+    Ok(None)
+}
+
+/// The checksum of a package, as computed by `fastn_core::manifest::write_manifest_file()`: the
+/// SHA-256 of the content of all its files, in the order of their names.
+pub(crate) fn package_checksum(files: &[(String, Vec<u8>)]) -> String {
+    use sha2::Digest;
+
+    let mut files = files.iter().collect::<Vec<_>>();
+    files.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut hasher = sha2::Sha256::new();
+    for (_, content) in files {
+        hasher.update(content);
+    }
+    format!("{:X}", hasher.finalize())
 }
 
 pub(crate) fn read_manifest(
@@ -83,3 +206,131 @@ pub(crate) async fn resolve_dependency_package(
         })?;
     Ok(dep_package)
 }
+
+#[cfg(test)]
+pub(crate) mod test {
+    /// A package archive with its files under `package-main/`, like a GitHub zipball.
+    pub(crate) fn archive(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+        for (name, content) in files {
+            writer
+                .start_file(
+                    format!("package-main/{name}"),
+                    zip::write::FileOptions::default(),
+                )
+                .unwrap();
+            std::io::Write::write_all(&mut writer, content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    /// A manifest listing `files`, with their checksums.
+    fn manifest(files: &[(&str, &str)]) -> fastn_core::Manifest {
+        fastn_core::Manifest::new(
+            files
+                .iter()
+                .map(|(name, content)| {
+                    (
+                        name.to_string(),
+                        fastn_core::manifest::File::new(
+                            name.to_string(),
+                            fastn_core::utils::generate_hash(content),
+                            content.len(),
+                        ),
+                    )
+                })
+                .collect(),
+            "https://foo.com/foo.zip".to_string(),
+            "FOO".to_string(),
+            None,
+        )
+    }
+
+    #[test]
+    fn read_verified_archive() {
+        let manifest = manifest(&[("FASTN.ftd", "fastn"), ("index.ftd", "index")]);
+        let archive = archive(&[
+            ("FASTN.ftd", "fastn"),
+            ("index.ftd", "index"),
+            ("extra.ftd", "not in the manifest"),
+        ]);
+
+        // files not listed in the manifest are skipped
+        assert_eq!(
+            super::read_verified_archive(&archive, &manifest, "foo.com").unwrap(),
+            vec![
+                ("FASTN.ftd".to_string(), b"fastn".to_vec()),
+                ("index.ftd".to_string(), b"index".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_verified_archive_checksum_mismatch() {
+        let manifest = manifest(&[("FASTN.ftd", "fastn"), ("index.ftd", "index")]);
+        let archive = archive(&[("FASTN.ftd", "fastn"), ("index.ftd", "tampered")]);
+
+        assert_eq!(
+            super::read_verified_archive(&archive, &manifest, "foo.com")
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "Checksum mismatch for 'index.ftd' in the archive of package 'foo.com' \
+                (expected: {}, found: {})",
+                fastn_core::utils::generate_hash("index"),
+                fastn_core::utils::generate_hash("tampered"),
+            )
+        );
+    }
+
+    #[test]
+    fn read_verified_archive_missing_entries() {
+        let manifest = manifest(&[
+            ("FASTN.ftd", "fastn"),
+            ("index.ftd", "index"),
+            ("lib.ftd", "lib"),
+        ]);
+        let archive = archive(&[("FASTN.ftd", "fastn")]);
+
+        assert_eq!(
+            super::read_verified_archive(&archive, &manifest, "foo.com")
+                .unwrap_err()
+                .to_string(),
+            "Archive of package 'foo.com' is missing files listed in its manifest.json: \
+            index.ftd, lib.ftd"
+        );
+        assert_eq!(
+            super::read_verified_archive(b"not a zip", &manifest, "foo.com")
+                .unwrap_err()
+                .to_string(),
+            "Failed to unpack archive for package 'foo.com'"
+        );
+    }
+
+    #[test]
+    fn read_archive_file() {
+        let archive = archive(&[("FASTN.ftd", "fastn"), ("manifest.json", "{}")]);
+        assert_eq!(
+            super::read_archive_file(&archive, "manifest.json", "p").unwrap(),
+            Some(b"{}".to_vec())
+        );
+        assert_eq!(
+            super::read_archive_file(&archive, "index.ftd", "p").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn package_checksum() {
+        use sha2::Digest;
+
+        let files = vec![
+            ("index.ftd".to_string(), b"index".to_vec()),
+            ("FASTN.ftd".to_string(), b"fastn".to_vec()),
+        ];
+        assert_eq!(
+            super::package_checksum(&files),
+            format!("{:X}", sha2::Sha256::digest(b"fastnindex"))
+        );
+    }
+}
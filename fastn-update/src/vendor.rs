@@ -0,0 +1,109 @@
+use snafu::ResultExt;
+
+/// Copies all resolved packages from `.packages` to `vendor/`, unpacked, with the lock and the
+/// `config.json` they were resolved to, and removes `.packages`. Once vendored, fastn reads
+/// dependencies from `vendor/` only and never fetches them.
+pub(crate) async fn vendor(
+    ds: &fastn_ds::DocumentStore,
+    packages_root: &fastn_ds::Path,
+    package_name: &str,
+    all_packages: std::collections::BTreeMap<String, fastn_core::Manifest>,
+    lock: &fastn_update::Lock,
+) -> Result<(), fastn_update::UpdateError> {
+    let vendor_root = ds.root().join(fastn_core::config_temp::VENDOR_DIRECTORY);
+    // Packages are copied to a temporary directory first, so that `vendor/` is left as it was
+    // if copying fails. Packages no longer depended upon do not end up in it either.
+    let staging_root = ds.root().join(format!(
+        ".{}.tmp",
+        fastn_core::config_temp::VENDOR_DIRECTORY
+    ));
+    let previous_root = ds.root().join(format!(
+        ".{}.old",
+        fastn_core::config_temp::VENDOR_DIRECTORY
+    ));
+
+    for leftover in [&staging_root, &previous_root] {
+        ds.remove(leftover)
+            .await
+            .context(fastn_update::RemoveVendoredSnafu {
+                path: leftover.to_string(),
+            })?;
+    }
+
+    for dependency in all_packages.keys() {
+        let dependency_path = packages_root.join(dependency);
+        for file in ds.get_all_file_path(&dependency_path, &[]).await {
+            let relative = match file.strip_prefix(packages_root) {
+                Some(relative) => relative,
+                None => continue,
+            };
+            let content =
+                ds.read_content(&file)
+                    .await
+                    .context(fastn_update::ReadPackageFileSnafu {
+                        package: dependency,
+                        file: file.to_string(),
+                    })?;
+            ds.write_content(&staging_root.join(relative.to_string()), content)
+                .await
+                .context(fastn_update::WriteVendoredSnafu {
+                    package: dependency,
+                })?;
+        }
+    }
+
+    lock.write_at(ds, &staging_root.join(fastn_update::LOCK_FILE))
+        .await?;
+    fastn_core::ConfigTemp::write_vendored(
+        ds,
+        &staging_root,
+        package_name.to_string(),
+        all_packages,
+    )
+    .await?;
+
+    // A directory can not be renamed over another one, the old `vendor/` is moved aside first
+    let vendored = ds.exists(&vendor_root).await;
+    if vendored {
+        rename(ds, &vendor_root, &previous_root).await?;
+    }
+    rename(ds, &staging_root, &vendor_root).await?;
+    if vendored {
+        ds.remove(&previous_root)
+            .await
+            .context(fastn_update::RemoveVendoredSnafu {
+                path: previous_root.to_string(),
+            })?;
+    }
+
+    ds.remove(packages_root)
+        .await
+        .context(fastn_update::RemoveVendoredSnafu {
+            path: packages_root.to_string(),
+        })?;
+
+    Ok(())
+}
+
+async fn rename(
+    ds: &fastn_ds::DocumentStore,
+    from: &fastn_ds::Path,
+    to: &fastn_ds::Path,
+) -> Result<(), fastn_update::VendorError> {
+    ds.rename(from, to)
+        .await
+        .context(fastn_update::RenameVendoredSnafu {
+            from: from.to_string(),
+            to: to.to_string(),
+        })
+}
+
+/// Whether the dependencies of the package are vendored, see `vendor()`.
+pub(crate) async fn is_vendored(ds: &fastn_ds::DocumentStore) -> bool {
+    ds.exists(
+        &ds.root()
+            .join(fastn_core::config_temp::VENDOR_DIRECTORY)
+            .join("config.json"),
+    )
+    .await
+}
@@ -0,0 +1,216 @@
+use snafu::prelude::*;
+
+/// A version requirement on a package, eg `>= 0.3, < 0.5`, and the package it comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Requirement {
+    pub required_by: String,
+    pub version: semver::VersionReq,
+}
+
+/// All version requirements found while resolving dependencies, keyed by package name.
+#[derive(Debug, Default)]
+pub(crate) struct Requirements(std::collections::BTreeMap<String, Vec<Requirement>>);
+
+impl Requirements {
+    pub(crate) fn add(&mut self, package_name: &str, requirement: Requirement) {
+        let requirements = self.0.entry(package_name.to_string()).or_default();
+        if !requirements.contains(&requirement) {
+            requirements.push(requirement);
+        }
+    }
+
+    pub(crate) fn get(&self, package_name: &str) -> &[Requirement] {
+        self.0
+            .get(package_name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn matches_version(&self, package_name: &str, version: &semver::Version) -> bool {
+        self.get(package_name)
+            .iter()
+            .all(|requirement| requirement.version.matches(version))
+    }
+
+    /// A manifest without a version only matches a package nobody has version requirements on.
+    pub(crate) fn matches(&self, package_name: &str, manifest: &fastn_core::Manifest) -> bool {
+        if self.get(package_name).is_empty() {
+            return true;
+        }
+        match manifest
+            .version
+            .as_deref()
+            .and_then(|v| semver::Version::parse(v).ok())
+        {
+            Some(version) => self.matches_version(package_name, &version),
+            None => false,
+        }
+    }
+
+    /// eg "fifthtry.github.io/a needs foo.com/bar ^0.3, fifthtry.github.io/b needs foo.com/bar ^0.5"
+    pub(crate) fn describe(&self, package_name: &str) -> String {
+        self.get(package_name)
+            .iter()
+            .map(|requirement| {
+                format!(
+                    "{} needs {} {}",
+                    requirement.required_by, package_name, requirement.version
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Picks the manifest of the highest published version of the package (listed in its
+/// `versions.json`) that satisfies all `requirements`, see `pick_version()`.
+pub(crate) async fn get_manifest(
+    package_name: &str,
+    requirements: &Requirements,
+    locked: Option<&fastn_update::LockedPackage>,
+) -> Result<(fastn_core::Manifest, Vec<u8>), fastn_update::VersionError> {
+    let versions_bytes = fastn_core::http::http_get(&format!(
+        "https://{}/{}",
+        package_name,
+        fastn_core::manifest::VERSIONS_FILE
+    ))
+    .await
+    .context(fastn_update::DownloadVersionsSnafu {
+        package: package_name,
+    })?;
+    let versions: fastn_core::manifest::Versions = serde_json::de::from_slice(&versions_bytes)
+        .context(fastn_update::DeserializeVersionsSnafu {
+            package: package_name,
+        })?;
+
+    let (version, mut manifest) = pick_version(package_name, versions, requirements, locked)?;
+    manifest.version = Some(version.to_string());
+
+    let manifest_bytes =
+        serde_json::ser::to_vec_pretty(&manifest).context(fastn_update::SerializeVersionSnafu {
+            package: package_name,
+        })?;
+
+    Ok((manifest, manifest_bytes))
+}
+
+/// The highest of `versions` that satisfies all `requirements`. The locked version is picked
+/// instead if it still satisfies them.
+fn pick_version(
+    package_name: &str,
+    versions: fastn_core::manifest::Versions,
+    requirements: &Requirements,
+    locked: Option<&fastn_update::LockedPackage>,
+) -> Result<(semver::Version, fastn_core::Manifest), fastn_update::VersionError> {
+    let mut candidates = vec![];
+    for (version, manifest) in versions {
+        let parsed = semver::Version::parse(version.as_str()).context(
+            fastn_update::InvalidVersionSnafu {
+                package: package_name,
+                version: version.as_str(),
+            },
+        )?;
+        if requirements.matches_version(package_name, &parsed) {
+            candidates.push((parsed, manifest));
+        }
+    }
+
+    let locked_candidate = locked.and_then(|locked| {
+        candidates
+            .iter()
+            .position(|(_, manifest)| manifest.checksum.eq(&locked.checksum))
+    });
+    match locked_candidate {
+        Some(index) => Ok(candidates.swap_remove(index)),
+        None => candidates
+            .into_iter()
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .context(fastn_update::NoMatchingVersionSnafu {
+                package: package_name,
+                requirements: requirements.describe(package_name),
+            }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    fn manifest(checksum: &str) -> fastn_core::Manifest {
+        fastn_core::Manifest::new(
+            Default::default(),
+            format!("https://foo.com/{checksum}.zip"),
+            checksum.to_string(),
+            None,
+        )
+    }
+
+    fn versions() -> fastn_core::manifest::Versions {
+        ["0.2.0", "0.3.0", "0.3.4", "0.4.1", "0.5.0"]
+            .into_iter()
+            .map(|v| (v.to_string(), manifest(v)))
+            .collect()
+    }
+
+    fn requirement(required_by: &str, version: &str) -> super::Requirement {
+        super::Requirement {
+            required_by: required_by.to_string(),
+            version: semver::VersionReq::parse(version).unwrap(),
+        }
+    }
+
+    fn pick(
+        requirements: &super::Requirements,
+        locked: Option<&str>,
+    ) -> Result<String, fastn_update::VersionError> {
+        let locked = locked.map(|checksum| fastn_update::LockedPackage {
+            zip_url: format!("https://foo.com/{checksum}.zip"),
+            checksum: checksum.to_string(),
+        });
+        super::pick_version("foo.com", versions(), requirements, locked.as_ref())
+            .map(|(version, _)| version.to_string())
+    }
+
+    #[test]
+    fn pick_version() {
+        let mut requirements = super::Requirements::default();
+        // nobody asked for a version
+        assert_eq!(pick(&requirements, None).unwrap(), "0.5.0");
+
+        requirements.add("foo.com", requirement("a.com", ">= 0.3, < 0.5"));
+        assert_eq!(pick(&requirements, None).unwrap(), "0.4.1");
+        // the locked version is kept while it satisfies the requirements
+        assert_eq!(pick(&requirements, Some("0.3.0")).unwrap(), "0.3.0");
+        assert_eq!(pick(&requirements, Some("0.2.0")).unwrap(), "0.4.1");
+
+        requirements.add("foo.com", requirement("b.com", "^0.3"));
+        assert_eq!(pick(&requirements, None).unwrap(), "0.3.4");
+
+        requirements.add("foo.com", requirement("c.com", "^0.5"));
+        assert_eq!(
+            pick(&requirements, None).unwrap_err().to_string(),
+            "No published version of package 'foo.com' satisfies all requirements: \
+            a.com needs foo.com >=0.3, <0.5, b.com needs foo.com ^0.3, \
+            c.com needs foo.com ^0.5"
+        );
+    }
+
+    #[test]
+    fn requirements_matches() {
+        let mut requirements = super::Requirements::default();
+        let mut unversioned = manifest("A");
+        // a manifest without a version matches only when nobody asked for one
+        assert!(requirements.matches("foo.com", &unversioned));
+
+        requirements.add("foo.com", requirement("a.com", "^0.3"));
+        // added twice, described once
+        requirements.add("foo.com", requirement("a.com", "^0.3"));
+        assert_eq!(requirements.describe("foo.com"), "a.com needs foo.com ^0.3");
+        assert!(!requirements.matches("foo.com", &unversioned));
+
+        unversioned.version = Some("0.3.1".to_string());
+        assert!(requirements.matches("foo.com", &unversioned));
+        unversioned.version = Some("0.4.0".to_string());
+        assert!(!requirements.matches("foo.com", &unversioned));
+        // other packages are not affected
+        assert!(requirements.matches("bar.com", &unversioned));
+    }
+}
@@ -42,11 +42,14 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
         return Ok(());
     }
 
+    let current_dir: camino::Utf8PathBuf = std::env::current_dir()?.canonicalize()?.try_into()?;
+    let ds = fastn_ds::DocumentStore::new(current_dir);
+
     if let Ok(auth_enabled) = std::env::var("FASTN_ENABLE_AUTH") {
         if auth_enabled == "true" {
             tracing::info!("running auth related migrations");
             let db_url = std::env::var("FASTN_DB_URL")?;
-            fastn_core::db::migrate(db_url).await?;
+            fastn_core::db::migrate(&ds, db_url).await?;
         }
     }
 
@@ -59,12 +62,23 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
         return fastn_core::create_package(name, path, download_base_url).await;
     }
 
-    let current_dir: camino::Utf8PathBuf = std::env::current_dir()?.canonicalize()?.try_into()?;
-    let ds = fastn_ds::DocumentStore::new(current_dir);
-
     if let Some(update) = matches.subcommand_matches("update") {
         let check = update.get_flag("check");
-        return fastn_update::update(&ds, false, check).await;
+        let archive = update.get_flag("archive");
+        let upgrade = update.get_flag("upgrade");
+        let offline = update.get_flag("offline");
+        let vendor = update.get_flag("vendor");
+        return fastn_update::update(
+            &ds,
+            fastn_update::UpdateOptions {
+                offline,
+                check,
+                archive,
+                upgrade,
+                vendor,
+            },
+        )
+        .await;
     }
 
     if let Some(serve) = matches.subcommand_matches("serve") {
@@ -85,7 +99,14 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
         let inline_css = serve.values_of_("css");
         let offline = serve.get_flag("offline");
 
-        fastn_update::update(&ds, offline, false).await?;
+        fastn_update::update(
+            &ds,
+            fastn_update::UpdateOptions {
+                offline,
+                ..Default::default()
+            },
+        )
+        .await?;
 
         let config = fastn_core::Config::read(ds, false)
             .await?
@@ -112,7 +133,14 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
         let inline_css = test.values_of_("css");
         let offline: bool = test.get_flag("offline");
 
-        fastn_update::update(&ds, offline, false).await?;
+        fastn_update::update(
+            &ds,
+            fastn_update::UpdateOptions {
+                offline,
+                ..Default::default()
+            },
+        )
+        .await?;
 
         let mut config = fastn_core::Config::read(ds, true).await?;
 
@@ -148,7 +176,14 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
         let zip_url = build.value_of_("zip-url");
         let offline: bool = build.get_flag("offline");
 
-        fastn_update::update(&ds, offline, false).await?;
+        fastn_update::update(
+            &ds,
+            fastn_update::UpdateOptions {
+                offline,
+                ..Default::default()
+            },
+        )
+        .await?;
 
         let mut config = fastn_core::Config::read(ds, true).await?;
 
@@ -167,6 +202,7 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
             matches.get_flag("test"),
             build.get_flag("check-build"),
             zip_url,
+            build.get_flag("explain"),
         )
         .await;
     }
@@ -187,7 +223,10 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
         .await;
     }
 
-    if matches.subcommand_matches("check").is_some() {
+    if let Some(check) = matches.subcommand_matches("check") {
+        if check.get_flag("queries") {
+            fastn_core::check_queries(&config).await?;
+        }
         return fastn_core::post_build_check(&config).await;
     }
 
@@ -271,6 +310,7 @@ fn app(version: &'static str) -> clap::Command {
                 .arg(clap::arg!(--"zip-url" <URL> "The zip archive url for this package"))
                 .arg(clap::arg!(--"ignore-failed" "Ignore failed files."))
                 .arg(clap::arg!(--"check-build" "Checks .build for index files validation."))
+                .arg(clap::arg!(--explain "Prints why each document is built again, or is up to date."))
                 .arg(clap::arg!(--"external-js" <URL> "Script added in ftd files")
                     .action(clap::ArgAction::Append))
                 .arg(clap::arg!(--"js" <URL> "Script text added in ftd files")
@@ -318,12 +358,17 @@ fn app(version: &'static str) -> clap::Command {
         .subcommand(
             clap::Command::new("check")
                 .about("Check if everything is fine with current fastn package")
+                .arg(clap::arg!(--queries "Also check the sql, pg and package-query queries of the package against its databases, which have to be reachable"))
                 .hide(true) // hidden since the feature is not being released yet.
         )
         .subcommand(
             clap::Command::new("update")
                 .about("Update dependency packages for this fastn package")
                 .arg(clap::arg!(--check "Check if packages are in sync with FASTN.ftd without performing updates."))
+                .arg(clap::arg!(--archive "Keep downloaded packages as zip archives in .packages instead of unpacking them."))
+                .arg(clap::arg!(--upgrade "Ignore fastn.lock, update packages to their latest published versions and rewrite the lock."))
+                .arg(clap::arg!(--offline "Install missing packages from the package cache shared by all projects, without network access."))
+                .arg(clap::arg!(--vendor "Copy all dependencies and their lock into vendor/, to be committed. Vendored packages are used instead of downloading them.").conflicts_with("offline"))
         )
         .subcommand(sub_command::serve())
 }
@@ -1893,6 +1893,131 @@ impl<'a> TDoc<'a> {
         )
     }
 
+    /// Like `rows_to_value()`, but columns are mapped to the fields of records by name, see
+    /// `named_row_to_value()`.
+    pub fn named_rows_to_value(
+        &self,
+        columns: &[String],
+        rows: &[Vec<serde_json::Value>],
+        kind: &ftd::interpreter::Kind,
+        value: &ftd::ast::VariableValue,
+    ) -> ftd::interpreter::Result<ftd::interpreter::Value> {
+        match kind {
+            ftd::interpreter::Kind::List { kind, .. } => {
+                let mut data = vec![];
+                for row in rows {
+                    data.push(
+                        self.named_row_to_value(columns, row, kind, value)?
+                            .into_property_value(false, value.line_number()),
+                    );
+                }
+
+                Ok(ftd::interpreter::Value::List {
+                    data,
+                    kind: kind.to_owned().into_kind_data(),
+                })
+            }
+            t => ftd::interpreter::utils::e2(
+                format!("expected a list, found: {:?}", t),
+                self.name,
+                value.line_number(),
+            ),
+        }
+    }
+
+    /// Converts `row`, eg of a database query, whose columns are named `columns`, to `kind`.
+    ///
+    /// A column is mapped to the record field of the same name, `full_name` or `full-name` for
+    /// the field `full-name`. A field without a column, or whose column is NULL, gets its default
+    /// value, if it has one, `NULL` if it is optional, and an empty list if it is a list, else
+    /// it is an error.
+    pub fn named_row_to_value(
+        &self,
+        columns: &[String],
+        row: &[serde_json::Value],
+        kind: &ftd::interpreter::Kind,
+        value: &ftd::ast::VariableValue,
+    ) -> ftd::interpreter::Result<ftd::interpreter::Value> {
+        match kind {
+            ftd::interpreter::Kind::Record { name } => {
+                self.named_row_to_record(columns, row, name, value)
+            }
+            ftd::interpreter::Kind::Optional { kind, .. }
+                if matches!(kind.as_ref(), ftd::interpreter::Kind::Record { .. }) =>
+            {
+                self.named_row_to_value(columns, row, kind, value)
+            }
+            _ => self.row_to_value(row, kind, value),
+        }
+    }
+
+    fn named_row_to_record(
+        &self,
+        columns: &[String],
+        row: &[serde_json::Value],
+        name: &str,
+        value: &ftd::ast::VariableValue,
+    ) -> ftd::interpreter::Result<ftd::interpreter::Value> {
+        let line_number = value.line_number();
+        let rec = self.get_record(name, line_number)?;
+        let mut fields: ftd::Map<ftd::interpreter::PropertyValue> = Default::default();
+        for field in rec.fields {
+            let column = columns.iter().position(|column| {
+                ftd::interpreter::utils::is_column_of_field(column, field.name.as_str())
+            });
+            let val = match column.and_then(|idx| row.get(idx)) {
+                Some(serde_json::Value::Null) | None => {
+                    if let Some(default) = field.value {
+                        fields.insert(field.name, default);
+                        continue;
+                    }
+                    if field.kind.is_optional() {
+                        serde_json::Value::Null
+                    } else if field.kind.is_list() {
+                        serde_json::Value::Array(vec![])
+                    } else if let Some(idx) = column {
+                        return ftd::interpreter::utils::e2(
+                            format!(
+                                "column `{}` is NULL, but `{}.{}` is not optional",
+                                columns[idx], name, field.name
+                            ),
+                            self.name,
+                            line_number,
+                        );
+                    } else {
+                        return ftd::interpreter::utils::e2(
+                            format!(
+                                "no column for `{}.{}`, the columns are: {}",
+                                name,
+                                field.name,
+                                columns.join(", ")
+                            ),
+                            self.name,
+                            line_number,
+                        );
+                    }
+                }
+                Some(v) => v.to_owned(),
+            };
+            let field_value = self
+                .as_json_(&field.kind.kind, &val, None, None, line_number)
+                .map_err(|e| ftd::interpreter::Error::ParseError {
+                    message: format!("`{}.{}`: {}", name, field.name, e),
+                    doc_id: self.name.to_string(),
+                    line_number,
+                })?;
+            fields.insert(
+                field.name,
+                field_value.into_property_value(false, line_number),
+            );
+        }
+
+        Ok(ftd::interpreter::Value::Record {
+            name: name.to_string(),
+            fields,
+        })
+    }
+
     pub fn from_json<T>(
         &self,
         json: &T,
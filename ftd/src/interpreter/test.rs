@@ -215,3 +215,94 @@ fn test_extract_kwargs() {
     assert_eq!(data.get("bar"), Some(&String::from("Hello")));
     assert_eq!(data.get("baz"), Some(&String::from("World")));
 }
+
+#[test]
+fn named_row_to_value() {
+    let doc = ftd::parse_doc(
+        "foo",
+        r#"
+            -- record person:
+            string full-name:
+            integer age: 0
+            optional string email:
+            string list tags:
+        "#,
+    )
+    .unwrap();
+    let doc = doc.tdoc();
+    let kind = ftd::interpreter::Kind::record("foo#person");
+    let value = ftd::ast::VariableValue::Constant {
+        value: String::new(),
+        line_number: 0,
+        source: ftd::ast::ValueSource::Default,
+        condition: None,
+    };
+    let columns = ["EMAIL", "Full_Name"].map(String::from);
+
+    let person = doc
+        .named_row_to_value(
+            &columns,
+            &[serde_json::Value::Null, serde_json::json!("Alice")],
+            &kind,
+            &value,
+        )
+        .unwrap()
+        .to_serde_value(&doc)
+        .unwrap();
+    assert_eq!(
+        person,
+        Some(serde_json::json!({
+            "full-name": "Alice",
+            "age": 0,
+            "email": null,
+            "tags": [],
+        }))
+    );
+
+    let error = doc
+        .named_row_to_value(
+            &columns,
+            &[
+                serde_json::json!("alice@example.com"),
+                serde_json::Value::Null,
+            ],
+            &kind,
+            &value,
+        )
+        .unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("column `Full_Name` is NULL, but `foo#person.full-name` is not optional"));
+
+    let error = doc
+        .named_row_to_value(
+            &["email".to_string()],
+            &[serde_json::Value::Null],
+            &kind,
+            &value,
+        )
+        .unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("no column for `foo#person.full-name`, the columns are: email"));
+}
+
+#[test]
+fn is_column_of_field() {
+    assert!(ftd::interpreter::utils::is_column_of_field(
+        "full_name",
+        "full-name"
+    ));
+    assert!(ftd::interpreter::utils::is_column_of_field(
+        "Full-Name",
+        "full-name"
+    ));
+    assert!(!ftd::interpreter::utils::is_column_of_field(
+        "fullname",
+        "full-name"
+    ));
+    assert!(!ftd::interpreter::utils::is_column_of_field(
+        "full-name",
+        "full_name"
+    ));
+}
@@ -908,3 +908,9 @@ pub fn get_children_properties_from_properties(
         })
         .collect_vec()
 }
+
+/// Whether the column `column` of a database query maps to the record field `field`: they
+/// have the same name, ignoring case, or `full_name` for the field `full-name`.
+pub fn is_column_of_field(column: &str, field: &str) -> bool {
+    column.eq_ignore_ascii_case(field) || column.replace('_', "-").eq_ignore_ascii_case(field)
+}
@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS fastn_password_reset;
+DROP TABLE IF EXISTS fastn_email_confirmation;
+DROP TABLE IF EXISTS fastn_user_email;
+DROP TABLE IF EXISTS fastn_oauthtoken;
+DROP TABLE IF EXISTS fastn_auth_session;
+DROP TABLE IF EXISTS fastn_user;
@@ -0,0 +1,64 @@
+-- The sqlite version of `migrations/2023-12-18-114245_fastn_auth_tables`, for
+-- `FASTN_DB_URL=sqlite:///auth.db`. Emails compare case insensitively, like
+-- `citext` on postgres. Timestamps are stored as text, eg
+-- `2023-12-18 11:42:45.000000+00:00`.
+
+-- registered user
+CREATE TABLE IF NOT EXISTS fastn_user (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username TEXT UNIQUE NOT NULL,
+    password TEXT NOT NULL,
+    email TEXT COLLATE NOCASE NOT NULL UNIQUE, -- de-normalised data to avoid joins
+    verified_email BOOLEAN DEFAULT FALSE NOT NULL,
+    name TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+-- logged in user session store
+CREATE TABLE IF NOT EXISTS fastn_auth_session (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER REFERENCES fastn_user(id) ON DELETE CASCADE NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+-- token from oauth apps
+CREATE TABLE IF NOT EXISTS fastn_oauthtoken (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id INTEGER REFERENCES fastn_auth_session(id) ON DELETE CASCADE NOT NULL,
+    token TEXT NOT NULL,
+    provider TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+-- user emails
+CREATE TABLE IF NOT EXISTS fastn_user_email (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER REFERENCES fastn_user(id) ON DELETE CASCADE NOT NULL,
+    email TEXT COLLATE NOCASE NOT NULL UNIQUE,
+    verified BOOLEAN DEFAULT FALSE NOT NULL,
+    "primary" BOOLEAN DEFAULT FALSE NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+-- email confirmations. Can't log in without confirming email first
+CREATE TABLE IF NOT EXISTS fastn_email_confirmation (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    email_id INTEGER REFERENCES fastn_user_email(id) ON DELETE CASCADE NOT NULL,
+    session_id INTEGER REFERENCES fastn_auth_session(id) ON DELETE CASCADE NOT NULL,
+    created_at TEXT NOT NULL,
+    sent_at TEXT NOT NULL, -- to check expiration
+    "key" TEXT UNIQUE NOT NULL -- for verification
+);
+
+-- fastn_password_reset
+CREATE TABLE IF NOT EXISTS fastn_password_reset (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER REFERENCES fastn_user(id) ON DELETE CASCADE NOT NULL,
+    created_at TEXT DEFAULT (strftime('%Y-%m-%d %H:%M:%f+00:00', 'now')) NOT NULL,
+    sent_at TEXT NOT NULL, -- to check expiration
+    "key" TEXT UNIQUE NOT NULL -- for verification
+);
@@ -0,0 +1,3 @@
+ALTER TABLE fastn_auth_session DROP COLUMN user_agent;
+ALTER TABLE fastn_auth_session DROP COLUMN revoked_at;
+ALTER TABLE fastn_auth_session DROP COLUMN expires_at;
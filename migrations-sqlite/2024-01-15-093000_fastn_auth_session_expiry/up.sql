@@ -0,0 +1,8 @@
+-- The sqlite version of `migrations/2024-01-15-093000_fastn_auth_session_expiry`.
+ALTER TABLE fastn_auth_session ADD COLUMN expires_at TEXT; -- NULL until the session cookie is set
+ALTER TABLE fastn_auth_session ADD COLUMN revoked_at TEXT;
+ALTER TABLE fastn_auth_session ADD COLUMN user_agent TEXT;
+
+-- existing sessions expire 30 days after they were created, the default
+UPDATE fastn_auth_session
+SET expires_at = strftime('%Y-%m-%d %H:%M:%f+00:00', created_at, '+30 days');
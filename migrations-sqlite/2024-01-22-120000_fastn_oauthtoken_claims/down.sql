@@ -0,0 +1 @@
+ALTER TABLE fastn_oauthtoken DROP COLUMN claims;
@@ -0,0 +1,2 @@
+-- The sqlite version of `migrations/2024-01-22-120000_fastn_oauthtoken_claims`.
+ALTER TABLE fastn_oauthtoken ADD COLUMN claims TEXT;
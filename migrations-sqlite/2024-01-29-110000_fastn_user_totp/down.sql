@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS fastn_user_recovery_code;
+ALTER TABLE fastn_user DROP COLUMN totp_enabled_at;
+ALTER TABLE fastn_user DROP COLUMN totp_secret;
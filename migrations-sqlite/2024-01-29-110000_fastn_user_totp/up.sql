@@ -0,0 +1,11 @@
+-- The sqlite version of `migrations/2024-01-29-110000_fastn_user_totp`.
+ALTER TABLE fastn_user ADD COLUMN totp_secret TEXT;
+ALTER TABLE fastn_user ADD COLUMN totp_enabled_at TEXT;
+
+CREATE TABLE IF NOT EXISTS fastn_user_recovery_code (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER REFERENCES fastn_user(id) ON DELETE CASCADE NOT NULL,
+    code TEXT NOT NULL,
+    used_at TEXT,
+    created_at TEXT NOT NULL
+);
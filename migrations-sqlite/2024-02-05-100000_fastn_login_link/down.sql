@@ -0,0 +1 @@
+DROP TABLE IF EXISTS fastn_login_link;
@@ -0,0 +1,8 @@
+-- The sqlite version of `migrations/2024-02-05-100000_fastn_login_link`.
+CREATE TABLE IF NOT EXISTS fastn_login_link (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER REFERENCES fastn_user(id) ON DELETE CASCADE NOT NULL,
+    created_at TEXT DEFAULT (strftime('%Y-%m-%d %H:%M:%f+00:00', 'now')) NOT NULL,
+    sent_at TEXT NOT NULL, -- to check expiration
+    "key" TEXT UNIQUE NOT NULL -- for verification
+);
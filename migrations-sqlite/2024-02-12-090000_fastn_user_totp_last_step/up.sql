@@ -0,0 +1,2 @@
+-- The sqlite version of `migrations/2024-02-12-090000_fastn_user_totp_last_step`.
+ALTER TABLE fastn_user ADD COLUMN totp_last_step INTEGER;
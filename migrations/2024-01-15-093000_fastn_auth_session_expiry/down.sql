@@ -0,0 +1,4 @@
+ALTER TABLE fastn_auth_session
+    DROP COLUMN expires_at,
+    DROP COLUMN revoked_at,
+    DROP COLUMN user_agent;
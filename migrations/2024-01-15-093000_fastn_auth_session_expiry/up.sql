@@ -0,0 +1,9 @@
+-- sessions expire when they are not used for a while, or some time after they are created,
+-- see `fastn_core::auth::session`, and can be revoked, eg to log out everywhere
+ALTER TABLE fastn_auth_session
+    ADD COLUMN expires_at TIMESTAMP WITH TIME ZONE, -- NULL until the session cookie is set
+    ADD COLUMN revoked_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN user_agent TEXT;
+
+-- existing sessions expire 30 days after they were created, the default
+UPDATE fastn_auth_session SET expires_at = created_at + INTERVAL '30 days';
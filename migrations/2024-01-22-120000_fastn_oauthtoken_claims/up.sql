@@ -0,0 +1,3 @@
+-- the claims of the user from an OpenID Connect provider, as json, read when matching user
+-- group identities, eg `keycloak-groups: admins`, after the access token has expired
+ALTER TABLE fastn_oauthtoken ADD COLUMN claims TEXT;
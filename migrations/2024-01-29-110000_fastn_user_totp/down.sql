@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS fastn_user_recovery_code;
+ALTER TABLE fastn_user DROP COLUMN totp_secret, DROP COLUMN totp_enabled_at;
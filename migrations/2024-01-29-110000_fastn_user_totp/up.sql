@@ -0,0 +1,13 @@
+-- two-factor authentication of email/password users, see `fastn_core::auth::email_password::totp`
+ALTER TABLE fastn_user
+    ADD COLUMN totp_secret TEXT, -- encrypted with FASTN_SECRET_KEY, set when enrolling
+    ADD COLUMN totp_enabled_at TIMESTAMP WITH TIME ZONE; -- NULL until the first code is entered
+
+-- one-time codes to log in without the authenticator app, only their hashes are stored
+CREATE TABLE IF NOT EXISTS fastn_user_recovery_code (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT REFERENCES fastn_user(id) ON DELETE CASCADE NOT NULL,
+    code TEXT NOT NULL,
+    used_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
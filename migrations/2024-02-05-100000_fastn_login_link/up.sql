@@ -0,0 +1,9 @@
+-- single use links, sent by email, to log in without a password, see
+-- `fastn_core::auth::email_password::login_link`
+create table if not exists fastn_login_link(
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT REFERENCES fastn_user(id) ON DELETE CASCADE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() NOT NULL,
+    sent_at TIMESTAMP WITH TIME ZONE NOT NULL, -- to check expiration
+    "key" TEXT UNIQUE NOT NULL -- for verification
+);
@@ -0,0 +1 @@
+ALTER TABLE fastn_user DROP COLUMN totp_last_step;
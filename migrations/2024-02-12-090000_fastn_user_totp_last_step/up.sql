@@ -0,0 +1,3 @@
+-- the time step of the last code accepted, a code can not be used twice, see
+-- https://datatracker.ietf.org/doc/html/rfc6238#section-5.2
+ALTER TABLE fastn_user ADD COLUMN totp_last_step BIGINT;